@@ -29,12 +29,15 @@ async fn main() {
         guild_id,
         action: EnforcementAction::VoiceMute { duration: Some(2) },
         execute_at,
-        reverse_at: Some(reverse_at.clone()),
+        reverse_at: Some(reverse_at),
         state: EnforcementState::Pending,
-        created_at: now.clone(),
+        created_at: now,
         executed_at: None,
         reversed_at: None,
         executed: false,
+        attempts: 0,
+        last_error: None,
+        haunt: None,
     };
 
     // 2. Create a one-time enforcement (no reversal needed)
@@ -47,13 +50,16 @@ async fn main() {
         user_id,
         guild_id,
         action: EnforcementAction::VoiceDisconnect { delay: Some(0) },
-        execute_at: now.clone(),
+        execute_at: now,
         reverse_at: None,
         state: EnforcementState::Pending,
-        created_at: now.clone(),
+        created_at: now,
         executed_at: None,
         reversed_at: None,
         executed: false,
+        attempts: 0,
+        last_error: None,
+        haunt: None,
     };
 
     // Add enforcements to the pending map
@@ -76,7 +82,7 @@ async fn main() {
     if let Some(mut pending) = data.pending_enforcements.get_mut(&enforcement_id_1) {
         println!("Found enforcement with id: {}", pending.id);
         pending.state = EnforcementState::Active;
-        pending.executed_at = Some(now.clone());
+        pending.executed_at = Some(now);
         pending.executed = true;
 
         // Clone it and move it to active
@@ -95,7 +101,7 @@ async fn main() {
     if let Some(mut pending) = data.pending_enforcements.get_mut(&enforcement_id_2) {
         println!("Found enforcement with id: {}", pending.id);
         pending.state = EnforcementState::Completed; // One-time actions go directly to completed
-        pending.executed_at = Some(now.clone());
+        pending.executed_at = Some(now);
         pending.executed = true;
 
         // Clone it and move it to completed
@@ -52,6 +52,9 @@ fn main() {
         executed_at: None,
         reversed_at: None,
         executed: false,
+        attempts: 0,
+        last_error: None,
+        haunt: None,
     };
 
     // 2. Create a one-time enforcement (no reversal needed)
@@ -71,6 +74,9 @@ fn main() {
         executed_at: None,
         reversed_at: None,
         executed: false,
+        attempts: 0,
+        last_error: None,
+        haunt: None,
     };
 
     // Add enforcements to the pending map
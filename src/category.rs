@@ -0,0 +1,262 @@
+//! Infraction categories
+//!
+//! Every warning belongs to a category, which decides how it is scored, which
+//! punishments it leads to and how it is logged. `voice`, `text` and `server`
+//! are built in, and each guild can define its own categories, such as
+//! `harassment` or `spam`, with their own default punishment, severity and log
+//! formatting.
+
+use crate::data::{EnforcementAction, GuildConfig};
+use crate::scoring;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Longest name a custom category can have
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Most custom categories a guild can define
+pub const MAX_CUSTOM_CATEGORIES: usize = 20;
+
+/// The category of an infraction, stored by name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum InfractionCategory {
+    Voice,
+    Text,
+    Server,
+    /// A category the guild defined
+    Custom(String),
+}
+
+impl InfractionCategory {
+    /// Categories every guild has
+    pub const BUILT_IN: [Self; 3] = [Self::Voice, Self::Text, Self::Server];
+
+    /// The category's name, as stored and typed in commands
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Voice => "voice",
+            Self::Text => "text",
+            Self::Server => "server",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Look up a category by name among the built-in ones and the guild's own
+    ///
+    /// # Errors
+    /// Returns an error listing the guild's categories if there is no such category.
+    pub fn parse(name: &str, config: &GuildConfig) -> Result<Self, String> {
+        let category = Self::from(name.trim().to_lowercase());
+        match &category {
+            Self::Custom(name) if !config.custom_categories.contains_key(name) => Err(format!(
+                "Unknown infraction category `{name}`. Use one of: {}.",
+                Self::all(config)
+                    .iter()
+                    .map(|category| format!("`{category}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => Ok(category),
+        }
+    }
+
+    /// The built-in categories followed by the guild's own
+    #[must_use]
+    pub fn all(config: &GuildConfig) -> Vec<Self> {
+        Self::BUILT_IN
+            .into_iter()
+            .chain(config.custom_categories.keys().cloned().map(Self::Custom))
+            .collect()
+    }
+
+    /// The guild's definition of a custom category
+    #[must_use]
+    pub fn custom<'a>(&self, config: &'a GuildConfig) -> Option<&'a CustomCategory> {
+        match self {
+            Self::Custom(name) => config.custom_categories.get(name),
+            _ => None,
+        }
+    }
+
+    /// Punishment a warning in this category leads to, before any escalation
+    #[must_use]
+    pub fn default_enforcement(&self, config: &GuildConfig) -> Option<EnforcementAction> {
+        self.custom(config)
+            .and_then(|custom| custom.default_enforcement.clone())
+            .or_else(|| config.default_enforcement.clone())
+    }
+
+    /// Severity of a warning in this category when the moderator doesn't give one
+    #[must_use]
+    pub fn default_severity(&self, config: &GuildConfig) -> f64 {
+        self.custom(config)
+            .map_or(scoring::SEVERITY_MODERATE, |custom| custom.severity)
+    }
+
+    /// Title and emoji for the category's altar log entries
+    #[must_use]
+    pub fn log_style(&self, config: &GuildConfig) -> (String, String) {
+        match self {
+            Self::Text => ("Text Channel".to_string(), "💬".to_string()),
+            Self::Server => ("Server Rule".to_string(), "⚠️".to_string()),
+            Self::Voice => ("General".to_string(), "⚠️".to_string()),
+            Self::Custom(name) => {
+                let custom = self.custom(config);
+                let title = custom
+                    .and_then(|custom| custom.title.clone())
+                    .unwrap_or_else(|| capitalize(name));
+                let emoji = custom
+                    .and_then(|custom| custom.emoji.clone())
+                    .unwrap_or_else(|| "⚠️".to_string());
+                (title, emoji)
+            }
+        }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+impl From<String> for InfractionCategory {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "voice" => Self::Voice,
+            "text" => Self::Text,
+            "server" => Self::Server,
+            _ => Self::Custom(name),
+        }
+    }
+}
+
+impl From<InfractionCategory> for String {
+    fn from(category: InfractionCategory) -> Self {
+        category.name().to_string()
+    }
+}
+
+impl Display for InfractionCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A category a guild defined for itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomCategory {
+    /// Punishment for warnings in the category, falling back to the guild's default
+    #[serde(default)]
+    pub default_enforcement: Option<EnforcementAction>,
+    /// Severity of warnings in the category when the moderator doesn't give one
+    #[serde(default = "default_severity")]
+    pub severity: f64,
+    /// Emoji shown on the category's altar log entries
+    #[serde(default)]
+    pub emoji: Option<String>,
+    /// Title of the category's altar log entries, the capitalized name if unset
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl Default for CustomCategory {
+    fn default() -> Self {
+        Self {
+            default_enforcement: None,
+            severity: scoring::SEVERITY_MODERATE,
+            emoji: None,
+            title: None,
+        }
+    }
+}
+
+fn default_severity() -> f64 {
+    scoring::SEVERITY_MODERATE
+}
+
+/// Check a name for a new custom category
+///
+/// # Errors
+/// Returns an error if the name is taken by a built-in category or isn't a
+/// short lowercase name.
+pub fn validate_custom_name(name: &str) -> Result<(), String> {
+    if InfractionCategory::BUILT_IN
+        .iter()
+        .any(|category| category.name() == name)
+    {
+        return Err(format!("`{name}` is a built-in category"));
+    }
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Category names are up to {MAX_NAME_LENGTH} lowercase letters, digits, `_` or `-`"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_style_categories() {
+        let mut config = GuildConfig::new(11111);
+        config.custom_categories.insert(
+            "harassment".to_string(),
+            CustomCategory {
+                default_enforcement: Some(EnforcementAction::Ban {
+                    duration: Some(86400),
+                }),
+                severity: scoring::SEVERITY_SEVERE,
+                emoji: Some("🛑".to_string()),
+                title: None,
+            },
+        );
+
+        assert_eq!(
+            InfractionCategory::parse(" Voice ", &config),
+            Ok(InfractionCategory::Voice)
+        );
+        let harassment = InfractionCategory::parse("harassment", &config).unwrap();
+        assert!(
+            InfractionCategory::parse("spam", &config)
+                .unwrap_err()
+                .contains("`harassment`")
+        );
+
+        assert!(
+            (harassment.default_severity(&config) - scoring::SEVERITY_SEVERE).abs() < f64::EPSILON
+        );
+        assert_eq!(
+            harassment.default_enforcement(&config),
+            Some(EnforcementAction::Ban {
+                duration: Some(86400)
+            })
+        );
+        assert_eq!(
+            harassment.log_style(&config),
+            ("Harassment".to_string(), "🛑".to_string())
+        );
+
+        // Stored as plain names
+        let yaml = serde_yaml::to_string(&harassment).unwrap();
+        assert_eq!(yaml.trim(), "harassment");
+        assert_eq!(
+            serde_yaml::from_str::<InfractionCategory>("text").unwrap(),
+            InfractionCategory::Text
+        );
+
+        assert!(validate_custom_name("spam").is_ok());
+        assert!(validate_custom_name("voice").is_err());
+        assert!(validate_custom_name("Hate Speech").is_err());
+    }
+}
@@ -1,22 +1,29 @@
 use crate::{
+    category::{self, InfractionCategory},
     data::{
         Data, EnforcementAction, EnforcementState, GuildConfig, NotificationMethod,
         PendingEnforcement, UserWarningState, Warning, WarningContext,
     },
-    enforcement::EnforcementCheckRequest,
+    enforcement::{self, EnforcementCheckRequest},
+    escalation,
+    judgment::{self, Judgment},
+    moderation::SerenityBackend,
+    policy::{self, Policy},
+    punishment::PunishmentTable,
+    retention,
+    scoring::{self, ScoringConfig},
     status::format_complete_status,
+    transfer::{GuildExport, TransferFormat},
 };
 type Error = Box<dyn std::error::Error + Send + Sync>;
 use ::serenity::all::CacheHttp;
-use chrono::{DateTime, Duration, Utc};
+use chrono::Utc;
 use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::{Colour, CreateEmbed, CreateMessage, Mentionable, Timestamp, User};
 use poise::{Context, command};
+use rand::Rng;
 use tracing::{error, info, warn};
 use uuid::Uuid;
-// Determine if enforcement should be triggered
-// Threshold is 2.0 (roughly 2 recent warnings)
-const WARNING_THRESHOLD: f64 = 2.0;
 
 /// Basic ping command
 /// This command is used to check if the bot is responsive.
@@ -26,6 +33,57 @@ pub async fn ping(ctx: Context<'_, Data, Error>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Commands that only read the daemon's records, so they still run read-only
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "ping",
+    "daemon_preview",
+    "judgment_history",
+    "daemon_status",
+    "daemon_replay",
+    "policy_explain",
+    "daemon_export",
+];
+
+/// Refuse commands that change records while the data is loaded read-only
+///
+/// Nothing is saved in read-only mode, so a change would quietly disappear on
+/// the next restart.
+///
+/// # Errors
+/// Returns an error if the refusal can't be sent.
+pub async fn check_writable(ctx: Context<'_, Data, Error>) -> Result<bool, Error> {
+    if !ctx.data().read_only || READ_ONLY_COMMANDS.contains(&ctx.command().name.as_str()) {
+        return Ok(true);
+    }
+    ctx.say("**[DAEMON SEALED]** The daemon's records were found damaged and it started read-only, so nothing can be changed until they are repaired and the bot restarted.")
+        .await?;
+    Ok(false)
+}
+
+/// Post a ritual's new settings to the guild's altar, if it changed anything
+async fn log_ritual_to_altar(
+    ctx: Context<'_, Data, Error>,
+    guild_config: &GuildConfig,
+    changed: bool,
+    title: &str,
+    settings: &str,
+) {
+    let Some(log_channel_id) = guild_config.enforcement_log_channel_id.filter(|_| changed) else {
+        return;
+    };
+    let msg_content = format!(
+        "{title}\n\nRitual performed by: {}\n{settings}",
+        ctx.author().mention()
+    );
+    let message = serenity::CreateMessage::new().content(msg_content);
+    if let Err(e) = serenity::ChannelId::new(log_channel_id)
+        .send_message(&ctx.http(), message)
+        .await
+    {
+        error!("Failed to log {} to the altar: {e}", ctx.command().name);
+    }
+}
+
 // Helper function to determine the appropriate notification method
 fn get_notification_method(
     notification: Option<&str>,
@@ -39,27 +97,68 @@ fn get_notification_method(
 }
 
 /// Helper function to determine the appropriate enforcement action
-#[allow(clippy::unnested_or_patterns)]
+///
+/// Any random choice is drawn from `rng`. Also returns whether one was drawn,
+/// so the judgment can be replayed. The choice becomes the user's pending
+/// enforcement.
 fn get_enforcement_action(
     state: &UserWarningState,
-    infraction_type: &str,
+    category: &InfractionCategory,
     guild_config: &GuildConfig,
     user_id: u64,
     guild_id: u64,
     ctx_data: &Data,
-) -> Option<EnforcementAction> {
+    rng: &mut impl Rng,
+) -> (Option<EnforcementAction>, bool) {
+    let (enforcement, drew_punishment) =
+        choose_enforcement_action(state, category, guild_config, rng);
+    warn!("Chose {enforcement:?} for user: {user_id}, guild: {guild_id}, state: {state:?}");
+
+    // Store the enforcement in the user state
+    let mut updated_state = state.clone();
+    updated_state.pending_enforcement.clone_from(&enforcement);
+    updated_state.last_updated = Utc::now();
+    ctx_data.set_user_warning_state(updated_state);
+
+    (enforcement, drew_punishment)
+}
+
+/// Choose the enforcement action for a user's latest warning without storing it
+#[allow(clippy::unnested_or_patterns)]
+fn choose_enforcement_action(
+    state: &UserWarningState,
+    category: &InfractionCategory,
+    guild_config: &GuildConfig,
+    rng: &mut impl Rng,
+) -> (Option<EnforcementAction>, bool) {
+    // A ladder configured for the category takes precedence over the built-in choices
+    if let Some(enforcement) = guild_config
+        .escalation
+        .select(state, category.name(), Utc::now())
+    {
+        return (Some(enforcement), false);
+    }
+
     // Check if there's a pending enforcement that is still relevant
     let pending_is_relevant = if let Some(existing_enforcement) = &state.pending_enforcement {
         // Check if the pending enforcement is relevant to the current infraction type
         let is_matching_type = matches!(
-            (infraction_type, existing_enforcement),
-            ("voice", EnforcementAction::VoiceMute { .. })
-                | ("voice", EnforcementAction::VoiceDeafen { .. })
-                | ("voice", EnforcementAction::VoiceDisconnect { .. })
-                | ("voice", EnforcementAction::VoiceChannelHaunt { .. })
-                | ("text", EnforcementAction::Mute { .. })
-                | ("server", EnforcementAction::Ban { .. })
-                | ("server", EnforcementAction::Kick { .. })
+            (category, existing_enforcement),
+            (
+                InfractionCategory::Voice,
+                EnforcementAction::VoiceMute { .. }
+            ) | (
+                InfractionCategory::Voice,
+                EnforcementAction::VoiceDeafen { .. }
+            ) | (
+                InfractionCategory::Voice,
+                EnforcementAction::VoiceDisconnect { .. }
+            ) | (
+                InfractionCategory::Voice,
+                EnforcementAction::VoiceChannelHaunt { .. }
+            ) | (InfractionCategory::Text, EnforcementAction::Mute { .. })
+                | (InfractionCategory::Server, EnforcementAction::Ban { .. })
+                | (InfractionCategory::Server, EnforcementAction::Kick { .. })
         );
 
         // Check if the pending enforcement is recent enough (within 14 days)
@@ -81,146 +180,63 @@ fn get_enforcement_action(
     };
 
     if pending_is_relevant {
-        warn!(
-            "Using existing pending enforcement for user: {user_id}, guild: {guild_id}, state: {state:?}"
-        );
         // Use the pending enforcement that was set previously
-        state.pending_enforcement.clone()
+        (state.pending_enforcement.clone(), false)
     } else if state.warning_timestamps.len() == 1 {
         // This is the first warning, set a pending enforcement based on infraction type
-        warn!("First warning for user: {user_id}, guild: {guild_id}, state: {state:?}");
-        let enforcement =
-            match infraction_type {
-                "voice" => guild_config.default_enforcement.clone().unwrap_or(
-                    EnforcementAction::VoiceMute {
-                        duration: Some(300),
-                    },
-                ),
-                "server" => guild_config
-                    .default_enforcement
-                    .clone()
-                    .unwrap_or(EnforcementAction::Kick { delay: Some(0) }),
-                _ => guild_config // text
-                    .default_enforcement
-                    .clone()
-                    .unwrap_or(EnforcementAction::Mute {
-                        duration: Some(300),
-                    }),
-            };
-
-        // Store the pending enforcement in the user state
-        let key = format!("{user_id}:{guild_id}");
-        let mut updated_state = state.clone();
-        updated_state.pending_enforcement = Some(enforcement.clone());
-        updated_state.last_updated = Utc::now();
-        ctx_data.user_warning_states.insert(key, updated_state);
+        let default_enforcement = category.default_enforcement(guild_config);
+        let enforcement = match category {
+            InfractionCategory::Voice => {
+                default_enforcement.unwrap_or(EnforcementAction::VoiceMute {
+                    duration: Some(300),
+                })
+            }
+            InfractionCategory::Server => {
+                default_enforcement.unwrap_or(EnforcementAction::Kick { delay: Some(0) })
+            }
+            InfractionCategory::Text | InfractionCategory::Custom(_) => default_enforcement
+                .unwrap_or(EnforcementAction::Mute {
+                    duration: Some(300),
+                }),
+        };
 
-        Some(enforcement)
+        (Some(enforcement), false)
     } else {
         // For repeat offenders, we need to set an appropriate escalated enforcement action
-        warn!("Repeated warning for user: {user_id}, guild: {guild_id}, state: {state:?}");
 
         // Select an escalated enforcement based on the current infraction type
-        let enforcement = match infraction_type {
-            "voice" => {
-                // For voice infractions, randomly select between different voice-related actions
-                let mut rng = rand::thread_rng();
-                let action_choice = rand::Rng::gen_range(&mut rng, 0..3); // 0-3 for four possible actions
-
-                match action_choice {
-                    0 => {
-                        let teleport_count = Some(rand::Rng::gen_range(&mut rng, 1..=4));
-                        let interval = Some(rand::Rng::gen_range(&mut rng, 5..=10));
-                        let return_to_origin = Some(rand::Rng::gen_range(&mut rng, 0..=1) == 1);
-                        let original_channel_id = None; // No original channel for teleport
-                        EnforcementAction::VoiceChannelHaunt {
-                            teleport_count,   // More teleports for repeat offenders
-                            interval,         // Quicker teleports
-                            return_to_origin, // Don't return them to their original channel
-                            original_channel_id,
-                        }
-                    }
-                    1 => EnforcementAction::VoiceDeafen {
-                        duration: Some(900), // 15 minutes of deafening
-                    },
-                    2 => EnforcementAction::VoiceDisconnect {
-                        delay: Some(0), // Immediate disconnection
-                    },
-                    _ => EnforcementAction::VoiceMute {
-                        duration: Some(1200), // 20 minutes of voice mute
-                    },
-                }
-            }
-            "server" => {
-                guild_config
-                    .default_enforcement
-                    .clone()
-                    .unwrap_or(EnforcementAction::Mute {
-                        duration: Some(3600),
-                    })
-            }
-            _ => guild_config // text
-                .default_enforcement
-                .clone()
+        let default_enforcement = category.default_enforcement(guild_config);
+        let enforcement = match category {
+            // For voice infractions, draw from the guild's punishment table
+            InfractionCategory::Voice => guild_config
+                .punishment_table
+                .draw(rng, guild_config.chaos_factor),
+            InfractionCategory::Server => default_enforcement.unwrap_or(EnforcementAction::Mute {
+                duration: Some(3600),
+            }),
+            InfractionCategory::Text | InfractionCategory::Custom(_) => default_enforcement
                 .unwrap_or(EnforcementAction::Mute {
                     duration: Some(600), // Longer duration for repeat offenders
                 }),
         };
 
-        // Store the new enforcement in the user state
-        let key = format!("{user_id}:{guild_id}");
-        let mut updated_state = state.clone();
-        updated_state.pending_enforcement = Some(enforcement.clone());
-        updated_state.last_updated = Utc::now();
-        ctx_data.user_warning_states.insert(key, updated_state);
-
-        Some(enforcement)
+        (Some(enforcement), *category == InfractionCategory::Voice)
     }
 }
 
 // Helper function to calculate the warning score with randomness
-fn calculate_adjusted_warning_score(base_score: f64, chaos_factor: f32) -> (f64, f64) {
+fn calculate_adjusted_warning_score(
+    base_score: f64,
+    chaos_factor: f32,
+    rng: &mut impl Rng,
+) -> (f64, f64) {
     // Add randomness based on the chaos factor
-    let random_factor: f64 = {
-        let mut rng = rand::thread_rng();
-        rand::Rng::gen_range(&mut rng, 0.0..f64::from(chaos_factor))
-    };
+    let random_factor = judgment::roll_chaos(rng, chaos_factor);
     let adjusted_score = base_score + random_factor;
 
     (adjusted_score, random_factor)
 }
 
-/// Helper function to create and store a warning
-fn create_and_insert_warning(
-    ctx_data: &Data,
-    user_id: u64,
-    issuer_id: u64,
-    guild_id: u64,
-    reason: String,
-    notification_method: NotificationMethod,
-    enforcement_action: Option<EnforcementAction>,
-) -> (String, DateTime<Utc>) {
-    let warning_id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now();
-
-    // Create a formal warning record
-    let warning = Warning {
-        id: warning_id.clone(),
-        user_id,
-        issuer_id,
-        guild_id,
-        reason,
-        timestamp: now,
-        notification_method,
-        enforcement: enforcement_action,
-    };
-
-    // Store warning
-    ctx_data.warnings.insert(warning_id.clone(), warning);
-
-    (warning_id, now)
-}
-
 /// Helper function to notify the target user
 async fn notify_target_user(
     ctx: &Context<'_, Data, Error>,
@@ -295,6 +311,22 @@ fn get_moderator_response(
     }
 }
 
+/// Suggest the guild's infraction categories, built-in ones first
+async fn autocomplete_category<'a>(
+    ctx: Context<'_, Data, Error>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let config = ctx
+        .guild_id()
+        .map(|guild_id| ctx.data().get_guild_config(guild_id))
+        .unwrap_or_default();
+    let partial = partial.trim().to_lowercase();
+    InfractionCategory::all(&config)
+        .into_iter()
+        .map(String::from)
+        .filter(move |name| name.starts_with(&partial))
+}
+
 /// Summon the daemon to judge a user's behavior and apply appropriate consequences
 #[command(
     slash_command,
@@ -308,8 +340,13 @@ pub async fn summon_daemon(
     ctx: Context<'_, Data, Error>,
     #[description = "User to warn"] user: User,
     #[description = "Reason for warning"] reason: String,
-    #[description = "Infraction type (text, voice, server)"] infraction_type: Option<String>,
+    #[description = "Infraction category (voice, text, server or one the guild defined)"]
+    #[autocomplete = "autocomplete_category"]
+    category: String,
     #[description = "Notification method (dm, public)"] notification: Option<String>,
+    #[description = "Severity (minor, moderate, severe, or a numeric weight)"] severity: Option<
+        String,
+    >,
 ) -> Result<(), Error> {
     ctx.defer().await?;
     let guild_id = ctx
@@ -320,51 +357,120 @@ pub async fn summon_daemon(
     let guild_config = ctx.data().get_guild_config(guild_id);
 
     // Determine infraction category
-    let infraction_type = infraction_type
-        .unwrap_or_else(|| "voice".to_string())
-        .to_lowercase();
+    let category = match InfractionCategory::parse(&category, &guild_config) {
+        Ok(category) => category,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let infraction_type = category.name();
+
+    // Determine how heavily this warning counts
+    let severity = match severity.as_deref().map(scoring::parse_severity) {
+        None => category.default_severity(&guild_config),
+        Some(Ok(severity)) => severity,
+        Some(Err(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
 
     // Determine notification method
-    let notification_method = get_notification_method(notification.as_deref(), &guild_config);
+    let mut notification_method = get_notification_method(notification.as_deref(), &guild_config);
 
     // Record this warning in the user's warning state
     let user_id = user.id.get();
     let mod_id = ctx.author().id.get();
-    let state =
-        ctx.data()
-            .add_to_user_warning_state(user_id, guild_id.get(), reason.clone(), mod_id);
-
-    // Calculate the warning score
-    let base_score = ctx.data().calculate_warning_score(user_id, guild_id.get());
-    let (adjusted_score, _) =
-        calculate_adjusted_warning_score(base_score, guild_config.chaos_factor);
-
-    // Determine if we should enforce
-    let enforce = adjusted_score > WARNING_THRESHOLD;
-
-    // Get the appropriate enforcement action
-    let enforcement_action = get_enforcement_action(
-        &state,
-        &infraction_type,
-        &guild_config,
+    let state = ctx.data().add_to_user_warning_state(
         user_id,
         guild_id.get(),
-        ctx.data(),
+        reason.clone(),
+        mod_id,
+        severity,
+        infraction_type,
     );
 
+    // Calculate the warning score for this category only, so warnings of one
+    // kind don't escalate punishments for another
+    let base_score = ctx
+        .data()
+        .calculate_category_score(user_id, guild_id.get(), infraction_type);
+
+    // Draw every random choice from a recorded seed so the judgment can be replayed
+    let seed = judgment::new_seed();
+    let mut rng = judgment::rng(seed);
+    let (adjusted_score, roll) =
+        calculate_adjusted_warning_score(base_score, guild_config.chaos_factor, &mut rng);
+    let threshold = guild_config.threshold_for(infraction_type);
+
+    // A guild's policy decides in place of the threshold and the built-in choices
+    let (enforce, enforcement_action, drew_punishment, rule) =
+        if let Some(policy) = &guild_config.policy {
+            let facts = policy::Facts::gather(
+                ctx.data(),
+                &state,
+                infraction_type,
+                adjusted_score,
+                severity,
+                Utc::now(),
+            );
+            match policy.evaluate(&facts) {
+                Some(rule) => {
+                    if let Some(notify) = rule.then.notify {
+                        notification_method = notify.into();
+                    }
+                    let action = rule.then.enforcement();
+                    (action.is_some(), action, false, Some(rule.name.clone()))
+                }
+                None => (false, None, false, None),
+            }
+        } else {
+            // Determine if we should enforce
+            let enforce = adjusted_score > threshold;
+
+            // Get the appropriate enforcement action
+            let (enforcement_action, drew_punishment) = get_enforcement_action(
+                &state,
+                &category,
+                &guild_config,
+                user_id,
+                guild_id.get(),
+                ctx.data(),
+                &mut rng,
+            );
+            (enforce, enforcement_action, drew_punishment, None)
+        };
+
     // Create and store warning
-    let (warning_id, _) = create_and_insert_warning(
-        ctx.data(),
+    let warning_id = Uuid::new_v4().to_string();
+    ctx.data().insert_warning(Warning {
+        id: warning_id.clone(),
         user_id,
-        mod_id,
-        guild_id.get(),
-        reason.clone(),
-        notification_method.clone(),
-        enforcement_action.clone(),
-    );
+        issuer_id: mod_id,
+        guild_id: guild_id.get(),
+        reason: reason.clone(),
+        timestamp: Utc::now(),
+        notification_method: notification_method.clone(),
+        enforcement: enforcement_action.clone(),
+        severity,
+        judgment: Some(Judgment {
+            seed,
+            chaos_factor: guild_config.chaos_factor,
+            category: infraction_type.to_string(),
+            base_score,
+            roll,
+            adjusted_score,
+            threshold,
+            drew_punishment,
+            rule,
+            table: drew_punishment.then(|| guild_config.punishment_table.clone()),
+        }),
+        category: Some(category.clone()),
+    });
 
     // Generate a demonic response
-    let is_voice = infraction_type == "voice";
+    let is_voice = category == InfractionCategory::Voice;
     let response_type = if enforce {
         crate::daemon_response::ResponseType::Punishment
     } else if state.warning_timestamps.len() == 1 {
@@ -379,7 +485,7 @@ pub async fn summon_daemon(
         num_warn: state.warning_timestamps.len() as u64,
         voice_warnings: ctx.data().get_warnings(),
         warning_score: adjusted_score,
-        warning_threshold: WARNING_THRESHOLD,
+        warning_threshold: threshold,
         mod_name: ctx.author().name.clone(),
     };
 
@@ -394,7 +500,7 @@ pub async fn summon_daemon(
             log_channel_id,
             &user,
             &reason,
-            &infraction_type,
+            &category,
             &state,
             &enforcement_action,
             enforce,
@@ -414,15 +520,10 @@ pub async fn summon_daemon(
     .await?;
 
     // If enforcing, create or update the enforcement
-    if enforce && enforcement_action.is_some() {
-        if let Some(action) = enforcement_action {
-            create_and_notify_enforcement(&ctx, warning_id, user_id, guild_id.get(), action).await;
-        }
+    if enforce && let Some(action) = enforcement_action {
+        create_and_notify_enforcement(&ctx, warning_id, user_id, guild_id.get(), action).await;
     }
 
-    // Save data
-    let _ = save_data(&ctx, "daemon summon").await;
-
     // Respond to the moderator
     let response =
         get_moderator_response(enforce, state.warning_timestamps.len(), &user.name, &reason);
@@ -431,6 +532,154 @@ pub async fn summon_daemon(
     Ok(())
 }
 
+/// Chaos rolls a preview evaluates a guild's policy at, spread evenly over the roll's range
+const PREVIEW_SAMPLES: u32 = 100;
+
+/// Preview what summoning the daemon on a user would do, without recording anything
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
+    default_member_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS"
+)]
+pub async fn daemon_preview(
+    ctx: Context<'_, Data, Error>,
+    #[description = "User to preview a warning for"] user: User,
+    #[description = "Infraction category (voice, text, server or one the guild defined)"]
+    #[autocomplete = "autocomplete_category"]
+    category: String,
+    #[description = "Severity (minor, moderate, severe, or a numeric weight)"] severity: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+    let guild_config = ctx.data().get_guild_config(guild_id);
+
+    let category = match InfractionCategory::parse(&category, &guild_config) {
+        Ok(category) => category,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let infraction_type = category.name();
+    let severity = match severity.as_deref().map(scoring::parse_severity) {
+        None => category.default_severity(&guild_config),
+        Some(Ok(severity)) => severity,
+        Some(Err(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+
+    // Run the summoning pipeline on a copy of the user's state, storing nothing
+    let user_id = user.id.get();
+    let now = Utc::now();
+    let current_score =
+        ctx.data()
+            .calculate_category_score(user_id, guild_id.get(), infraction_type);
+    let state = ctx.data().project_warning(
+        user_id,
+        guild_id.get(),
+        String::new(),
+        ctx.author().id.get(),
+        severity,
+        infraction_type,
+    );
+    let base_score = guild_config
+        .scoring_strategy()
+        .score(&state.for_category(infraction_type), now);
+    let threshold = guild_config.threshold_for(infraction_type);
+    let chaos_factor = guild_config.chaos_factor;
+
+    let mut content = format!(
+        "**[DAEMON PREVIEW]** Nothing has been recorded.\n\nA {} {infraction_type} warning would be warning #{} for {}.\nProjected {infraction_type} score: {current_score:.2} → **{base_score:.2}**, plus a chaos roll of up to {chaos_factor:.2}\n",
+        scoring::severity_label(severity),
+        state.warning_timestamps.len(),
+        user.mention(),
+    );
+
+    if let Some(policy) = &guild_config.policy {
+        // The roll decides which rule fires, so tally the rules across its range
+        let mut outcomes: Vec<(String, Option<EnforcementAction>, u32)> = Vec::new();
+        for sample in 0..PREVIEW_SAMPLES {
+            let roll =
+                f64::from(chaos_factor) * (f64::from(sample) + 0.5) / f64::from(PREVIEW_SAMPLES);
+            let facts = policy::Facts::gather(
+                ctx.data(),
+                &state,
+                infraction_type,
+                base_score + roll,
+                severity,
+                now,
+            );
+            let (name, action) = policy.evaluate(&facts).map_or_else(
+                || ("no rule".to_string(), None),
+                |rule| (format!("rule `{}`", rule.name), rule.then.enforcement()),
+            );
+            match outcomes.iter_mut().find(|(seen, _, _)| *seen == name) {
+                Some((_, _, count)) => *count += 1,
+                None => outcomes.push((name, action, 1)),
+            }
+        }
+
+        let punished: u32 = outcomes
+            .iter()
+            .filter(|(_, action, _)| action.is_some())
+            .map(|(_, _, count)| count)
+            .sum();
+        content.push_str(&format!(
+            "Chance of punishment under the guild's policy: **{:.0}%**\nCandidate outcomes:",
+            f64::from(punished) * 100.0 / f64::from(PREVIEW_SAMPLES)
+        ));
+        for (name, action, count) in outcomes {
+            let action = action
+                .as_ref()
+                .map_or_else(|| "warning only".to_string(), escalation::format_action);
+            content.push_str(&format!(
+                "\n- {name}: `{action}` ({:.0}%)",
+                f64::from(count) * 100.0 / f64::from(PREVIEW_SAMPLES)
+            ));
+        }
+    } else {
+        let probability = judgment::enforcement_probability(base_score, chaos_factor, threshold);
+        content.push_str(&format!(
+            "Chance of punishment against a threshold of {threshold:.1}: **{:.0}%**\nCandidate punishments:",
+            probability * 100.0
+        ));
+
+        let mut rng = judgment::rng(judgment::new_seed());
+        let (action, drew_punishment) =
+            choose_enforcement_action(&state, &category, &guild_config, &mut rng);
+        if drew_punishment {
+            let probabilities = guild_config.punishment_table.probabilities(chaos_factor);
+            for (entry, probability) in guild_config
+                .punishment_table
+                .entries
+                .iter()
+                .zip(probabilities)
+            {
+                content.push_str(&format!(
+                    "\n- `{}` ({:.1}%)",
+                    entry.candidate.describe(),
+                    probability * 100.0
+                ));
+            }
+        } else {
+            let action = action
+                .as_ref()
+                .map_or_else(|| "none".to_string(), escalation::format_action);
+            content.push_str(&format!("\n- `{action}`"));
+        }
+    }
+
+    ctx.say(content).await?;
+    Ok(())
+}
+
 /// Generate a demonic response based on the context.
 /// This should be used to create thematic messages for the daemon via
 /// the LLM integration.
@@ -508,12 +757,13 @@ pub async fn warn(
         timestamp: now,
         notification_method,
         enforcement: enforcement.clone(),
+        severity: scoring::SEVERITY_MODERATE,
+        judgment: None,
+        category: None,
     };
 
     // Store warning
-    ctx.data()
-        .warnings
-        .insert(warning_id.clone(), warning.clone());
+    ctx.data().insert_warning(warning.clone());
 
     // Create pending enforcement if applicable
     if let Some(action) = enforcement {
@@ -578,9 +828,6 @@ pub async fn warn(
         "Warning issued to user"
     );
 
-    // Save data
-    let _ = save_data(&ctx, "warning").await;
-
     info!(
         target: crate::COMMAND_TARGET,
         command = "warn",
@@ -656,7 +903,7 @@ pub async fn daemon_altar(
     guild_config.enforcement_log_channel_id = Some(channel_id.get());
 
     // Save the updated config
-    ctx.data().guild_configs.insert(guild_id, guild_config);
+    ctx.data().set_guild_config(guild_config);
 
     // Generate a demonic response for the altar setting
     let context = format!(
@@ -673,13 +920,6 @@ pub async fn daemon_altar(
     )
     .await;
 
-    // Save data
-    if (save_data(&ctx, "setting enforcement log channel").await).is_err() {
-        ctx.say("Failed to save configuration. Check logs for details.")
-            .await?;
-        return Ok(());
-    }
-
     // Send a test message to verify permissions
     let test_message = format!(
         "**[DAEMON ALTAR ESTABLISHED]**\n\n{demonic_message}\n\nThis channel shall serve as my altar. All warnings, judgments, and enforcements shall be proclaimed here.",
@@ -739,9 +979,7 @@ pub async fn chaos_ritual(
     guild_config.chaos_factor = factor;
 
     // Save the updated config
-    ctx.data()
-        .guild_configs
-        .insert(guild_id, guild_config.clone());
+    ctx.data().set_guild_config(guild_config.clone());
 
     // Generate a demonic response for the chaos ritual
     let context = format!(
@@ -775,13 +1013,6 @@ pub async fn chaos_ritual(
         "**[DAEMON RITUAL COMPLETE]** {demonic_message}\n\nChaos factor set to {factor:.2}. {ritual_status}",
     );
 
-    // Save data
-    if (save_data(&ctx, "setting chaos factor").await).is_err() {
-        ctx.say("Failed to save configuration. Check logs for details.")
-            .await?;
-        return Ok(());
-    }
-
     // If there's a log channel, also log the ritual there
     if let Some(log_channel_id) = guild_config.enforcement_log_channel_id {
         let msg_content = format!(
@@ -801,107 +1032,624 @@ pub async fn chaos_ritual(
     Ok(())
 }
 
-/// View a user's warning history and current warning score
+/// Choose how the daemon scores a user's warnings
 #[command(
     slash_command,
     guild_only,
     ephemeral,
-    required_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
-    required_bot_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
-    default_member_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS"
+    required_permissions = "ADMINISTRATOR"
 )]
-pub async fn judgment_history(
+pub async fn scoring_ritual(
     ctx: Context<'_, Data, Error>,
-    #[description = "User to check"] user: User,
+    #[description = "Scoring strategy: decay, sliding_window or points"] strategy: String,
+    #[description = "Window (sliding_window) or expiry (points) in hours"] hours: Option<u32>,
+    #[description = "Points per warning (points)"] points: Option<f64>,
 ) -> Result<(), Error> {
-    ctx.defer().await?;
     let guild_id = ctx
         .guild_id()
         .ok_or("This command must be used in a guild")?;
 
-    let user_id = user.id.get();
+    let scoring = match ScoringConfig::parse(&strategy, hours, points) {
+        Ok(scoring) => scoring,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
 
-    // Get the user's warning state
-    let state = ctx
-        .data()
-        .get_or_create_user_warning_state(user_id, guild_id.get());
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    let previous = guild_config.scoring_strategy().describe();
+    guild_config.scoring = scoring;
+    ctx.data().set_guild_config(guild_config.clone());
 
-    // Get all warnings for this user in this guild
-    let mut warnings = Vec::new();
-    let mut voice_warnings = 0;
+    let description = guild_config.scoring_strategy().describe();
+    info!(
+        target: crate::COMMAND_TARGET,
+        "Scoring strategy for guild {} changed from {} to {} by {}",
+        guild_id,
+        previous,
+        description,
+        ctx.author().name
+    );
 
-    for entry in &ctx.data().warnings {
-        let warning = entry.value();
-        if warning.user_id == user_id && warning.guild_id == guild_id.get() {
-            // Check if it's a voice-related warning based on enforcement action
-            if let Some(action) = &warning.enforcement {
-                if matches!(
-                    action,
-                    EnforcementAction::VoiceMute { .. }
-                        | EnforcementAction::VoiceDeafen { .. }
-                        | EnforcementAction::VoiceDisconnect { .. }
-                        | EnforcementAction::VoiceChannelHaunt { .. }
-                ) {
-                    voice_warnings += 1;
-                }
-            }
-            warnings.push(warning.clone());
-        }
+    if let Some(log_channel_id) = guild_config.enforcement_log_channel_id {
+        let msg_content = format!(
+            "⚖️ **SCORING RITUAL PERFORMED**\n\nRitual performed by: {}\nPrevious judgment: {}\nNew judgment: {}",
+            ctx.author().mention(),
+            previous,
+            description
+        );
+        let channel_id = serenity::ChannelId::new(log_channel_id);
+        let message = serenity::CreateMessage::new().content(msg_content);
+        let _ = channel_id.send_message(&ctx.http(), message).await;
     }
 
-    // Sort warnings by timestamp (newest first)
-    warnings.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    ctx.say(format!(
+        "**[DAEMON RITUAL COMPLETE]** The daemon now judges by {description}."
+    ))
+    .await?;
+    Ok(())
+}
 
-    // Get the current warning score
-    let score = ctx.data().calculate_warning_score(user_id, guild_id.get());
+/// Tune the warning threshold, decay rate and moderator diversity bonus
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn judgment_ritual(
+    ctx: Context<'_, Data, Error>,
+    #[description = "Score at which the daemon enforces"] threshold: Option<f64>,
+    #[description = "Infraction category the threshold is for (0 falls back to the guild threshold)"]
+    #[autocomplete = "autocomplete_category"]
+    category: Option<String>,
+    #[description = "How fast warnings fade under decay scoring, per hour"] decay_rate: Option<f64>,
+    #[description = "Score bonus per extra moderator reporting a user, under decay scoring"]
+    diversity_bonus: Option<f64>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
 
-    // Generate a demonic response for the judgment history
-    let warn_context = WarningContext {
-        user_name: user.name.clone(),
-        num_warn: warnings.len() as u64,
-        voice_warnings: warnings.clone(),
-        warning_score: score,
-        warning_threshold: WARNING_THRESHOLD,
-        mod_name: ctx.author().name.clone(),
-    };
+    let category = category.map(|category| category.trim().to_lowercase());
+    let clearing_category = category.is_some() && threshold == Some(0.0);
+    if category.is_some() && threshold.is_none() {
+        ctx.say("Give a threshold for the category").await?;
+        return Ok(());
+    }
+    if !clearing_category && threshold.is_some_and(|t| !(t.is_finite() && t > 0.0)) {
+        ctx.say("The threshold must be greater than 0").await?;
+        return Ok(());
+    }
+    if [decay_rate, diversity_bonus]
+        .into_iter()
+        .flatten()
+        .any(|value| !(value.is_finite() && value >= 0.0))
+    {
+        ctx.say("The decay rate and diversity bonus can't be negative")
+            .await?;
+        return Ok(());
+    }
 
-    // Use a punishment type if close to threshold, otherwise warning type
-    let response_type = if score > WARNING_THRESHOLD * 0.75 {
-        crate::daemon_response::ResponseType::Punishment
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    // Clearing is allowed for any name, so a threshold left by a removed category can go
+    if let Some(category) = category.as_deref().filter(|_| !clearing_category)
+        && let Err(e) = InfractionCategory::parse(category, &guild_config)
+    {
+        ctx.say(e).await?;
+        return Ok(());
+    }
+    match (threshold, &category) {
+        (Some(_), Some(category)) if clearing_category => {
+            guild_config.category_thresholds.remove(category);
+        }
+        (Some(threshold), Some(category)) => {
+            guild_config
+                .category_thresholds
+                .insert(category.clone(), threshold);
+        }
+        (Some(threshold), None) => guild_config.warning_threshold = threshold,
+        (None, _) => {}
+    }
+    if let Some(decay_rate) = decay_rate {
+        guild_config.decay_rate = decay_rate;
+    }
+    if let Some(diversity_bonus) = diversity_bonus {
+        guild_config.mod_diversity_bonus = diversity_bonus;
+    }
+    let changed = threshold.is_some() || decay_rate.is_some() || diversity_bonus.is_some();
+    if changed {
+        ctx.data().set_guild_config(guild_config.clone());
+    }
+
+    let category_thresholds = if guild_config.category_thresholds.is_empty() {
+        "none".to_string()
     } else {
-        crate::daemon_response::ResponseType::Warning
+        guild_config
+            .category_thresholds
+            .iter()
+            .map(|(category, threshold)| format!("{category} {threshold:.2}"))
+            .collect::<Vec<_>>()
+            .join(", ")
     };
+    let settings = format!(
+        "Threshold: {:.2}\nCategory thresholds: {}\nDecay rate: {:.3}/hour\nDiversity bonus: {:.2} per extra moderator\nScoring: {}",
+        guild_config.warning_threshold,
+        category_thresholds,
+        guild_config.decay_rate,
+        guild_config.mod_diversity_bonus,
+        guild_config.scoring_strategy().describe()
+    );
 
-    let demonic_message =
-        generate_daemon_response(&warn_context.to_string(), Some(&state), response_type).await;
+    if changed {
+        info!(
+            target: crate::COMMAND_TARGET,
+            "Judgment settings for guild {} changed by {}: {}",
+            guild_id,
+            ctx.author().name,
+            settings.replace('\n', ", ")
+        );
+    }
 
-    // Create thematic header based on warning score
-    let header = if score > WARNING_THRESHOLD {
-        "**[DAEMON JUDGMENT SCROLL - CONDEMNED]**"
-    } else if score > WARNING_THRESHOLD * 0.75 {
-        "**[DAEMON JUDGMENT SCROLL - TEETERING]**"
-    } else if score > WARNING_THRESHOLD * 0.5 {
-        "**[DAEMON JUDGMENT SCROLL - CONCERNING]**"
-    } else if score > 0.0 {
-        "**[DAEMON JUDGMENT SCROLL - NOTED]**"
-    } else {
-        "**[DAEMON JUDGMENT SCROLL - UNBLEMISHED]**"
-    };
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "⚖️ **JUDGMENT RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
 
-    // Determine if there are voice infractions
-    let has_voice_infractions = voice_warnings > 0;
+    ctx.say(format!(
+        "**[DAEMON RITUAL COMPLETE]** The scales are set.\n\n{settings}"
+    ))
+    .await?;
+    Ok(())
+}
 
-    // Build a message content instead of an embed for more natural daemon speech
+/// Set the weighted table of punishments for repeat voice offenders, and show their odds
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn punishment_ritual(
+    ctx: Context<'_, Data, Error>,
+    #[description = "Entries like \"haunt 2 teleports=1-4 interval=5s-10s, deafen 1 duration=15m\" (default restores)"]
+    table: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    if let Some(table) = table.as_deref() {
+        let parsed = if table.trim().eq_ignore_ascii_case("default") {
+            Ok(PunishmentTable::default())
+        } else {
+            PunishmentTable::parse(table)
+        };
+        match parsed {
+            Ok(parsed) => guild_config.punishment_table = parsed,
+            Err(e) => {
+                ctx.say(e).await?;
+                return Ok(());
+            }
+        }
+        ctx.data().set_guild_config(guild_config.clone());
+    }
+    let changed = table.is_some();
+
+    let probabilities = guild_config
+        .punishment_table
+        .probabilities(guild_config.chaos_factor);
+    let mut settings = format!(
+        "Chance of each punishment at chaos factor {:.2}:",
+        guild_config.chaos_factor
+    );
+    for (entry, probability) in guild_config
+        .punishment_table
+        .entries
+        .iter()
+        .zip(probabilities)
+    {
+        settings.push_str(&format!(
+            "\n- `{}` weight {}: **{:.1}%**",
+            entry.candidate.describe(),
+            entry.weight,
+            probability * 100.0
+        ));
+    }
+
+    if changed {
+        info!(
+            target: crate::COMMAND_TARGET,
+            "Punishment table for guild {} changed by {}: {}",
+            guild_id,
+            ctx.author().name,
+            settings.replace('\n', " ")
+        );
+    }
+
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "🎲 **PUNISHMENT RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
+
+    ctx.say(format!(
+        "**[DAEMON RITUAL COMPLETE]** The dice are weighed.\n\n{settings}"
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Define, change or remove one of the guild's own infraction categories
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn category_ritual(
+    ctx: Context<'_, Data, Error>,
+    #[description = "Name of the category, e.g. harassment"]
+    #[autocomplete = "autocomplete_category"]
+    name: Option<String>,
+    #[description = "Default punishment, e.g. \"mute 1h\" (none falls back to the guild default)"]
+    action: Option<String>,
+    #[description = "Default severity (minor, moderate, severe, or a numeric weight)"]
+    severity: Option<String>,
+    #[description = "Emoji for the category's altar log entries (none clears)"] emoji: Option<
+        String,
+    >,
+    #[description = "Title for the category's altar log entries (none clears)"] title: Option<
+        String,
+    >,
+    #[description = "Remove the category"] remove: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    let name = name.map(|name| name.trim().to_lowercase());
+    let mut changed = false;
+    if let Some(name) = &name {
+        if remove == Some(true) {
+            if guild_config.custom_categories.remove(name).is_none() {
+                ctx.say(format!("There is no custom category `{name}`"))
+                    .await?;
+                return Ok(());
+            }
+        } else {
+            if let Err(e) = category::validate_custom_name(name) {
+                ctx.say(e).await?;
+                return Ok(());
+            }
+            let existing = guild_config.custom_categories.get(name).cloned();
+            if existing.is_none()
+                && guild_config.custom_categories.len() >= category::MAX_CUSTOM_CATEGORIES
+            {
+                ctx.say(format!(
+                    "A guild can define at most {} categories",
+                    category::MAX_CUSTOM_CATEGORIES
+                ))
+                .await?;
+                return Ok(());
+            }
+
+            let mut custom = existing.unwrap_or_default();
+            match action.as_deref().map(str::trim) {
+                None => {}
+                Some(action) if action.eq_ignore_ascii_case("none") => {
+                    custom.default_enforcement = None;
+                }
+                Some(action) => match escalation::parse_action(action) {
+                    Ok(action) => custom.default_enforcement = Some(action),
+                    Err(e) => {
+                        ctx.say(e).await?;
+                        return Ok(());
+                    }
+                },
+            }
+            match severity.as_deref().map(scoring::parse_severity) {
+                None => {}
+                Some(Ok(severity)) => custom.severity = severity,
+                Some(Err(e)) => {
+                    ctx.say(e).await?;
+                    return Ok(());
+                }
+            }
+            let cleared = |value: String| {
+                let value = value.trim().to_string();
+                (!value.eq_ignore_ascii_case("none") && !value.is_empty()).then_some(value)
+            };
+            if let Some(emoji) = emoji {
+                custom.emoji = cleared(emoji);
+            }
+            if let Some(title) = title {
+                custom.title = cleared(title);
+            }
+            guild_config.custom_categories.insert(name.clone(), custom);
+        }
+        changed = true;
+        ctx.data().set_guild_config(guild_config.clone());
+    }
+
+    let mut settings = if guild_config.custom_categories.is_empty() {
+        "No custom categories: only voice, text and server".to_string()
+    } else {
+        "Custom categories:".to_string()
+    };
+    for (name, custom) in &guild_config.custom_categories {
+        let (title, emoji) = InfractionCategory::Custom(name.clone()).log_style(&guild_config);
+        let action = custom
+            .default_enforcement
+            .as_ref()
+            .map_or_else(|| "guild default".to_string(), escalation::format_action);
+        settings.push_str(&format!(
+            "\n- `{name}` {emoji} {title}: punishment `{action}`, {} severity",
+            scoring::severity_label(custom.severity)
+        ));
+    }
+
+    if changed {
+        info!(
+            target: crate::COMMAND_TARGET,
+            "Categories for guild {} changed by {}: {}",
+            guild_id,
+            ctx.author().name,
+            settings.replace('\n', " ")
+        );
+    }
+
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "🏷️ **CATEGORY RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
+
+    ctx.say(format!(
+        "**[DAEMON RITUAL COMPLETE]** The sins are named.\n\n{settings}"
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Set the escalation ladder of punishments for an infraction category
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn escalation_ritual(
+    ctx: Context<'_, Data, Error>,
+    #[description = "Infraction category (voice, text, server or one the guild defined)"]
+    #[autocomplete = "autocomplete_category"]
+    category: Option<String>,
+    #[description = "Punishments in order, e.g. \"voice_mute 5m, deafen 15m, haunt, disconnect\" (none removes)"]
+    ladder: Option<String>,
+    #[description = "Days an offense counts toward climbing the ladder"] lookback_days: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let category = category.map(|category| category.trim().to_lowercase());
+    if ladder.is_some() && category.is_none() {
+        ctx.say("Give the category the ladder is for").await?;
+        return Ok(());
+    }
+    if lookback_days.is_some_and(|days| !(1..=escalation::MAX_LOOKBACK_DAYS).contains(&days)) {
+        ctx.say(format!(
+            "The lookback must be from 1 to {} days",
+            escalation::MAX_LOOKBACK_DAYS
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    if let (Some(category), Some(ladder)) = (&category, ladder.as_deref()) {
+        if ladder.trim().eq_ignore_ascii_case("none") {
+            guild_config.escalation.ladders.remove(category);
+        } else if let Err(e) = InfractionCategory::parse(category, &guild_config) {
+            ctx.say(e).await?;
+            return Ok(());
+        } else {
+            match escalation::parse_ladder(ladder) {
+                Ok(rungs) => {
+                    guild_config
+                        .escalation
+                        .ladders
+                        .insert(category.clone(), rungs);
+                }
+                Err(e) => {
+                    ctx.say(e).await?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+    if let Some(lookback_days) = lookback_days {
+        guild_config.escalation.lookback_days = lookback_days;
+    }
+    let changed = ladder.is_some() || lookback_days.is_some();
+    if changed {
+        ctx.data().set_guild_config(guild_config.clone());
+    }
+
+    let mut settings = format!("Lookback: {} days", guild_config.escalation.lookback_days);
+    if guild_config.escalation.ladders.is_empty() {
+        settings.push_str("\nLadders: none, the daemon picks punishments itself");
+    }
+    for (category, rungs) in &guild_config.escalation.ladders {
+        settings.push_str(&format!(
+            "\n{category}: {}",
+            escalation::format_ladder(rungs)
+        ));
+    }
+
+    if changed {
+        info!(
+            target: crate::COMMAND_TARGET,
+            "Escalation settings for guild {} changed by {}: {}",
+            guild_id,
+            ctx.author().name,
+            settings.replace('\n', ", ")
+        );
+    }
+
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "🪜 **ESCALATION RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
+
+    ctx.say(format!(
+        "**[DAEMON RITUAL COMPLETE]** The ladders are set.\n\n{settings}"
+    ))
+    .await?;
+    Ok(())
+}
+
+/// View a user's warning history and current warning score
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
+    required_bot_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
+    default_member_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS"
+)]
+pub async fn judgment_history(
+    ctx: Context<'_, Data, Error>,
+    #[description = "User to check"] user: User,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let user_id = user.id.get();
+
+    // Get the user's warning state
+    let state = ctx
+        .data()
+        .get_or_create_user_warning_state(user_id, guild_id.get());
+
+    // Get all warnings for this user in this guild
+    let mut warnings = ctx.data().user_warnings(user_id, guild_id.get());
+    let mut voice_warnings = 0;
+
+    for warning in &warnings {
+        // Check if it's a voice-related warning based on enforcement action
+        if matches!(
+            warning.enforcement,
+            Some(
+                EnforcementAction::VoiceMute { .. }
+                    | EnforcementAction::VoiceDeafen { .. }
+                    | EnforcementAction::VoiceDisconnect { .. }
+                    | EnforcementAction::VoiceChannelHaunt { .. }
+            )
+        ) {
+            voice_warnings += 1;
+        }
+    }
+
+    // Sort warnings by timestamp (newest first)
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.timestamp));
+
+    // Get the current warning score and the guild's threshold
+    let score = ctx.data().calculate_warning_score(user_id, guild_id.get());
+    let breakdown = ctx.data().score_breakdown(user_id, guild_id.get());
+    let guild_config = ctx.data().get_guild_config(guild_id);
+
+    // Judge by the category closest to its threshold
+    let (judged_score, threshold) = breakdown
+        .iter()
+        .map(|category| (category.score, category.threshold))
+        .max_by(|a, b| (a.0 / a.1).total_cmp(&(b.0 / b.1)))
+        .unwrap_or((score, guild_config.warning_threshold));
+
+    // Generate a demonic response for the judgment history
+    let warn_context = WarningContext {
+        user_name: user.name.clone(),
+        num_warn: warnings.len() as u64,
+        voice_warnings: warnings.clone(),
+        warning_score: judged_score,
+        warning_threshold: threshold,
+        mod_name: ctx.author().name.clone(),
+    };
+
+    // Use a punishment type if close to threshold, otherwise warning type
+    let response_type = if judged_score > threshold * 0.75 {
+        crate::daemon_response::ResponseType::Punishment
+    } else {
+        crate::daemon_response::ResponseType::Warning
+    };
+
+    let demonic_message =
+        generate_daemon_response(&warn_context.to_string(), Some(&state), response_type).await;
+
+    // Create thematic header based on warning score
+    let header = if judged_score > threshold {
+        "**[DAEMON JUDGMENT SCROLL - CONDEMNED]**"
+    } else if judged_score > threshold * 0.75 {
+        "**[DAEMON JUDGMENT SCROLL - TEETERING]**"
+    } else if judged_score > threshold * 0.5 {
+        "**[DAEMON JUDGMENT SCROLL - CONCERNING]**"
+    } else if judged_score > 0.0 {
+        "**[DAEMON JUDGMENT SCROLL - NOTED]**"
+    } else {
+        "**[DAEMON JUDGMENT SCROLL - UNBLEMISHED]**"
+    };
+
+    // Determine if there are voice infractions
+    let has_voice_infractions = voice_warnings > 0;
+
+    // Build a message content instead of an embed for more natural daemon speech
     let mut content = format!(
-        "{}\n\n{}\n\n{} has **{}** warnings with a current judgment score of **{:.2}/{:.1}**.\n",
+        "{}\n\n{}\n\n{} has **{}** warnings with an overall judgment score of **{:.2}**.\n",
         header,
         demonic_message,
         user.mention(),
         state.warning_timestamps.len(),
-        score,
-        WARNING_THRESHOLD
+        score
     );
 
+    // Show how each category's score compares to its threshold, and when it
+    // will have faded below it and below half of it
+    let now = Utc::now();
+    for forecast in ctx.data().forecast(user_id, guild_id.get(), now) {
+        content.push_str(&format!(
+            "- {}: **{:.2}/{:.1}**, under the threshold {}, under half of it {}\n",
+            forecast.category,
+            forecast.score,
+            forecast.threshold,
+            scoring::format_forecast(forecast.below_threshold, now),
+            scoring::format_forecast(forecast.below_half, now)
+        ));
+    }
+
+    content.push_str(&format!(
+        "*Judged by {}.*\n",
+        guild_config.scoring_strategy().describe()
+    ));
+
     // Add pending enforcement if any
     if let Some(action) = &state.pending_enforcement {
         let action_desc = match action {
@@ -937,7 +1685,7 @@ pub async fn judgment_history(
         };
 
         content.push_str(&format!(
-            "\n**PENDING JUDGMENT**: Should the mortal's score exceed {WARNING_THRESHOLD:.1}, their fate shall be: **{action_desc}**\n",
+            "\n**PENDING JUDGMENT**: Should the mortal's score exceed {threshold:.1}, their fate shall be: **{action_desc}**\n",
         ));
     }
 
@@ -956,12 +1704,20 @@ pub async fn judgment_history(
                 .map(|u| u.name.clone())
                 .unwrap_or_else(|_| "Unknown Moderator".to_string());
 
+            let category = warning
+                .category
+                .as_ref()
+                .map(|category| format!("{category}, "))
+                .unwrap_or_default();
             content.push_str(&format!(
-                "{}. **{}**: {} (Reported by {})\n",
+                "{}. **{}**: {} ({}{}, reported by {}) `{}`\n",
                 i + 1,
                 timestamp,
                 warning.reason,
-                issuer
+                category,
+                scoring::severity_label(warning.severity),
+                issuer,
+                warning.id
             ));
         }
 
@@ -973,6 +1729,13 @@ pub async fn judgment_history(
         }
     }
 
+    if state.purged_warnings > 0 {
+        content.push_str(&format!(
+            "\n{} older transgressions have faded past this realm's memory, though the daemon still counts them.\n",
+            state.purged_warnings
+        ));
+    }
+
     // Add a thematic closing
     if has_voice_infractions {
         content.push_str("\n*The daemon remembers all voices that have disturbed its realm...*");
@@ -984,253 +1747,825 @@ pub async fn judgment_history(
     Ok(())
 }
 
-/// Appease the daemon to cancel a pending punishment
+/// Appease the daemon to cancel a pending punishment
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn appease(
+    ctx: Context<'_, Data, Error>,
+    #[description = "User whose enforcement to cancel"] user: User,
+    #[description = "Specific enforcement ID to cancel (optional)"] enforcement_id: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+    let user_id = user.id.get();
+    let mut canceled = false;
+    let mut canceled_enforcements = Vec::new();
+
+    // Find pending and active enforcements for this user in this guild
+    let mut to_cancel = Vec::new();
+
+    for enforcement in ctx.data().user_enforcements(user_id, guild_id.get()) {
+        let is_pending = match enforcement.state {
+            crate::data::EnforcementState::Pending => true,
+            crate::data::EnforcementState::Active => false,
+            _ => continue,
+        };
+        if enforcement_id
+            .as_ref()
+            .is_some_and(|eid| enforcement.id != *eid)
+        {
+            continue;
+        }
+        to_cancel.push((enforcement.id.clone(), is_pending));
+        canceled_enforcements.push(enforcement);
+    }
+
+    // Cancel the found enforcements
+    for (id, is_pending) in to_cancel {
+        let enforcement = if is_pending {
+            ctx.data()
+                .pending_enforcements
+                .get(&id)
+                .map(|entry| entry.value().clone())
+        } else {
+            ctx.data()
+                .active_enforcements
+                .get(&id)
+                .map(|entry| entry.value().clone())
+        };
+
+        // A haunt underway is stopped and the user sent back where they started
+        if enforcement
+            .as_ref()
+            .is_some_and(|enforcement| enforcement.haunt.is_some())
+        {
+            let backend = SerenityBackend::new(std::sync::Arc::clone(&ctx.serenity_context().http));
+            if let Err(e) = crate::haunt::cancel(&backend, ctx.data(), &id).await {
+                error!("Failed to return user {user_id} from haunt {id}: {e}");
+            }
+            canceled = true;
+            continue;
+        }
+
+        if let Some(mut enforcement_data) = enforcement {
+            enforcement_data.state = crate::data::EnforcementState::Cancelled;
+            enforcement_data.executed = true; // For backward compatibility
+
+            // Move to completed enforcements
+            ctx.data().store_enforcement(enforcement_data);
+
+            canceled = true;
+
+            // Notify the enforcement task
+            notify_enforcement_task_by_id(&ctx, id).await;
+        }
+    }
+
+    // Get user state if available
+    let user_state = ctx
+        .data()
+        .get_or_create_user_warning_state(user_id, guild_id.get());
+
+    // Generate a demonic appeasement response
+    let context = format!(
+        "User: {}. Enforcements canceled: {}. Moderator: {}.",
+        user.name,
+        canceled_enforcements.len(),
+        ctx.author().name
+    );
+
+    let demonic_message = generate_daemon_response(
+        &context,
+        Some(&user_state),
+        crate::daemon_response::ResponseType::Appeasement,
+    )
+    .await;
+
+    if canceled {
+        // Check if any of the canceled enforcements involved voice
+        let has_voice_enforcement = canceled_enforcements.iter().any(|enforcement| {
+            matches!(
+                enforcement.action,
+                EnforcementAction::VoiceMute { .. }
+                    | EnforcementAction::VoiceDeafen { .. }
+                    | EnforcementAction::VoiceDisconnect { .. }
+                    | EnforcementAction::VoiceChannelHaunt { .. }
+            )
+        });
+
+        // Format response based on whether it's voice-related
+        let response = if has_voice_enforcement {
+            format!(
+                "**[DAEMON RELUCTANTLY YIELDS]** {}\n\nThe daemon has been appeased. Pending punishment for {} has been canceled.",
+                demonic_message, user.name
+            )
+        } else {
+            format!(
+                "**[DAEMON GRUMBLES]** {}\n\nThe daemon has been appeased. Pending punishment for {} has been canceled.",
+                demonic_message, user.name
+            )
+        };
+
+        ctx.say(response).await?;
+    } else {
+        ctx.say(format!("No pending enforcements found for {}", user.name))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// View the current state of the daemon, including active voice channels and enforcements
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn daemon_status(ctx: Context<'_, Data, Error>) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    // Update the status tracker with latest data
+    ctx.data().status.write().await.update_from_data(ctx.data());
+
+    let status = ctx.data().status.read().await.clone();
+
+    let cache_http = (&ctx.data().get_cache(), ctx.http());
+    // Generate complete status report
+    let status_text = format_complete_status(&status, ctx.data(), &cache_http).await;
+
+    // Split into chunks if needed (Discord has a 2000 character limit)
+    if status_text.len() <= 1900 {
+        ctx.say(status_text).await?;
+    } else {
+        // Split into smaller chunks
+        let mut chunks = Vec::new();
+        let mut current_chunk = String::new();
+
+        for line in status_text.lines() {
+            if current_chunk.len() + line.len() + 1 > 1900 {
+                // This line would make the chunk too big, start a new one
+                chunks.push(current_chunk);
+                current_chunk = line.to_string();
+            } else {
+                if !current_chunk.is_empty() {
+                    current_chunk.push('\n');
+                }
+                current_chunk.push_str(line);
+            }
+        }
+
+        // Add the last chunk if non-empty
+        if !current_chunk.is_empty() {
+            chunks.push(current_chunk);
+        }
+
+        // Send chunks
+        for (i, chunk) in chunks.iter().enumerate() {
+            let msg = if chunks.len() > 1 {
+                format!(
+                    "**Status Report (Part {}/{})**\n{}",
+                    i + 1,
+                    chunks.len(),
+                    chunk
+                )
+            } else {
+                chunk.to_string()
+            };
+            ctx.say(msg).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Set how many days old warnings and finished enforcements are kept
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn daemon_retention(
+    ctx: Context<'_, Data, Error>,
+    #[description = "Days to keep warnings (0 keeps them forever)"] warning_days: Option<u32>,
+    #[description = "Days to keep finished enforcements (0 keeps them forever)"]
+    enforcement_days: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    if [warning_days, enforcement_days]
+        .into_iter()
+        .flatten()
+        .any(|days| days > retention::MAX_RETENTION_DAYS)
+    {
+        ctx.say(format!(
+            "Records can be kept for at most {} days, or 0 to keep them forever",
+            retention::MAX_RETENTION_DAYS
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
+    if let Some(days) = warning_days {
+        guild_config.retention.warning_days = (days > 0).then_some(days);
+    }
+    if let Some(days) = enforcement_days {
+        guild_config.retention.enforcement_days = (days > 0).then_some(days);
+    }
+    let retention = guild_config.retention;
+    let purged = guild_config.purged;
+    let changed = warning_days.is_some() || enforcement_days.is_some();
+    if changed {
+        ctx.data().set_guild_config(guild_config.clone());
+    }
+
+    let describe = |days: Option<u32>| days.map_or("forever".to_string(), |d| format!("{d} days"));
+    let settings = format!(
+        "Warnings are kept {}. Finished enforcements are kept {}.\nSo far the daemon has forgotten {} warnings and {} enforcements.",
+        describe(retention.warning_days),
+        describe(retention.enforcement_days),
+        purged.warnings,
+        purged.enforcements
+    );
+
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "⏳ **RETENTION RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
+
+    ctx.say(format!("**[DAEMON MEMORY]** {settings}")).await?;
+    Ok(())
+}
+
+/// Erase every record the daemon holds about a user, for privacy requests
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn daemon_erase(
+    ctx: Context<'_, Data, Error>,
+    #[description = "User whose records to erase"] user: User,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+    let user_id = user.id.get();
+
+    ctx.defer().await?;
+
+    let backend = SerenityBackend::new(std::sync::Arc::clone(&ctx.serenity_context().http));
+    let lifts = retention::lift_active(&backend, ctx.data(), guild_id.get(), user_id).await;
+
+    let report = retention::erase_user(ctx.data(), guild_id.get(), user_id, &lifts.kept());
+    if report.is_empty() && lifts.failed.is_empty() {
+        ctx.say(format!("The daemon holds no records of {}", user.name))
+            .await?;
+        return Ok(());
+    }
+
+    info!(
+        target: crate::COMMAND_TARGET,
+        "Erased user {} from guild {} for {}: {} warnings, {} enforcements, {} warning states",
+        user_id,
+        guild_id,
+        ctx.author().name,
+        report.warnings,
+        report.enforcements,
+        report.warning_states
+    );
+    retention::log_erasure(
+        ctx.http(),
+        ctx.data(),
+        guild_id,
+        user_id,
+        ctx.author().id,
+        &report,
+        &lifts,
+    )
+    .await;
+
+    let mut reply = format!(
+        "**[DAEMON FORGETS]** The live records of {} have been erased: {} warnings, {} enforcements and {} warning states. {} active punishments were lifted first.",
+        user.name, report.warnings, report.enforcements, report.warning_states, lifts.lifted
+    );
+    if let Some(failures) = lifts.describe_failures() {
+        reply.push_str(&format!(
+            "\n\n{failures}. Lift those by hand, then erase again."
+        ));
+    }
+    if let Some(copies) = ctx.data().storage.retained_copies() {
+        reply.push_str(&format!(
+            "\n\nNot erased: copies of these records remain in {copies}. Remove those by hand if the request covers them."
+        ));
+    }
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Replay the daemon's judgment of a past warning from its recorded seed
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn daemon_replay(
+    ctx: Context<'_, Data, Error>,
+    #[description = "ID of the warning to replay, as shown by /judgment_history"]
+    warning_id: String,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let warning = ctx
+        .data()
+        .warnings
+        .get(warning_id.trim())
+        .map(|entry| entry.value().clone())
+        .filter(|warning| warning.guild_id == guild_id.get());
+    let Some(warning) = warning else {
+        ctx.say(format!(
+            "No warning `{}` found in this guild",
+            warning_id.trim()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let Some(recorded) = &warning.judgment else {
+        ctx.say("That warning was not judged by a summoning, so there is nothing to replay")
+            .await?;
+        return Ok(());
+    };
+
+    let replay = judgment::replay(recorded);
+    let verdict = match &recorded.rule {
+        Some(rule) => format!("decided by policy rule `{rule}`"),
+        None if replay.enforced => "punish".to_string(),
+        None => "spare".to_string(),
+    };
+    let mut content = format!(
+        "**[DAEMON REPLAY]** Judgment of <@{}> for \"{}\"\n\nSeed: `{}`\nCategory: {}\nBase score: {:.2}\nChaos roll: {:.4} (chaos factor {:.2})\nAdjusted score: {:.4} against a threshold of {:.1}\nVerdict: {}\n",
+        warning.user_id,
+        warning.reason,
+        recorded.seed,
+        recorded.category,
+        recorded.base_score,
+        replay.roll,
+        recorded.chaos_factor,
+        replay.adjusted_score,
+        recorded.threshold,
+        verdict
+    );
+    if let Some(punishment) = &replay.punishment {
+        content.push_str(&format!("Punishment drawn: {punishment:?}\n"));
+    }
+
+    let punishment_matches =
+        replay.punishment.is_none() || replay.punishment == warning.enforcement;
+    if replay.matches(recorded) && punishment_matches {
+        content.push_str("\n*The replay matches the recorded judgment.*");
+    } else {
+        content.push_str(&format!(
+            "\n*The replay differs from the recorded judgment, which rolled {:.4} for an adjusted score of {:.4} and chose {:?}.*",
+            recorded.roll, recorded.adjusted_score, warning.enforcement
+        ));
+    }
+
+    info!(
+        target: crate::COMMAND_TARGET,
+        "Replayed judgment for warning {} in guild {} for {}",
+        warning.id,
+        guild_id,
+        ctx.author().name
+    );
+
+    ctx.say(content).await?;
+    Ok(())
+}
+
+/// Upload, show or remove the guild's moderation policy
 #[command(
     slash_command,
     guild_only,
     ephemeral,
     required_permissions = "ADMINISTRATOR"
 )]
-pub async fn appease(
+pub async fn daemon_policy(
     ctx: Context<'_, Data, Error>,
-    #[description = "User whose enforcement to cancel"] user: User,
-    #[description = "Specific enforcement ID to cancel (optional)"] enforcement_id: Option<String>,
+    #[description = "YAML policy file replacing the current one"] file: Option<
+        serenity::Attachment,
+    >,
+    #[description = "Remove the policy and go back to the threshold"] clear: Option<bool>,
 ) -> Result<(), Error> {
     let guild_id = ctx
         .guild_id()
         .ok_or("This command must be used in a guild")?;
-    let user_id = user.id.get();
-    let mut canceled = false;
-    let mut canceled_enforcements = Vec::new();
-
-    // Find pending enforcements for this user in this guild - both from pending and active maps
-    let mut to_cancel = Vec::new();
+    let mut guild_config = ctx.data().get_guild_config(guild_id);
 
-    // Check pending enforcements
-    for entry in &ctx.data().pending_enforcements {
-        let pending = entry.value();
-        if pending.user_id == user_id
-            && pending.guild_id == guild_id.get()
-            && pending.state == crate::data::EnforcementState::Pending
-        {
-            if let Some(ref eid) = enforcement_id {
-                if pending.id == *eid {
-                    to_cancel.push((pending.id.clone(), true));
-                    canceled_enforcements.push(pending.clone());
-                    break;
-                }
-            } else {
-                to_cancel.push((pending.id.clone(), true));
-                canceled_enforcements.push(pending.clone());
-            }
+    let changed = if clear.unwrap_or(false) {
+        guild_config.policy = None;
+        true
+    } else if let Some(file) = file {
+        if file.size > MAX_IMPORT_BYTES {
+            ctx.say(format!(
+                "That file is too large ({} bytes, the limit is {MAX_IMPORT_BYTES})",
+                file.size
+            ))
+            .await?;
+            return Ok(());
         }
-    }
-
-    // Also check active enforcements
-    for entry in &ctx.data().active_enforcements {
-        let active = entry.value();
-        if active.user_id == user_id
-            && active.guild_id == guild_id.get()
-            && active.state == crate::data::EnforcementState::Active
-        {
-            if let Some(ref eid) = enforcement_id {
-                if active.id == *eid {
-                    to_cancel.push((active.id.clone(), false));
-                    canceled_enforcements.push(active.clone());
-                    break;
-                }
-            } else {
-                to_cancel.push((active.id.clone(), false));
-                canceled_enforcements.push(active.clone());
+        ctx.defer().await?;
+
+        let bytes = file.download().await?;
+        let parsed = String::from_utf8(bytes)
+            .map_err(|_| vec!["The file is not UTF-8 text".to_string()])
+            .and_then(|yaml| Policy::parse(&yaml));
+        match parsed {
+            Ok(policy) => guild_config.policy = Some(policy),
+            Err(problems) => {
+                ctx.say(format!(
+                    "**[DAEMON REJECTS]** The policy in `{}` was not loaded:\n{}",
+                    file.filename,
+                    format_problem_list(&problems)
+                ))
+                .await?;
+                return Ok(());
             }
         }
+        true
+    } else {
+        false
+    };
+    if changed {
+        ctx.data().set_guild_config(guild_config.clone());
     }
 
-    // Cancel the found enforcements
-    for (id, is_pending) in to_cancel {
-        if is_pending {
-            if let Some(mut pending) = ctx.data().pending_enforcements.get_mut(&id) {
-                pending.state = crate::data::EnforcementState::Cancelled;
-                pending.executed = true; // For backward compatibility
-
-                // Move to completed enforcements
-                let enforcement_data = pending.value().clone();
-                drop(pending);
-                ctx.data().pending_enforcements.remove(&id);
-                ctx.data()
-                    .completed_enforcements
-                    .insert(id.clone(), enforcement_data);
-
-                canceled = true;
-
-                // Notify the enforcement task
-                notify_enforcement_task_by_id(&ctx, id).await;
-            }
-        } else if let Some(mut active) = ctx.data().active_enforcements.get_mut(&id) {
-            active.state = crate::data::EnforcementState::Cancelled;
-            active.executed = true; // For backward compatibility
+    let settings = match &guild_config.policy {
+        None => "No policy, the daemon judges by the threshold".to_string(),
+        Some(policy) => policy
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| format!("{}. `{}`: {}", index + 1, rule.name, rule.then.action))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
 
-            // Move to completed enforcements
-            let enforcement_data = active.value().clone();
-            drop(active);
-            ctx.data().active_enforcements.remove(&id);
-            ctx.data()
-                .completed_enforcements
-                .insert(id.clone(), enforcement_data);
+    if changed {
+        info!(
+            target: crate::COMMAND_TARGET,
+            "Policy for guild {} changed by {}: {}",
+            guild_id,
+            ctx.author().name,
+            settings.replace('\n', ", ")
+        );
+    }
 
-            canceled = true;
+    log_ritual_to_altar(
+        ctx,
+        &guild_config,
+        changed,
+        "📜 **POLICY RITUAL PERFORMED**",
+        &settings,
+    )
+    .await;
 
-            // Notify the enforcement task
-            notify_enforcement_task_by_id(&ctx, id).await;
-        }
-    }
+    ctx.say(format!("**[DAEMON POLICY]**\n\n{settings}"))
+        .await?;
+    Ok(())
+}
 
-    // Get user state if available
-    let user_state = ctx
+/// Show which policy rule fires for a user, and which fired at their last summoning
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS",
+    default_member_permissions = "KICK_MEMBERS|BAN_MEMBERS|MUTE_MEMBERS|DEAFEN_MEMBERS|MODERATE_MEMBERS"
+)]
+pub async fn policy_explain(
+    ctx: Context<'_, Data, Error>,
+    #[description = "User to check"] user: User,
+    #[description = "Infraction category (defaults to that of their latest warning)"]
+    #[autocomplete = "autocomplete_category"]
+    category: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+    let guild_config = ctx.data().get_guild_config(guild_id);
+    let Some(policy) = &guild_config.policy else {
+        ctx.say("This guild has no policy; use /daemon_policy to upload one")
+            .await?;
+        return Ok(());
+    };
+
+    let user_id = user.id.get();
+    let latest = ctx
+        .data()
+        .user_warnings(user_id, guild_id.get())
+        .into_iter()
+        .max_by_key(|warning| warning.timestamp);
+    let category = category
+        .map(|category| category.trim().to_lowercase())
+        .or_else(|| {
+            latest
+                .as_ref()
+                .and_then(|warning| warning.judgment.as_ref())
+                .map(|judgment| judgment.category.clone())
+        })
+        .unwrap_or_else(|| "voice".to_string());
+
+    // Evaluate as if the user were summoned now, without a chaos roll
+    let state = ctx
         .data()
         .get_or_create_user_warning_state(user_id, guild_id.get());
+    let score = ctx
+        .data()
+        .calculate_category_score(user_id, guild_id.get(), &category);
+    let severity = latest
+        .as_ref()
+        .map_or(scoring::SEVERITY_MODERATE, |warning| warning.severity);
+    let facts = policy::Facts::gather(ctx.data(), &state, &category, score, severity, Utc::now());
 
-    // Generate a demonic appeasement response
-    let context = format!(
-        "User: {}. Enforcements canceled: {}. Moderator: {}.",
-        user.name,
-        canceled_enforcements.len(),
-        ctx.author().name
+    let mut content = format!(
+        "**[DAEMON POLICY]** {} in {}: score {:.2}, {} warnings\n\n",
+        user.mention(),
+        category,
+        facts.score,
+        facts.warnings
     );
-
-    let demonic_message = generate_daemon_response(
-        &context,
-        Some(&user_state),
-        crate::daemon_response::ResponseType::Appeasement,
-    )
-    .await;
-
-    if canceled {
-        // Check if any of the canceled enforcements involved voice
-        let has_voice_enforcement = canceled_enforcements.iter().any(|enforcement| {
-            matches!(
-                enforcement.action,
-                EnforcementAction::VoiceMute { .. }
-                    | EnforcementAction::VoiceDeafen { .. }
-                    | EnforcementAction::VoiceDisconnect { .. }
-                    | EnforcementAction::VoiceChannelHaunt { .. }
-            )
-        });
-
-        // Format response based on whether it's voice-related
-        let response = if has_voice_enforcement {
-            format!(
-                "**[DAEMON RELUCTANTLY YIELDS]** {}\n\nThe daemon has been appeased. Pending punishment for {} has been canceled.",
-                demonic_message, user.name
-            )
+    let fired = policy.evaluate(&facts).map(|rule| rule.name.clone());
+    for (index, rule) in policy.rules.iter().enumerate() {
+        let checks = rule
+            .when
+            .check(&facts)
+            .into_iter()
+            .map(|(condition, holds)| format!("{} {condition}", if holds { "✅" } else { "❌" }))
+            .collect::<Vec<_>>();
+        let marker = if fired.as_ref() == Some(&rule.name) {
+            " ← **fires**"
         } else {
-            format!(
-                "**[DAEMON GRUMBLES]** {}\n\nThe daemon has been appeased. Pending punishment for {} has been canceled.",
-                demonic_message, user.name
-            )
+            ""
         };
+        content.push_str(&format!(
+            "{}. `{}` → {}{}\n",
+            index + 1,
+            rule.name,
+            rule.then.action,
+            marker
+        ));
+        if !checks.is_empty() {
+            content.push_str(&format!("   {}\n", checks.join(", ")));
+        }
+    }
+    if fired.is_none() {
+        content.push_str("No rule fires, so the user would only be warned.\n");
+    }
 
-        // Save data
-        let _ = save_data(&ctx, "canceling enforcement").await;
-
-        ctx.say(response).await?;
-    } else {
-        ctx.say(format!("No pending enforcements found for {}", user.name))
-            .await?;
+    let last_rule = latest
+        .as_ref()
+        .and_then(|warning| warning.judgment.as_ref())
+        .and_then(|judgment| judgment.rule.clone());
+    if let Some(rule) = last_rule {
+        content.push_str(&format!("\nAt their last summoning, rule `{rule}` fired."));
     }
 
+    ctx.say(content).await?;
     Ok(())
 }
 
-/// View the current state of the daemon, including active voice channels and enforcements
+/// Largest file `/daemon_import` will read
+const MAX_IMPORT_BYTES: u32 = 25 * 1024 * 1024;
+
+/// Most conflicts or problems listed in a single reply
+const MAX_LISTED_PROBLEMS: usize = 10;
+
+/// List problems one per line, cutting the list short to fit in a message
+fn format_problem_list(problems: &[String]) -> String {
+    let mut list = problems
+        .iter()
+        .take(MAX_LISTED_PROBLEMS)
+        .map(|problem| format!("- {problem}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if problems.len() > MAX_LISTED_PROBLEMS {
+        list.push_str(&format!(
+            "\n- ...and {} more",
+            problems.len() - MAX_LISTED_PROBLEMS
+        ));
+    }
+    list
+}
+
+/// Parse the format option, falling back to the file name and then JSON
+fn transfer_format(
+    format: Option<&str>,
+    file_name: Option<&str>,
+) -> Result<TransferFormat, String> {
+    match format {
+        Some(name) => TransferFormat::parse(name)
+            .ok_or_else(|| format!("Unknown format `{name}`. Use `json` or `csv`.")),
+        None => Ok(file_name
+            .and_then(TransferFormat::from_file_name)
+            .unwrap_or(TransferFormat::Json)),
+    }
+}
+
+/// Export this guild's config, warnings, enforcements and warning states as a file
 #[command(
     slash_command,
     guild_only,
     ephemeral,
     required_permissions = "ADMINISTRATOR"
 )]
-pub async fn daemon_status(ctx: Context<'_, Data, Error>) -> Result<(), Error> {
+pub async fn daemon_export(
+    ctx: Context<'_, Data, Error>,
+    #[description = "File format: json (default) or csv"] format: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
+
+    let format = match transfer_format(format.as_deref(), None) {
+        Ok(format) => format,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+
     ctx.defer().await?;
 
-    // Update the status tracker with latest data
-    ctx.data().status.write().await.update_from_data(ctx.data());
+    let export = GuildExport::from_data(ctx.data(), guild_id.get());
+    let bytes = export.to_bytes(format)?;
+    let filename = format!(
+        "daemon-export-{}-{}.{}",
+        guild_id,
+        export.exported_at.format("%Y%m%d-%H%M%S"),
+        format.extension()
+    );
 
-    let status = ctx.data().status.read().await.clone();
+    info!(
+        target: crate::COMMAND_TARGET,
+        "Exported {} records from guild {} as {:?} for {}",
+        export.record_count(),
+        guild_id,
+        format,
+        ctx.author().name
+    );
 
-    let cache_http = (&ctx.data().get_cache(), ctx.http());
-    // Generate complete status report
-    let status_text = format_complete_status(&status, ctx.data(), &cache_http).await;
+    let reply = poise::CreateReply::default()
+        .content(format!(
+            "**[DAEMON ARCHIVES]** The daemon surrenders its records: {} warnings, {} enforcements and {} warning states.",
+            export.warnings.len(),
+            export.enforcements().count(),
+            export.user_warning_states.len()
+        ))
+        .attachment(serenity::CreateAttachment::bytes(bytes, filename));
+    ctx.send(reply).await?;
+    Ok(())
+}
 
-    // Split into chunks if needed (Discord has a 2000 character limit)
-    if status_text.len() <= 1900 {
-        ctx.say(status_text).await?;
-    } else {
-        // Split into smaller chunks
-        let mut chunks = Vec::new();
-        let mut current_chunk = String::new();
+/// Import records from a file made by /daemon_export, reporting conflicts with existing ones
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn daemon_import(
+    ctx: Context<'_, Data, Error>,
+    #[description = "File made by /daemon_export"] file: serenity::Attachment,
+    #[description = "File format: json or csv (detected from the file name if omitted)"]
+    format: Option<String>,
+    #[description = "Replace existing records that differ instead of keeping them"]
+    overwrite: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be used in a guild")?;
 
-        for line in status_text.lines() {
-            if current_chunk.len() + line.len() + 1 > 1900 {
-                // This line would make the chunk too big, start a new one
-                chunks.push(current_chunk);
-                current_chunk = line.to_string();
-            } else {
-                if !current_chunk.is_empty() {
-                    current_chunk.push('\n');
-                }
-                current_chunk.push_str(line);
-            }
+    let format = match transfer_format(format.as_deref(), Some(&file.filename)) {
+        Ok(format) => format,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
         }
+    };
 
-        // Add the last chunk if non-empty
-        if !current_chunk.is_empty() {
-            chunks.push(current_chunk);
-        }
+    if file.size > MAX_IMPORT_BYTES {
+        ctx.say(format!(
+            "That file is too large to import ({} bytes, the limit is {MAX_IMPORT_BYTES})",
+            file.size
+        ))
+        .await?;
+        return Ok(());
+    }
 
-        // Send chunks
-        for (i, chunk) in chunks.iter().enumerate() {
-            let msg = if chunks.len() > 1 {
-                format!(
-                    "**Status Report (Part {}/{})**\n{}",
-                    i + 1,
-                    chunks.len(),
-                    chunk
-                )
-            } else {
-                chunk.to_string()
-            };
-            ctx.say(msg).await?;
+    ctx.defer().await?;
+
+    let bytes = file.download().await?;
+    let export = match GuildExport::from_bytes(&bytes, format) {
+        Ok(export) => export,
+        Err(e) => {
+            ctx.say(format!(
+                "**[DAEMON REJECTS]** The daemon can't read `{}`: {e}",
+                file.filename
+            ))
+            .await?;
+            return Ok(());
         }
+    };
+
+    let problems = export.validate(guild_id.get());
+    if !problems.is_empty() {
+        ctx.say(format!(
+            "**[DAEMON REJECTS]** Nothing was imported from `{}`:\n{}",
+            file.filename,
+            format_problem_list(&problems)
+        ))
+        .await?;
+        return Ok(());
     }
+
+    let overwrite = overwrite.unwrap_or(false);
+    let report = export.merge_into(ctx.data(), overwrite);
+
+    info!(
+        target: crate::COMMAND_TARGET,
+        "Imported {} from guild {} for {}: {} added, {} replaced, {} unchanged, {} conflicts",
+        file.filename,
+        guild_id,
+        ctx.author().name,
+        report.added,
+        report.replaced,
+        report.unchanged,
+        report.conflicts.len()
+    );
+
+    let mut response = format!(
+        "**[DAEMON ARCHIVES RESTORED]** Imported `{}`: {} added, {} replaced, {} unchanged.",
+        file.filename, report.added, report.replaced, report.unchanged
+    );
+    if !report.conflicts.is_empty() {
+        response.push_str(&format!(
+            "\n\n{} records already exist with different contents and were kept as they are (use `overwrite` to replace them):\n{}",
+            report.conflicts.len(),
+            format_problem_list(&report.conflicts)
+        ));
+    }
+
+    ctx.say(response).await?;
     Ok(())
 }
 
 /// Logs a daemon warning/enforcement to the guild's log channel
+#[allow(clippy::too_many_arguments)]
 async fn log_daemon_warning(
     ctx: &Context<'_, Data, Error>,
     log_channel_id: u64,
     user: &User,
     reason: &str,
-    infraction_type: &str,
+    category: &InfractionCategory,
     state: &crate::data::UserWarningState,
     enforcement_action: &Option<EnforcementAction>,
     enforce: bool,
     demonic_message: &str,
 ) {
     let channel_id = serenity::ChannelId::new(log_channel_id);
+    let infraction_type = category.name();
     let user_mention = user.mention();
     let mod_mention = ctx.author().mention();
     let warning_count = state.warning_timestamps.len();
-    let warning_score = ctx
+    let warning_score =
+        ctx.data()
+            .calculate_category_score(user.id.get(), state.guild_id, infraction_type);
+    let overall_score = ctx
         .data()
         .calculate_warning_score(user.id.get(), state.guild_id);
+    let guild_config = ctx
+        .data()
+        .get_guild_config(serenity::GuildId::new(state.guild_id));
+    let warning_threshold = guild_config.threshold_for(infraction_type);
 
-    // For non-voice infractions, use a hybrid approach with an embed for the log
-    let (title_prefix, emoji) = match infraction_type {
-        "text" => ("Text Channel", "💬"),
-        "server" => ("Server Rule", "⚠️"),
-        _ => ("General", "⚠️"),
-    };
+    // Each category, including the guild's own, has its own title and emoji
+    let (title_prefix, emoji) = category.log_style(&guild_config);
 
     let title = if enforce {
         format!("🚫 {title_prefix} Enforcement")
@@ -1244,9 +2579,18 @@ async fn log_daemon_warning(
             "{demonic_message}\n\n{user_mention} has received a {infraction_type} warning",
         ))
         .field("Reason", reason, false)
+        .field(
+            "Severity",
+            scoring::severity_label(state.severity(warning_count.saturating_sub(1))),
+            true,
+        )
         .field("Issued By", mod_mention.to_string(), true)
         .field("Total Warnings", warning_count.to_string(), true)
-        .field("Warning Score", format!("{warning_score:.2}"), true)
+        .field(
+            "Warning Score",
+            format!("{warning_score:.2} {infraction_type} ({overall_score:.2} overall)"),
+            true,
+        )
         .colour(serenity::Colour::GOLD)
         .timestamp(serenity::Timestamp::now());
 
@@ -1292,7 +2636,7 @@ async fn log_daemon_warning(
             embed = embed.field(
                 "🚨 If behavior continues:",
                 format!(
-                    "After reaching a warning score of {WARNING_THRESHOLD:.1}, the user will receive: **{action_desc}**",
+                    "After reaching a {infraction_type} warning score of {warning_threshold:.1}, the user will receive: **{action_desc}**",
                 ),
                 false,
             );
@@ -1319,21 +2663,17 @@ async fn log_daemon_warning(
 
 /// Calculates the execution time for an enforcement action
 fn calculate_execute_at(action: &EnforcementAction) -> chrono::DateTime<Utc> {
-    match action {
+    let delay = match action {
         EnforcementAction::Ban { duration }
         | EnforcementAction::Mute { duration }
         | EnforcementAction::VoiceMute { duration }
-        | EnforcementAction::VoiceDeafen { duration } => {
-            Utc::now() + Duration::seconds(duration.unwrap_or(0) as i64)
-        }
-        EnforcementAction::Kick { delay } | EnforcementAction::VoiceDisconnect { delay } => {
-            Utc::now() + Duration::seconds(delay.unwrap_or(0) as i64)
-        }
-        EnforcementAction::VoiceChannelHaunt { interval, .. } => {
-            Utc::now() + Duration::seconds(interval.unwrap_or(0) as i64)
-        }
-        EnforcementAction::None => Utc::now(),
-    }
+        | EnforcementAction::VoiceDeafen { duration } => *duration,
+        EnforcementAction::Kick { delay } | EnforcementAction::VoiceDisconnect { delay } => *delay,
+        EnforcementAction::VoiceChannelHaunt { interval, .. } => *interval,
+        EnforcementAction::None => None,
+    };
+    // A delay too long to represent runs right away rather than panicking
+    enforcement::seconds_from_now(delay.unwrap_or(0)).unwrap_or_else(Utc::now)
 }
 
 /// Creates and stores a pending enforcement
@@ -1361,33 +2701,25 @@ async fn create_pending_enforcement(
         executed_at: None,
         reversed_at: None,
         executed: false, // For backward compatibility
+        attempts: 0,
+        last_error: None,
+        haunt: None,
     };
 
-    ctx.data()
-        .pending_enforcements
-        .insert(enforcement_id.clone(), pending);
+    ctx.data().store_enforcement(pending);
 
     enforcement_id
 }
 
 /// Notifies the enforcement task about a user
 async fn notify_enforcement_task(ctx: &Context<'_, Data, Error>, user_id: u64, guild_id: u64) {
-    if let Some(tx) = &*ctx.data().enforcement_tx {
+    if let Some(tx) = ctx.data().enforcement_tx.get() {
         let _ = tx
             .send(EnforcementCheckRequest::CheckUser { user_id, guild_id })
             .await;
     }
 }
 
-/// Saves data with appropriate error handling
-async fn save_data(ctx: &Context<'_, Data, Error>, error_context: &str) -> Result<(), Error> {
-    if let Err(e) = ctx.data().save().await {
-        error!("Failed to save data after {error_context}: {e}");
-        return Err(e);
-    }
-    Ok(())
-}
-
 /// Checks if an enforcement action should be applied immediately
 fn is_immediate_action(action: &EnforcementAction) -> bool {
     match action {
@@ -1421,7 +2753,7 @@ async fn create_and_notify_enforcement(
 
 /// Notifies the enforcement task about a specific enforcement
 async fn notify_enforcement_task_by_id(ctx: &Context<'_, Data, Error>, enforcement_id: String) {
-    if let Some(tx) = &*ctx.data().enforcement_tx {
+    if let Some(tx) = ctx.data().enforcement_tx.get() {
         let _ = tx
             .send(EnforcementCheckRequest::CheckEnforcement { enforcement_id })
             .await;
@@ -1439,7 +2771,7 @@ mod tests {
         assert_eq!(cmd.name, "ping");
         assert!(
             cmd.description
-                .unwrap_or_else(Default::default)
+                .unwrap_or_default()
                 .contains("check if the bot is responsive")
         );
         assert!(cmd.guild_only);
@@ -1453,4 +2785,19 @@ mod tests {
         let cmd = ping();
         assert!(cmd.create_as_slash_command().is_some());
     }
+
+    #[test]
+    fn test_read_only_commands_exist() {
+        let names = [
+            ping(),
+            daemon_preview(),
+            judgment_history(),
+            daemon_status(),
+            daemon_replay(),
+            policy_explain(),
+            daemon_export(),
+        ]
+        .map(|cmd| cmd.name);
+        assert_eq!(names, READ_ONLY_COMMANDS);
+    }
 }
@@ -1,12 +1,24 @@
 use std::{
+    collections::{BTreeMap, HashSet},
     default::Default,
     fmt::{Display, Formatter},
-    ops::{Deref, DerefMut},
-    sync::Arc,
+    ops::Deref,
+    sync::{Arc, OnceLock},
 };
 
+use crate::category::{CustomCategory, InfractionCategory};
 use crate::enforcement::EnforcementCheckRequest;
+use crate::escalation::EscalationSettings;
+use crate::haunt::{HauntJobs, HauntProgress};
+use crate::judgment::Judgment;
+use crate::policy::Policy;
+use crate::punishment::PunishmentTable;
+use crate::schedule::{Deadline, Schedule};
+use crate::scoring::{self, ScoringConfig, ScoringStrategy};
 use crate::status::BotStatus;
+use crate::storage::{
+    self, ChangeSet, CorruptionPolicy, DirtyRecords, Snapshot, Storage, YamlStorage,
+};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use poise::serenity_prelude as serenity;
@@ -14,10 +26,6 @@ use serde::{Deserialize, Serialize};
 use serenity::prelude::TypeMapKey;
 use tokio::sync::{RwLock, mpsc::Sender};
 
-// Constants for the scoring algorithm
-const DECAY_RATE: f64 = 0.05; // Higher values mean faster decay
-const MOD_DIVERSITY_BONUS: f64 = 0.5; // Bonus for different mods reporting
-
 /// Guild configuration structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildConfig {
@@ -36,6 +44,36 @@ pub struct GuildConfig {
     pub chaos_factor: f32,
     // Warning threshold for the weighted warning system
     pub warning_threshold: f64,
+    // Thresholds for categories that don't use `warning_threshold`
+    #[serde(default)]
+    pub category_thresholds: BTreeMap<String, f64>,
+    // How fast warnings fade under the decay strategy, per hour
+    #[serde(default = "default_decay_rate")]
+    pub decay_rate: f64,
+    // Score bonus per extra moderator reporting a user, under the decay strategy
+    #[serde(default = "default_mod_diversity_bonus")]
+    pub mod_diversity_bonus: f64,
+    // How long old warnings and finished enforcements are kept
+    #[serde(default)]
+    pub retention: RetentionSettings,
+    // Totals of the records removed by retention sweeps
+    #[serde(default)]
+    pub purged: PurgedCounts,
+    // How warning scores are calculated
+    #[serde(default)]
+    pub scoring: ScoringConfig,
+    // Punishment ladders for each infraction category
+    #[serde(default)]
+    pub escalation: EscalationSettings,
+    // Rules that decide punishments in place of the threshold, if set
+    #[serde(default)]
+    pub policy: Option<Policy>,
+    // Punishments drawn for repeat voice offenders
+    #[serde(default)]
+    pub punishment_table: PunishmentTable,
+    // Infraction categories the guild defined, by name
+    #[serde(default)]
+    pub custom_categories: BTreeMap<String, CustomCategory>,
 }
 
 impl Default for GuildConfig {
@@ -47,11 +85,29 @@ impl Default for GuildConfig {
             default_enforcement: None,
             enforcement_log_channel_id: None,
             chaos_factor: 0.3,
-            warning_threshold: 2.0,
+            warning_threshold: scoring::WARNING_THRESHOLD,
+            category_thresholds: BTreeMap::new(),
+            decay_rate: scoring::DECAY_RATE,
+            mod_diversity_bonus: scoring::MOD_DIVERSITY_BONUS,
+            retention: RetentionSettings::default(),
+            purged: PurgedCounts::default(),
+            scoring: ScoringConfig::default(),
+            escalation: EscalationSettings::default(),
+            policy: None,
+            punishment_table: PunishmentTable::default(),
+            custom_categories: BTreeMap::new(),
         }
     }
 }
 
+fn default_decay_rate() -> f64 {
+    scoring::DECAY_RATE
+}
+
+fn default_mod_diversity_bonus() -> f64 {
+    scoring::MOD_DIVERSITY_BONUS
+}
+
 impl GuildConfig {
     /// Create a new `GuildConfig` with the specified guild ID
     #[must_use]
@@ -61,6 +117,38 @@ impl GuildConfig {
             ..Default::default()
         }
     }
+
+    /// Threshold for a category's score, falling back to the guild threshold
+    #[must_use]
+    pub fn threshold_for(&self, category: &str) -> f64 {
+        self.category_thresholds
+            .get(category)
+            .copied()
+            .unwrap_or(self.warning_threshold)
+    }
+
+    /// The scoring strategy this guild selected, with its decay settings
+    #[must_use]
+    pub fn scoring_strategy(&self) -> Box<dyn ScoringStrategy> {
+        self.scoring
+            .strategy(self.decay_rate, self.mod_diversity_bonus)
+    }
+}
+
+/// How many days a guild keeps old records; `None` keeps them forever
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionSettings {
+    /// Days to keep warnings
+    pub warning_days: Option<u32>,
+    /// Days to keep enforcements after they finished
+    pub enforcement_days: Option<u32>,
+}
+
+/// Number of records a guild's retention sweeps have removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgedCounts {
+    pub warnings: u64,
+    pub enforcements: u64,
 }
 
 /// Notification method for warnings
@@ -72,7 +160,7 @@ pub enum NotificationMethod {
 }
 
 /// Enforcement actions that can be taken as part of a warning
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EnforcementAction {
     #[default]
     None,
@@ -119,13 +207,32 @@ pub struct Warning {
     pub timestamp: DateTime<Utc>,
     pub notification_method: NotificationMethod,
     pub enforcement: Option<EnforcementAction>,
+    #[serde(default = "default_severity")]
+    pub severity: f64,
+    #[serde(default)]
+    pub judgment: Option<Judgment>, // None for warnings not judged by a summon
+    #[serde(default)]
+    pub category: Option<InfractionCategory>, // None for warnings issued without one
+}
+
+fn default_severity() -> f64 {
+    scoring::SEVERITY_MODERATE
 }
 
 impl Display for Warning {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "Warning ID: {}. User ID: {}. Issuer ID: {}. Guild ID: {}. Reason: {}. Timestamp: {}.",
-            self.id, self.user_id, self.issuer_id, self.guild_id, self.reason, self.timestamp
+            "Warning ID: {}. User ID: {}. Issuer ID: {}. Guild ID: {}. Reason: {}. Category: {}. Severity: {}. Timestamp: {}.",
+            self.id,
+            self.user_id,
+            self.issuer_id,
+            self.guild_id,
+            self.reason,
+            self.category
+                .as_ref()
+                .map_or("none", InfractionCategory::name),
+            scoring::severity_label(self.severity),
+            self.timestamp
         ))
     }
 }
@@ -156,9 +263,10 @@ impl Display for WarningContext {
 }
 
 /// Enforcement lifecycle states
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EnforcementState {
     /// Not yet executed
+    #[default]
     Pending,
     /// Applied but waiting for duration to expire
     Active,
@@ -168,12 +276,8 @@ pub enum EnforcementState {
     Completed,
     /// Manually cancelled by moderator
     Cancelled,
-}
-
-impl Default for EnforcementState {
-    fn default() -> Self {
-        Self::Pending
-    }
+    /// Gave up after a permanent failure or running out of retries
+    Failed,
 }
 
 /// Represents an enforcement action
@@ -191,6 +295,12 @@ pub struct PendingEnforcement {
     pub executed_at: Option<DateTime<Utc>>,
     pub reversed_at: Option<DateTime<Utc>>,
     pub executed: bool, // Legacy field for backward compatibility
+    #[serde(default)]
+    pub attempts: u32, // Failed attempts at the current step, reset once it succeeds
+    #[serde(default)]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub haunt: Option<HauntProgress>, // Set once a voice channel haunt begins
 }
 
 /// Implement Display for `PendingEnforcement`
@@ -210,6 +320,8 @@ impl std::fmt::Display for PendingEnforcement {
         \nExecuted At: {:?}
         \nReversed At: {:?}
         \nExecuted: {}
+        \nAttempts: {}
+        \nLast Error: {:?}
         ",
             self.id,
             self.warning_id,
@@ -222,11 +334,75 @@ impl std::fmt::Display for PendingEnforcement {
             self.created_at,
             self.executed_at,
             self.reversed_at,
-            self.executed
+            self.executed,
+            self.attempts,
+            self.last_error
         )
     }
 }
 
+#[cfg(test)]
+impl PendingEnforcement {
+    /// An enforcement due now for user 12345 in guild 11111, for tests to
+    /// adjust with struct update syntax
+    pub fn test(id: &str, action: EnforcementAction, state: EnforcementState) -> Self {
+        let now = Utc::now();
+        let executed = state != EnforcementState::Pending;
+        Self {
+            id: id.to_string(),
+            warning_id: "warning".to_string(),
+            user_id: 12345,
+            guild_id: 11111,
+            action,
+            execute_at: now,
+            reverse_at: None,
+            state,
+            created_at: now,
+            executed_at: executed.then_some(now),
+            reversed_at: None,
+            executed,
+            attempts: 0,
+            last_error: None,
+            haunt: None,
+        }
+    }
+}
+
+/// A user's score in one infraction category
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryScore {
+    pub category: String,
+    pub score: f64,
+    pub threshold: f64,
+}
+
+/// When a user's score in one category is projected to fall back down
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreForecast {
+    pub category: String,
+    pub score: f64,
+    pub threshold: f64,
+    /// When the score drops below the threshold, `None` if not within the forecast horizon
+    pub below_threshold: Option<DateTime<Utc>>,
+    /// When the score drops below half the threshold, `None` if not within the forecast horizon
+    pub below_half: Option<DateTime<Utc>>,
+}
+
+impl ScoreForecast {
+    /// When the user will be below the threshold in every category, `None`
+    /// if some category won't be within the forecast horizon
+    #[must_use]
+    pub fn all_clear(forecasts: &[Self]) -> Option<DateTime<Utc>> {
+        forecasts
+            .iter()
+            .map(|forecast| forecast.below_threshold)
+            .try_fold(None, |latest: Option<DateTime<Utc>>, at| {
+                at.map(|at| Some(latest.map_or(at, |latest| latest.max(at))))
+            })
+            .flatten()
+    }
+}
+
 /// Tracks warning state for a user, used for the weighted warning system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserWarningState {
@@ -235,8 +411,84 @@ pub struct UserWarningState {
     pub warning_timestamps: Vec<DateTime<Utc>>, // Stored as RFC3339 strings
     pub warning_reasons: Vec<String>,
     pub mod_issuers: Vec<u64>,
+    #[serde(default)]
+    pub warning_severities: Vec<f64>, // Missing entries count as moderate
+    #[serde(default)]
+    pub warning_categories: Vec<Option<String>>, // Infraction category; None predates categories
     pub pending_enforcement: Option<EnforcementAction>,
     pub last_updated: DateTime<Utc>, // RFC3339 timestamp
+    #[serde(default)]
+    pub purged_warnings: u64, // Warnings dropped by retention sweeps
+}
+
+impl UserWarningState {
+    /// Severity of the warning at `index`, moderate if it predates severities
+    #[must_use]
+    pub fn severity(&self, index: usize) -> f64 {
+        self.warning_severities
+            .get(index)
+            .copied()
+            .unwrap_or(scoring::SEVERITY_MODERATE)
+    }
+
+    /// Each warning's timestamp with its severity
+    pub fn weighted_warnings(&self) -> impl Iterator<Item = (DateTime<Utc>, f64)> + '_ {
+        self.warning_timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, timestamp)| (*timestamp, self.severity(i)))
+    }
+
+    /// Category of the warning at `index`, if it was recorded with one
+    #[must_use]
+    pub fn category(&self, index: usize) -> Option<&str> {
+        self.warning_categories
+            .get(index)
+            .and_then(|category| category.as_deref())
+    }
+
+    /// Categories of the recorded warnings, sorted and without repeats
+    #[must_use]
+    pub fn categories(&self) -> Vec<String> {
+        let categories: std::collections::BTreeSet<String> =
+            self.warning_categories.iter().flatten().cloned().collect();
+        categories.into_iter().collect()
+    }
+
+    /// A copy holding only the warnings of one category
+    #[must_use]
+    pub fn for_category(&self, category: &str) -> Self {
+        let keep: Vec<bool> = (0..self.warning_timestamps.len())
+            .map(|i| self.category(i) == Some(category))
+            .collect();
+        let mut state = self.clone();
+        state.retain_warnings(&keep);
+        state
+    }
+
+    /// Keep only the warnings whose flag in `keep` is set, across every parallel list
+    pub fn retain_warnings(&mut self, keep: &[bool]) {
+        fn retain<T>(list: &mut Vec<T>, keep: &[bool]) {
+            let mut flags = keep.iter();
+            list.retain(|_| flags.next().copied().unwrap_or(true));
+        }
+
+        self.align_parallel_lists();
+        retain(&mut self.warning_timestamps, keep);
+        retain(&mut self.warning_reasons, keep);
+        retain(&mut self.mod_issuers, keep);
+        retain(&mut self.warning_severities, keep);
+        retain(&mut self.warning_categories, keep);
+    }
+
+    /// Fill in severities and categories for warnings that predate them, so
+    /// every parallel list lines up with the timestamps again
+    pub fn align_parallel_lists(&mut self) {
+        let len = self.warning_timestamps.len();
+        self.warning_severities
+            .resize(len, scoring::SEVERITY_MODERATE);
+        self.warning_categories.resize(len, None);
+    }
 }
 
 /// Centralized data structure for the bot
@@ -262,6 +514,7 @@ impl std::fmt::Debug for Data {
             .field("warnings", &self.warnings)
             .field("pending_enforcements", &self.pending_enforcements)
             .field("enforcement_tx", &self.enforcement_tx)
+            .field("storage", &self.storage.name())
             .finish()
     }
 }
@@ -277,10 +530,10 @@ impl Data {
             .clone()
     }
 
-    /// Get the cache
+    /// Get the cache, or an empty one until the bot's cache is ready
     #[must_use]
     pub fn get_cache(&self) -> Arc<serenity::Cache> {
-        Arc::clone(&self.0.cache)
+        self.0.cache.get().cloned().unwrap_or_default()
     }
 }
 
@@ -292,12 +545,6 @@ impl Deref for Data {
     }
 }
 
-impl DerefMut for Data {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        Arc::make_mut(&mut self.0)
-    }
-}
-
 impl Data {
     /// Create a new Data instance
     #[must_use]
@@ -306,25 +553,179 @@ impl Data {
     }
 
     /// Set the enforcement task sender
-    pub fn set_enforcement_tx(&mut self, tx: Sender<EnforcementCheckRequest>) {
-        Arc::make_mut(&mut self.0).enforcement_tx = Arc::new(Some(tx));
+    ///
+    /// Every handle to the data sees the sender, including ones cloned before
+    /// it was set. Only the first sender set is kept.
+    pub fn set_enforcement_tx(&self, tx: Sender<EnforcementCheckRequest>) {
+        if self.0.enforcement_tx.set(tx).is_err() {
+            tracing::warn!("Enforcement sender was already set, keeping the first one");
+        }
     }
 
-    /// Load data from YAML file
-    pub async fn load() -> Self {
-        Self(Arc::new(DataInner::load().await))
+    /// Set the bot's cache once it's ready
+    ///
+    /// Like the enforcement sender, every handle to the data sees it. Only the
+    /// first cache set is kept.
+    pub fn set_cache(&self, cache: Arc<serenity::Cache>) {
+        if self.0.cache.set(cache).is_err() {
+            tracing::warn!("Cache was already set, keeping the first one");
+        }
+    }
+
+    /// Load data from the given storage backend
+    ///
+    /// # Errors
+    /// See [`DataInner::load`].
+    pub async fn load(
+        storage: Arc<dyn Storage>,
+        policy: CorruptionPolicy,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(Arc::new(DataInner::load(storage, policy).await?)))
     }
 
-    /// Save data to YAML file
+    /// Save changed records to the storage backend
+    ///
+    /// Normally only the autosave task calls this; everything else just marks
+    /// records as changed.
     /// # Errors
     /// This function will return an error if:
-    /// - The config directory cannot be created
-    /// - The guild configurations cannot be serialized to YAML
-    /// - The YAML data cannot be written to the config file
+    /// - The changed records cannot be serialized
+    /// - The storage backend fails to write them
     pub async fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.0.save().await
     }
 
+    /// Store a guild configuration and mark it for saving
+    pub fn set_guild_config(&self, config: GuildConfig) {
+        self.0.dirty.guild_configs.insert(config.guild_id);
+        self.0
+            .guild_configs
+            .insert(serenity::GuildId::new(config.guild_id), config);
+        self.0.dirty.changed.notify_one();
+    }
+
+    /// Store a warning and mark it for saving
+    pub fn insert_warning(&self, warning: Warning) {
+        self.0.dirty.warnings.insert(warning.id.clone());
+        // Unindex any old copy first, in case it's filed under the same user
+        if let Some((_, old)) = self.0.warnings.remove(&warning.id) {
+            self.0.unindex_warning(&old);
+        }
+        self.0.index_warning(&warning);
+        self.0.warnings.insert(warning.id.clone(), warning);
+        self.0.dirty.changed.notify_one();
+    }
+
+    /// Store a user's warning state and mark it for saving
+    pub fn set_user_warning_state(&self, state: UserWarningState) {
+        let key = storage::user_state_key(state.user_id, state.guild_id);
+        self.0.dirty.user_warning_states.insert(key.clone());
+        self.0.user_warning_states.insert(key, state);
+        self.0.dirty.changed.notify_one();
+    }
+
+    /// Store an enforcement in the map matching its state and mark it for saving
+    ///
+    /// Pending enforcements go to `pending_enforcements`, active ones to
+    /// `active_enforcements` and everything else to `completed_enforcements`.
+    /// Any copy in the other maps is removed, so this also moves an enforcement
+    /// along its lifecycle.
+    pub fn store_enforcement(&self, enforcement: PendingEnforcement) {
+        let id = enforcement.id.clone();
+        self.0.dirty.enforcements.insert(id.clone());
+        let old = [
+            self.0.pending_enforcements.remove(&id),
+            self.0.active_enforcements.remove(&id),
+            self.0.completed_enforcements.remove(&id),
+        ];
+        for (_, old) in old.into_iter().flatten() {
+            self.0.unindex_enforcement(&old);
+        }
+        self.0.index_enforcement(&enforcement);
+        self.0.schedule.update(&enforcement);
+
+        match enforcement.state {
+            EnforcementState::Pending => {
+                self.0.pending_enforcements.insert(id, enforcement);
+            }
+            EnforcementState::Active => {
+                self.0.active_enforcements.insert(id, enforcement);
+            }
+            _ => {
+                self.0.completed_enforcements.insert(id, enforcement);
+            }
+        }
+        self.0.dirty.changed.notify_one();
+    }
+
+    /// Remove a warning and mark it for saving
+    pub fn remove_warning(&self, id: &str) -> Option<Warning> {
+        let (id, warning) = self.0.warnings.remove(id)?;
+        self.0.unindex_warning(&warning);
+        self.0.dirty.warnings.insert(id);
+        self.0.dirty.changed.notify_one();
+        Some(warning)
+    }
+
+    /// Remove an enforcement from whichever map holds it and mark it for saving
+    pub fn remove_enforcement(&self, id: &str) -> Option<PendingEnforcement> {
+        let (id, enforcement) = self
+            .0
+            .pending_enforcements
+            .remove(id)
+            .or_else(|| self.0.active_enforcements.remove(id))
+            .or_else(|| self.0.completed_enforcements.remove(id))?;
+        self.0.unindex_enforcement(&enforcement);
+        self.0.schedule.remove(&id);
+        self.0.dirty.enforcements.insert(id);
+        self.0.dirty.changed.notify_one();
+        Some(enforcement)
+    }
+
+    /// Remove a user's warning state and mark it for saving
+    pub fn remove_user_warning_state(
+        &self,
+        user_id: u64,
+        guild_id: u64,
+    ) -> Option<UserWarningState> {
+        let (key, state) = self
+            .0
+            .user_warning_states
+            .remove(&storage::user_state_key(user_id, guild_id))?;
+        self.0.dirty.user_warning_states.insert(key);
+        self.0.dirty.changed.notify_one();
+        Some(state)
+    }
+
+    /// Get a user's warnings in a guild
+    #[must_use]
+    pub fn user_warnings(&self, user_id: u64, guild_id: u64) -> Vec<Warning> {
+        self.0
+            .warnings_by_user
+            .ids(guild_id, user_id)
+            .iter()
+            .filter_map(|id| self.0.warnings.get(id).map(|entry| entry.value().clone()))
+            .collect()
+    }
+
+    /// Get a user's enforcements in a guild, whatever their state
+    #[must_use]
+    pub fn user_enforcements(&self, user_id: u64, guild_id: u64) -> Vec<PendingEnforcement> {
+        self.0
+            .enforcements_by_user
+            .ids(guild_id, user_id)
+            .iter()
+            .filter_map(|id| {
+                self.0
+                    .pending_enforcements
+                    .get(id)
+                    .or_else(|| self.0.active_enforcements.get(id))
+                    .or_else(|| self.0.completed_enforcements.get(id))
+                    .map(|entry| entry.value().clone())
+            })
+            .collect()
+    }
+
     /// Get the enforcement task sender
     #[must_use]
     pub fn get_warnings(&self) -> Vec<Warning> {
@@ -358,7 +759,7 @@ impl Data {
         user_id: u64,
         guild_id: u64,
     ) -> UserWarningState {
-        let key = format!("{user_id}:{guild_id}");
+        let key = storage::user_state_key(user_id, guild_id);
         if let Some(state) = self.0.user_warning_states.get(&key) {
             state.value().clone()
         } else {
@@ -368,8 +769,11 @@ impl Data {
                 warning_timestamps: Vec::new(),
                 warning_reasons: Vec::new(),
                 mod_issuers: Vec::new(),
+                warning_severities: Vec::new(),
+                warning_categories: Vec::new(),
                 pending_enforcement: None,
                 last_updated: chrono::Utc::now(),
+                purged_warnings: 0,
             }
         }
     }
@@ -382,21 +786,40 @@ impl Data {
         guild_id: u64,
         reason: String,
         issuer_id: u64,
+        severity: f64,
+        category: &str,
+    ) -> UserWarningState {
+        let state = self.project_warning(user_id, guild_id, reason, issuer_id, severity, category);
+        self.set_user_warning_state(state.clone());
+        state
+    }
+
+    /// A user's warning state as it would be with one more warning, without
+    /// storing it
+    #[must_use]
+    pub fn project_warning(
+        &self,
+        user_id: u64,
+        guild_id: u64,
+        reason: String,
+        issuer_id: u64,
+        severity: f64,
+        category: &str,
     ) -> UserWarningState {
-        let key = format!("{user_id}:{guild_id}");
         let timestamp = Utc::now();
 
         let mut state = self.get_or_create_user_warning_state(user_id, guild_id);
+        state.align_parallel_lists();
         state.warning_timestamps.push(timestamp);
         state.warning_reasons.push(reason);
         state.mod_issuers.push(issuer_id);
+        state.warning_severities.push(severity);
+        state.warning_categories.push(Some(category.to_string()));
         state.last_updated = timestamp;
-
-        self.0.user_warning_states.insert(key, state.clone());
         state
     }
 
-    /// Calculate a weighted warning score for a user based on recency and mod diversity
+    /// Calculate a user's warning score with the strategy their guild selected
     /// Returns a score from 0.0 to infinity where higher scores mean more warnings
     #[must_use]
     pub fn calculate_warning_score(&self, user_id: u64, guild_id: u64) -> f64 {
@@ -405,38 +828,120 @@ impl Data {
             return 0.0;
         }
 
-        let now = chrono::Utc::now();
-        let mut total_score = 0.0;
-        let mut unique_mods = std::collections::HashSet::new();
-
-        // Calculate score for each warning based on recency
-        for (i, timestamp) in state.warning_timestamps.iter().enumerate() {
-            let age_hours = (now - timestamp.with_timezone(&Utc)).num_seconds() as f64 / 3600.0;
-            let weight = (-DECAY_RATE * age_hours).exp(); // Exponential decay based on age
-            total_score += weight;
+        self.get_guild_config(serenity::GuildId::new(guild_id))
+            .scoring_strategy()
+            .score(&state, Utc::now())
+    }
 
-            // Track unique mods who issued warnings
-            if i < state.mod_issuers.len() {
-                unique_mods.insert(state.mod_issuers[i]);
-            }
+    /// Calculate a user's warning score from the warnings of one category only
+    #[must_use]
+    pub fn calculate_category_score(&self, user_id: u64, guild_id: u64, category: &str) -> f64 {
+        let state = self
+            .get_or_create_user_warning_state(user_id, guild_id)
+            .for_category(category);
+        if state.warning_timestamps.is_empty() {
+            return 0.0;
         }
 
-        // Apply a bonus if multiple mods issued warnings (more credible reports)
-        if unique_mods.len() > 1 {
-            total_score += MOD_DIVERSITY_BONUS * (unique_mods.len() as f64 - 1.0);
+        self.get_guild_config(serenity::GuildId::new(guild_id))
+            .scoring_strategy()
+            .score(&state, Utc::now())
+    }
+
+    /// A user's score in each category they've been warned in, with the
+    /// category's threshold
+    #[must_use]
+    pub fn score_breakdown(&self, user_id: u64, guild_id: u64) -> Vec<CategoryScore> {
+        let state = self.get_or_create_user_warning_state(user_id, guild_id);
+        let config = self.get_guild_config(serenity::GuildId::new(guild_id));
+        let strategy = config.scoring_strategy();
+        let now = Utc::now();
+
+        state
+            .categories()
+            .into_iter()
+            .map(|category| CategoryScore {
+                score: strategy.score(&state.for_category(&category), now),
+                threshold: config.threshold_for(&category),
+                category,
+            })
+            .collect()
+    }
+
+    /// Project a user's score in each category forward to when it falls below
+    /// the category's threshold and half of it
+    ///
+    /// Users whose warnings all predate categories get a single `overall`
+    /// forecast against the guild threshold.
+    #[must_use]
+    pub fn forecast(&self, user_id: u64, guild_id: u64, now: DateTime<Utc>) -> Vec<ScoreForecast> {
+        let state = self.get_or_create_user_warning_state(user_id, guild_id);
+        let config = self.get_guild_config(serenity::GuildId::new(guild_id));
+        let strategy = config.scoring_strategy();
+
+        let mut scopes: Vec<(String, UserWarningState, f64)> = state
+            .categories()
+            .into_iter()
+            .map(|category| {
+                let threshold = config.threshold_for(&category);
+                let scoped = state.for_category(&category);
+                (category, scoped, threshold)
+            })
+            .collect();
+        if scopes.is_empty() && !state.warning_timestamps.is_empty() {
+            scopes.push(("overall".to_string(), state, config.warning_threshold));
         }
 
-        total_score
+        scopes
+            .into_iter()
+            .map(|(category, scoped, threshold)| ScoreForecast {
+                score: strategy.score(&scoped, now),
+                below_threshold: scoring::time_below(strategy.as_ref(), &scoped, now, threshold),
+                below_half: scoring::time_below(strategy.as_ref(), &scoped, now, threshold / 2.0),
+                threshold,
+                category,
+            })
+            .collect()
+    }
+}
+
+/// Index from a user in a guild to the IDs of their records
+///
+/// Kept up to date by the `Data` helpers, so per-user lookups don't have to
+/// scan every record.
+#[derive(Debug, Clone, Default)]
+pub struct UserIndex(DashMap<(u64, u64), HashSet<String>>);
+
+impl UserIndex {
+    /// Add a record ID under a user
+    pub fn insert(&self, guild_id: u64, user_id: u64, id: String) {
+        self.0.entry((guild_id, user_id)).or_default().insert(id);
+    }
+
+    /// Remove a record ID from a user, dropping the entry once it's empty
+    pub fn remove(&self, guild_id: u64, user_id: u64, id: &str) {
+        self.0.remove_if_mut(&(guild_id, user_id), |_, ids| {
+            ids.remove(id);
+            ids.is_empty()
+        });
+    }
+
+    /// IDs of a user's records
+    #[must_use]
+    pub fn ids(&self, guild_id: u64, user_id: u64) -> Vec<String> {
+        self.0
+            .get(&(guild_id, user_id))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
     }
 }
 
 /// Main centralized data structure for the bot
-#[derive(Clone)]
 pub struct DataInner {
     // Map of guild_id -> guild configuration
     pub guild_configs: DashMap<serenity::GuildId, GuildConfig>,
-    // Cache from the bot's context
-    pub cache: Arc<serenity::Cache>,
+    // Cache from the bot's context, set once it's ready
+    pub cache: Arc<OnceLock<Arc<serenity::Cache>>>,
     // Http client for making requests
     // pub http: Arc<serenity::Http>,
     // Map of warning_id -> warning
@@ -449,10 +954,24 @@ pub struct DataInner {
     pub completed_enforcements: DashMap<String, PendingEnforcement>,
     // Map of user_id+guild_id -> user warning state
     pub user_warning_states: DashMap<String, UserWarningState>,
+    // Index of (guild_id, user_id) -> warning IDs
+    pub warnings_by_user: UserIndex,
+    // Index of (guild_id, user_id) -> enforcement IDs, whichever map holds them
+    pub enforcements_by_user: UserIndex,
     // Channel to send enforcement check requests
-    pub enforcement_tx: Arc<Option<Sender<EnforcementCheckRequest>>>,
+    pub enforcement_tx: Arc<OnceLock<Sender<EnforcementCheckRequest>>>,
+    // Deadlines of pending and active enforcements, earliest first
+    pub schedule: Arc<Schedule>,
+    // Jobs teleporting users around during voice channel haunts
+    pub haunts: Arc<HauntJobs>,
     // Status tracking for the bot's state and active voice channels
     pub status: Arc<RwLock<BotStatus>>,
+    // Backend the data is persisted to
+    pub storage: Arc<dyn Storage>,
+    // Records changed since the last save
+    pub dirty: Arc<DirtyRecords>,
+    // Set when damaged data was loaded read-only; nothing is saved
+    pub read_only: bool,
 }
 
 impl Default for DataInner {
@@ -467,191 +986,253 @@ impl DataInner {
     pub fn new() -> Self {
         Self {
             guild_configs: DashMap::new(),
-            cache: Arc::new(serenity::Cache::default()),
+            cache: Arc::new(OnceLock::new()),
             warnings: DashMap::new(),
             pending_enforcements: DashMap::new(),
             active_enforcements: DashMap::new(),
             completed_enforcements: DashMap::new(),
             user_warning_states: DashMap::new(),
-            enforcement_tx: Arc::new(None),
+            enforcement_tx: Arc::new(OnceLock::new()),
+            schedule: Arc::new(Schedule::default()),
+            haunts: Arc::new(HauntJobs::default()),
             status: Arc::new(RwLock::new(BotStatus::new())),
+            storage: Arc::new(YamlStorage::new(storage::DEFAULT_DATA_DIR)),
+            dirty: Arc::new(DirtyRecords::default()),
+            read_only: false,
+            warnings_by_user: UserIndex::default(),
+            enforcements_by_user: UserIndex::default(),
         }
     }
 
-    /// Load data from YAML file
+    /// Load data from a storage backend
     ///
-    /// This method loads guild configurations from a YAML file.
-    /// If the file doesn't exist, it returns a new empty Data instance.
-    pub async fn load() -> Self {
-        const CONFIG_FILE: &str = "data/bot_config.yaml";
-        const WARNINGS_FILE: &str = "data/warnings.yaml";
-        const ENFORCEMENTS_FILE: &str = "data/enforcements.yaml";
-        const WARNING_STATES_FILE: &str = "data/warning_states.yaml";
-
-        // Create a new empty Data instance
+    /// Damaged data is handled according to `policy`: by default loading fails,
+    /// so a damaged file can never be silently replaced with an empty one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend can't be read, or if it holds damaged
+    /// data and the policy is [`CorruptionPolicy::Fail`].
+    pub async fn load(
+        storage: Arc<dyn Storage>,
+        policy: CorruptionPolicy,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let snapshot = storage
+            .load()
+            .await
+            .map_err(|e| format!("Failed to load data from {} storage: {e}", storage.name()))?;
+
+        let mut read_only = false;
+        if !snapshot.corruption.is_empty() {
+            for damage in &snapshot.corruption {
+                tracing::error!("Damaged data: {damage}");
+            }
+            match policy {
+                CorruptionPolicy::Fail => {
+                    let damage: Vec<String> = snapshot
+                        .corruption
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect();
+                    return Err(format!(
+                        "Found damaged data in {} storage: {}. Set DATA_ON_CORRUPTION to read_only or salvage to start with what could be salvaged",
+                        storage.name(),
+                        damage.join("; ")
+                    )
+                    .into());
+                }
+                CorruptionPolicy::ReadOnly => {
+                    tracing::warn!(
+                        "Starting read-only with salvaged data; no changes will be saved"
+                    );
+                    read_only = true;
+                }
+                CorruptionPolicy::Salvage => {
+                    storage.repair().await?;
+                    tracing::warn!("Replaced damaged data with the salvaged records");
+                }
+            }
+        }
+
+        let mut data = Self::from_snapshot(snapshot);
+        data.storage = storage;
+        data.read_only = read_only;
+        Ok(data)
+    }
+
+    /// Build the in-memory maps from a set of persisted records
+    #[must_use]
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
         let data = Self::new();
 
-        // Check if the config file exists
-        if let Ok(file_content) = tokio::fs::read_to_string(CONFIG_FILE).await {
-            // Try to deserialize the file content
-            if let Ok(configs) = serde_yaml::from_str::<Vec<GuildConfig>>(&file_content) {
-                // Add each guild config to the map
-                for config in configs {
-                    let guild_id = serenity::GuildId::new(config.guild_id);
-                    data.guild_configs.insert(guild_id, config);
+        // Add each guild config to the map
+        for config in snapshot.guild_configs {
+            let guild_id = serenity::GuildId::new(config.guild_id);
+            data.guild_configs.insert(guild_id, config);
+        }
+
+        for warning in snapshot.warnings {
+            data.index_warning(&warning);
+            data.warnings.insert(warning.id.clone(), warning);
+        }
+
+        let mut deadlines = Vec::new();
+        for enforcement in snapshot.enforcements {
+            data.index_enforcement(&enforcement);
+            deadlines.extend(Deadline::of(&enforcement));
+            // Store in the appropriate map based on state
+            match enforcement.state {
+                EnforcementState::Pending => {
+                    data.pending_enforcements
+                        .insert(enforcement.id.clone(), enforcement);
+                }
+                EnforcementState::Active => {
+                    data.active_enforcements
+                        .insert(enforcement.id.clone(), enforcement);
+                }
+                _ => {
+                    data.completed_enforcements
+                        .insert(enforcement.id.clone(), enforcement);
                 }
             }
         }
 
-        // Load warnings
-        if let Ok(file_content) = tokio::fs::read_to_string(WARNINGS_FILE).await {
-            if let Ok(warnings) = serde_yaml::from_str::<Vec<Warning>>(&file_content) {
-                for warning in warnings {
-                    data.warnings.insert(warning.id.clone(), warning);
-                }
+        data.schedule.rebuild(deadlines);
+
+        for state in snapshot.user_warning_states {
+            let key = storage::user_state_key(state.user_id, state.guild_id);
+            data.user_warning_states.insert(key, state);
+        }
+
+        data
+    }
+
+    fn index_warning(&self, warning: &Warning) {
+        self.warnings_by_user
+            .insert(warning.guild_id, warning.user_id, warning.id.clone());
+    }
+
+    fn unindex_warning(&self, warning: &Warning) {
+        self.warnings_by_user
+            .remove(warning.guild_id, warning.user_id, &warning.id);
+    }
+
+    fn index_enforcement(&self, enforcement: &PendingEnforcement) {
+        self.enforcements_by_user.insert(
+            enforcement.guild_id,
+            enforcement.user_id,
+            enforcement.id.clone(),
+        );
+    }
+
+    fn unindex_enforcement(&self, enforcement: &PendingEnforcement) {
+        self.enforcements_by_user.remove(
+            enforcement.guild_id,
+            enforcement.user_id,
+            &enforcement.id,
+        );
+    }
+
+    /// Collect every changed record into a change set, clearing the dirty marks
+    ///
+    /// Records that were marked but no longer exist are reported as removed.
+    #[must_use]
+    pub fn take_changes(&self) -> ChangeSet {
+        let mut changes = ChangeSet::default();
+
+        for guild_id in storage::drain(&self.dirty.guild_configs) {
+            if let Some(config) = self.guild_configs.get(&serenity::GuildId::new(guild_id)) {
+                changes.guild_configs.push(config.value().clone());
             }
         }
 
-        // Load pending enforcements
-        if let Ok(file_content) = tokio::fs::read_to_string(ENFORCEMENTS_FILE).await {
-            if let Ok(enforcements) = serde_yaml::from_str::<Vec<PendingEnforcement>>(&file_content)
-            {
-                for mut enforcement in enforcements {
-                    // Add new fields for backward compatibility if they don't exist
-                    if enforcement.reverse_at.is_none() {
-                        enforcement.reverse_at = None;
-                    }
-                    if enforcement.executed_at.is_none() {
-                        enforcement.executed_at = None;
-                    }
-                    if enforcement.reversed_at.is_none() {
-                        enforcement.reversed_at = None;
-                    }
-
-                    // Set state based on legacy executed field
-                    if enforcement.executed {
-                        enforcement.state = EnforcementState::Completed;
-                    } else {
-                        enforcement.state = EnforcementState::Pending;
-                    }
-
-                    // Store in the appropriate map based on state
-                    match enforcement.state {
-                        EnforcementState::Pending => {
-                            data.pending_enforcements
-                                .insert(enforcement.id.clone(), enforcement);
-                        }
-                        EnforcementState::Active => {
-                            data.active_enforcements
-                                .insert(enforcement.id.clone(), enforcement);
-                        }
-                        _ => {
-                            data.completed_enforcements
-                                .insert(enforcement.id.clone(), enforcement);
-                        }
-                    }
-                }
+        for id in storage::drain(&self.dirty.warnings) {
+            match self.warnings.get(&id) {
+                Some(warning) => changes.warnings.push(warning.value().clone()),
+                None => changes.removed_warnings.push(id),
             }
         }
 
-        // Load user warning states
-        if let Ok(file_content) = tokio::fs::read_to_string(WARNING_STATES_FILE).await {
-            if let Ok(states) = serde_yaml::from_str::<Vec<UserWarningState>>(&file_content) {
-                for state in states {
-                    let key = format!("{}:{}", state.user_id, state.guild_id);
-                    data.user_warning_states.insert(key, state);
-                }
+        for id in storage::drain(&self.dirty.enforcements) {
+            let enforcement = self
+                .pending_enforcements
+                .get(&id)
+                .or_else(|| self.active_enforcements.get(&id))
+                .or_else(|| self.completed_enforcements.get(&id))
+                .map(|entry| entry.value().clone());
+            match enforcement {
+                Some(enforcement) => changes.enforcements.push(enforcement),
+                None => changes.removed_enforcements.push(id),
             }
         }
 
-        data
+        for key in storage::drain(&self.dirty.user_warning_states) {
+            match self.user_warning_states.get(&key) {
+                Some(state) => changes.user_warning_states.push(state.value().clone()),
+                None => changes.removed_user_warning_states.push(key),
+            }
+        }
+
+        changes
     }
 
-    /// Save data to YAML file
+    /// Save every changed record to the storage backend
     ///
-    /// This method saves all guild configurations to a YAML file.
-    /// It creates the config directory if it doesn't exist.
+    /// Only records marked as changed since the last save are written.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - The config directory cannot be created
-    /// - The guild configurations cannot be serialized to YAML
-    /// - The YAML data cannot be written to the config file
+    /// This function will return an error if the storage backend fails to
+    /// write the changes. The changes are marked dirty again so the next
+    /// save retries them.
     pub async fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        const CONFIG_DIR: &str = "data";
-        const CONFIG_FILE: &str = "data/bot_config.yaml";
-        const WARNINGS_FILE: &str = "data/warnings.yaml";
-        const ENFORCEMENTS_FILE: &str = "data/enforcements.yaml";
-        const WARNING_STATES_FILE: &str = "data/warning_states.yaml";
-
-        // Create the config directory if it doesn't exist
-        if !std::path::Path::new(CONFIG_DIR).exists() {
-            tokio::fs::create_dir_all(CONFIG_DIR).await?;
+        let changes = self.take_changes();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if self.read_only {
+            tracing::warn!("Not saving changes: data was loaded read-only after damage was found");
+            return Ok(());
         }
 
-        // Collect all guild configs into a Vec for serialization
-        let configs: Vec<GuildConfig> = self
-            .guild_configs
-            .iter()
-            .map(|entry| entry.value().clone())
-            .collect();
-
-        // Serialize the configs to YAML
-        let yaml = serde_yaml::to_string(&configs)?;
+        if let Err(e) = self.storage.apply(changes.clone()).await {
+            self.mark_dirty(&changes);
+            return Err(e);
+        }
 
-        // Write the YAML to the config file
-        tokio::fs::write(CONFIG_FILE, yaml).await?;
+        Ok(())
+    }
 
-        // Save warnings
-        let warnings: Vec<Warning> = self
+    /// Mark every record in a change set as changed again
+    fn mark_dirty(&self, changes: &ChangeSet) {
+        for config in &changes.guild_configs {
+            self.dirty.guild_configs.insert(config.guild_id);
+        }
+        for id in changes
             .warnings
             .iter()
-            .map(|entry| entry.value().clone())
-            .collect();
-        let warnings_yaml = serde_yaml::to_string(&warnings)?;
-        tokio::fs::write(WARNINGS_FILE, warnings_yaml).await?;
-
-        // Save all enforcements to a single file for backward compatibility
-        let mut all_enforcements = Vec::new();
-
-        // Add pending enforcements
-        all_enforcements.extend(
-            self.pending_enforcements
-                .iter()
-                .map(|entry| entry.value().clone())
-                .collect::<Vec<PendingEnforcement>>(),
-        );
-
-        // Add active enforcements
-        all_enforcements.extend(
-            self.active_enforcements
-                .iter()
-                .map(|entry| entry.value().clone())
-                .collect::<Vec<PendingEnforcement>>(),
-        );
-
-        // Add completed enforcements
-        all_enforcements.extend(
-            self.completed_enforcements
-                .iter()
-                .map(|entry| entry.value().clone())
-                .collect::<Vec<PendingEnforcement>>(),
-        );
-
-        let enforcements_yaml = serde_yaml::to_string(&all_enforcements)?;
-        tokio::fs::write(ENFORCEMENTS_FILE, enforcements_yaml).await?;
-
-        // Save user warning states
-        let warning_states: Vec<UserWarningState> = self
-            .user_warning_states
+            .map(|warning| &warning.id)
+            .chain(&changes.removed_warnings)
+        {
+            self.dirty.warnings.insert(id.clone());
+        }
+        for id in changes
+            .enforcements
             .iter()
-            .map(|entry| entry.value().clone())
-            .collect();
-        let warning_states_yaml = serde_yaml::to_string(&warning_states)?;
-        tokio::fs::write(WARNING_STATES_FILE, warning_states_yaml).await?;
-
-        Ok(())
+            .map(|enforcement| &enforcement.id)
+            .chain(&changes.removed_enforcements)
+        {
+            self.dirty.enforcements.insert(id.clone());
+        }
+        for state in &changes.user_warning_states {
+            self.dirty
+                .user_warning_states
+                .insert(storage::user_state_key(state.user_id, state.guild_id));
+        }
+        for key in &changes.removed_user_warning_states {
+            self.dirty.user_warning_states.insert(key.clone());
+        }
+        self.dirty.changed.notify_one();
     }
 }
 
@@ -664,11 +1245,21 @@ mod tests {
     fn test_data_new() {
         let data = Data::new();
         assert_eq!(data.guild_configs.len(), 0);
-        assert!(data.cache.guilds().is_empty());
+        assert!(data.get_cache().guilds().is_empty());
         assert_eq!(data.warnings.len(), 0);
         assert_eq!(data.pending_enforcements.len(), 0);
     }
 
+    #[test]
+    fn test_cache_set_later_reaches_every_handle() {
+        let data = Data::new();
+        let handler = data.clone();
+        let cache = Arc::new(serenity::Cache::default());
+        data.set_cache(Arc::clone(&cache));
+        assert!(Arc::ptr_eq(&handler.get_cache(), &cache));
+        assert!(Arc::ptr_eq(&handler.0, &data.0));
+    }
+
     #[test]
     fn test_guild_config_default() {
         let config = GuildConfig::default();
@@ -739,6 +1330,9 @@ mod tests {
             timestamp: "2023-01-01T00:00:00Z".parse().unwrap(),
             notification_method: NotificationMethod::PublicWithMention,
             enforcement: Some(EnforcementAction::Kick { delay: Some(86400) }),
+            severity: scoring::SEVERITY_SEVERE,
+            judgment: None,
+            category: None,
         };
 
         let serialized = serde_yaml::to_string(&warning).expect("Failed to serialize");
@@ -752,6 +1346,7 @@ mod tests {
             serde_yaml::from_str(&serialized).expect("Failed to deserialize");
         assert_eq!(deserialized.id, "test-id");
         assert_eq!(deserialized.user_id, 12345);
+        assert!((deserialized.severity - scoring::SEVERITY_SEVERE).abs() < f64::EPSILON);
         assert!(matches!(
             deserialized.notification_method,
             NotificationMethod::PublicWithMention
@@ -788,6 +1383,9 @@ mod tests {
             executed_at: None,
             reversed_at: None,
             executed: false,
+            attempts: 0,
+            last_error: None,
+            haunt: None,
         };
 
         let serialized = serde_yaml::to_string(&enforcement).expect("Failed to serialize");
@@ -807,4 +1405,192 @@ mod tests {
             panic!("Expected Ban enforcement");
         }
     }
+
+    #[test]
+    fn test_warning_score_uses_guild_settings() {
+        let data = Data::new();
+        let _ = data.add_to_user_warning_state(12345, 11111, "Loud".to_string(), 1, 1.0, "voice");
+        let _ = data.add_to_user_warning_state(12345, 11111, "Louder".to_string(), 2, 1.0, "voice");
+        let default_score = data.calculate_warning_score(12345, 11111);
+
+        let mut config = GuildConfig::new(11111);
+        config.mod_diversity_bonus = 0.0;
+        data.set_guild_config(config);
+        let score = data.calculate_warning_score(12345, 11111);
+        assert!((default_score - score - scoring::MOD_DIVERSITY_BONUS).abs() < 0.01);
+
+        // Configs saved before these settings existed get the defaults
+        let config: GuildConfig = serde_yaml::from_str(
+            "guild_id: 1\nmusic_channel_id: null\ndefault_notification_method: DirectMessage\ndefault_enforcement: null\nenforcement_log_channel_id: null\nchaos_factor: 0.3\nwarning_threshold: 3.0\n",
+        )
+        .unwrap();
+        assert!((config.warning_threshold - 3.0).abs() < f64::EPSILON);
+        assert!((config.decay_rate - scoring::DECAY_RATE).abs() < f64::EPSILON);
+        assert_eq!(config.scoring, ScoringConfig::Decay);
+    }
+
+    #[test]
+    fn test_categories_have_separate_scores() {
+        let data = Data::new();
+        let mut config = GuildConfig::new(11111);
+        config.mod_diversity_bonus = 0.0;
+        config.category_thresholds.insert("text".to_string(), 5.0);
+        data.set_guild_config(config);
+
+        for _ in 0..3 {
+            let _ =
+                data.add_to_user_warning_state(12345, 11111, "Spam".to_string(), 1, 1.0, "text");
+        }
+        let mut state =
+            data.add_to_user_warning_state(12345, 11111, "Loud".to_string(), 1, 1.0, "voice");
+
+        // Text warnings don't count toward the voice track
+        assert!(data.calculate_category_score(12345, 11111, "voice") < 1.01);
+        assert!(data.calculate_category_score(12345, 11111, "text") > 2.99);
+        assert!(data.calculate_warning_score(12345, 11111) > 3.99);
+
+        let breakdown = data.score_breakdown(12345, 11111);
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[0].category, "text");
+        assert!((breakdown[0].threshold - 5.0).abs() < f64::EPSILON);
+        assert!((breakdown[1].threshold - scoring::WARNING_THRESHOLD).abs() < f64::EPSILON);
+
+        // Warnings recorded before categories only count toward the overall score
+        state.warning_timestamps.insert(0, Utc::now());
+        state.warning_categories.remove(0);
+        state.warning_categories.truncate(3);
+        data.set_user_warning_state(state);
+        assert!(data.calculate_category_score(12345, 11111, "voice") < 1.01);
+        assert!(data.calculate_warning_score(12345, 11111) > 4.99);
+    }
+
+    #[test]
+    fn test_projected_warning_is_not_stored() {
+        let data = Data::new();
+        let _ = data.add_to_user_warning_state(12345, 11111, "Loud".to_string(), 1, 1.0, "voice");
+
+        let projected = data.project_warning(12345, 11111, "Louder".to_string(), 2, 2.0, "voice");
+        assert_eq!(projected.warning_timestamps.len(), 2);
+        assert_eq!(projected.category(1), Some("voice"));
+        assert_eq!(
+            data.get_or_create_user_warning_state(12345, 11111)
+                .warning_timestamps
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_forecast_per_category() {
+        let data = Data::new();
+        let mut config = GuildConfig::new(11111);
+        config.mod_diversity_bonus = 0.0;
+        data.set_guild_config(config);
+        let now = Utc::now();
+        assert!(data.forecast(12345, 11111, now).is_empty());
+
+        for _ in 0..3 {
+            let _ =
+                data.add_to_user_warning_state(12345, 11111, "Spam".to_string(), 1, 1.0, "text");
+        }
+        let _ = data.add_to_user_warning_state(12345, 11111, "Loud".to_string(), 1, 1.0, "voice");
+
+        let forecasts = data.forecast(12345, 11111, now);
+        assert_eq!(forecasts.len(), 2);
+        let (text, voice) = (&forecasts[0], &forecasts[1]);
+        // Voice is already below the threshold but not yet below half of it
+        assert_eq!(voice.below_threshold, Some(now));
+        assert!(voice.below_half.unwrap() > now);
+        // Text has further to fall
+        let text_clear = text.below_threshold.unwrap();
+        assert!(text_clear > now && text.below_half.unwrap() > text_clear);
+        assert_eq!(ScoreForecast::all_clear(&forecasts), Some(text_clear));
+    }
+
+    #[test]
+    fn test_user_indexes_follow_records() {
+        let data = Data::new();
+        let warning = Warning {
+            id: "warn-id".to_string(),
+            user_id: 12345,
+            issuer_id: 67890,
+            guild_id: 11111,
+            reason: "Test warning".to_string(),
+            timestamp: Utc::now(),
+            notification_method: NotificationMethod::PublicWithMention,
+            enforcement: None,
+            severity: scoring::SEVERITY_MODERATE,
+            judgment: None,
+            category: None,
+        };
+        let mut enforcement = PendingEnforcement {
+            warning_id: "warn-id".to_string(),
+            ..PendingEnforcement::test(
+                "enf-id",
+                EnforcementAction::VoiceMute { duration: Some(60) },
+                EnforcementState::Pending,
+            )
+        };
+        data.insert_warning(warning.clone());
+        data.store_enforcement(enforcement.clone());
+
+        // Moving along the lifecycle keeps the enforcement indexed once
+        enforcement.state = EnforcementState::Active;
+        data.store_enforcement(enforcement.clone());
+        let found = data.user_enforcements(12345, 11111);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].state, EnforcementState::Active);
+        assert_eq!(data.user_warnings(12345, 11111).len(), 1);
+        assert!(data.user_warnings(12345, 22222).is_empty());
+
+        // Overwriting a warning, as an import does, keeps it indexed
+        let mut reworded = warning.clone();
+        reworded.reason = "Reworded warning".to_string();
+        data.insert_warning(reworded);
+        let found = data.user_warnings(12345, 11111);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].reason, "Reworded warning");
+
+        // Loading rebuilds the indexes
+        let loaded = DataInner::from_snapshot(Snapshot {
+            warnings: vec![warning],
+            enforcements: vec![enforcement],
+            ..Default::default()
+        });
+        assert_eq!(loaded.warnings_by_user.ids(11111, 12345), vec!["warn-id"]);
+        assert_eq!(
+            loaded.enforcements_by_user.ids(11111, 12345),
+            vec!["enf-id"]
+        );
+
+        data.remove_warning("warn-id");
+        data.remove_enforcement("enf-id");
+        assert!(data.user_warnings(12345, 11111).is_empty());
+        assert!(data.user_enforcements(12345, 11111).is_empty());
+        assert!(data.warnings_by_user.0.is_empty());
+        assert!(data.enforcements_by_user.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_damaged_data_fails_or_loads_read_only() {
+        let dir = std::env::temp_dir().join(format!("daemon-data-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("warnings.yaml"), "- id: [unterminated").unwrap();
+
+        let storage = Arc::new(YamlStorage::new(&dir));
+        assert!(Data::load(storage, CorruptionPolicy::Fail).await.is_err());
+
+        let storage = Arc::new(YamlStorage::new(&dir));
+        let data = Data::load(storage, CorruptionPolicy::ReadOnly)
+            .await
+            .expect("Failed to load read-only");
+        assert!(data.read_only);
+
+        // Saving never touches the damaged file
+        data.set_guild_config(GuildConfig::new(42));
+        data.save().await.expect("Failed to save");
+        assert!(!dir.join("bot_config.yaml").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,14 +1,26 @@
-use crate::data::{EnforcementAction, EnforcementState};
+use crate::data::{EnforcementAction, EnforcementState, PendingEnforcement};
+use crate::haunt;
+use crate::moderation::{ModerationBackend, ModerationError, SerenityBackend};
+use crate::schedule::{Deadline, DeadlineKind};
+use crate::storage::user_state_key;
 use crate::{Data, Error};
 use chrono::{DateTime, Utc};
-use poise::serenity_prelude::{GuildId, Http, UserId, builder::EditMember};
-use serenity::all::{CacheHttp, ChannelId};
+use poise::serenity_prelude::{ChannelId, GuildId, Http, UserId};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::time::Duration;
 use tracing::{error, info, warn};
 
+/// Default seconds between the safety-net scans of every enforcement
+pub const SAFETY_SCAN_SECONDS: u64 = 600;
+
+/// Attempts at executing or reversing an enforcement before giving up on it
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Seconds before the first retry; each further retry waits twice as long
+pub const RETRY_BASE_SECONDS: u64 = 5;
+
 /// Type of enforcement check request
 #[allow(dead_code)]
 pub enum EnforcementCheckRequest {
@@ -60,38 +72,51 @@ pub fn take_enforcement_receiver() -> Option<Receiver<EnforcementCheckRequest>>
     ENFORCEMENT_RECEIVER.with(|cell| cell.borrow_mut().take())
 }
 
-/// The main enforcement task that periodically checks for enforcement actions
+/// The main enforcement task
+///
+/// Sleeps until the next scheduled deadline and re-arms whenever the schedule
+/// changes. A full scan every `check_interval_seconds` is kept as a safety net
+/// for anything the schedule missed.
 async fn enforcement_task(
     http: Arc<Http>,
     data: Data,
     mut rx: Receiver<EnforcementCheckRequest>,
     check_interval_seconds: u64,
 ) {
-    info!("Starting enforcement task with {check_interval_seconds}s interval",);
+    info!("Starting enforcement task with {check_interval_seconds}s safety-net scan",);
+
+    let backend: Arc<dyn ModerationBackend> = Arc::new(SerenityBackend::new(http));
+
+    // Deal with anything that came due while the bot was offline
+    catch_up_overdue_enforcements(backend.as_ref(), &data).await;
 
     let check_interval = Duration::from_secs(check_interval_seconds);
     let mut interval = tokio::time::interval(check_interval);
 
     loop {
+        // Start jobs for haunts that just began, or were underway before a restart
+        data.haunts.supervise(&backend, &data).await;
+
+        let next_deadline = sleep_until(data.schedule.next_deadline());
         tokio::select! {
             // Handle any incoming requests
             Some(request) = rx.recv() => {
                 match request {
                     EnforcementCheckRequest::CheckAll => {
                         info!("Received request to check all enforcements");
-                        if let Err(e) = check_all_enforcements(&http, &data).await {
+                        if let Err(e) = check_all_enforcements(backend.as_ref(), &data).await {
                             error!("Error checking all enforcements: {e}");
                         }
                     },
                     EnforcementCheckRequest::CheckUser { user_id, guild_id } => {
                         info!("Received request to check enforcements for user {} in guild {}", user_id, guild_id);
-                        if let Err(e) = check_user_enforcements(&http, &data, user_id, guild_id).await {
+                        if let Err(e) = check_user_enforcements(backend.as_ref(), &data, user_id, guild_id).await {
                             error!("Error checking user enforcements: {e}");
                         }
                     },
                     EnforcementCheckRequest::CheckEnforcement { enforcement_id } => {
                         info!("Received request to check enforcement {}", enforcement_id);
-                        if let Err(e) = check_specific_enforcement(&http, &data, &enforcement_id).await {
+                        if let Err(e) = check_specific_enforcement(backend.as_ref(), &data, &enforcement_id).await {
                             error!("Error checking specific enforcement: {e}");
                         }
                     },
@@ -102,12 +127,21 @@ async fn enforcement_task(
                 }
             },
 
-            // Periodic check
+            // A deadline was added, moved or cancelled, so loop around to re-arm
+            () = data.schedule.rearmed() => {},
+
+            // The earliest deadline has passed
+            () = next_deadline => {
+                run_due_deadlines(backend.as_ref(), &data).await;
+            },
+
+            // Safety-net check
             _ = interval.tick() => {
                 info!("Performing periodic enforcement check");
-                if let Err(e) = check_all_enforcements(&http, &data).await {
+                if let Err(e) = check_all_enforcements(backend.as_ref(), &data).await {
                     error!("Error in periodic enforcement check: {}", e);
                 }
+                resync_schedule(&data);
             }
         }
     }
@@ -115,19 +149,65 @@ async fn enforcement_task(
     info!("Enforcement task shut down");
 }
 
-/// Check all enforcements (both pending executions and active ones that need reversal)
-async fn check_all_enforcements(http: &Http, data: &Data) -> Result<(), Error> {
-    let now = Utc::now();
+/// Sleep until `deadline`, or forever if there is none
+async fn sleep_until(deadline: Option<DateTime<Utc>>) {
+    match deadline {
+        Some(at) => {
+            let delay = (at - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(delay).await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Execute or reverse every enforcement whose deadline has passed
+///
+/// Deadlines that no longer match the stored enforcement, because it was
+/// cancelled or moved on since, are skipped. Failures are logged and left to
+/// the safety-net check.
+async fn run_due_deadlines(backend: &dyn ModerationBackend, data: &Data) {
+    for deadline in data.schedule.pop_due(Utc::now()) {
+        let id = &deadline.enforcement_id;
+        let map = match deadline.kind {
+            DeadlineKind::Execute => &data.pending_enforcements,
+            DeadlineKind::Reverse => &data.active_enforcements,
+        };
+        if !map
+            .get(id)
+            .is_some_and(|entry| deadline.is_current(entry.value()))
+        {
+            warn!("Skipping stale deadline for enforcement {id}");
+            continue;
+        }
+
+        let result = match deadline.kind {
+            DeadlineKind::Execute => execute_enforcement(backend, data, id).await,
+            DeadlineKind::Reverse => reverse_enforcement(backend, data, id).await,
+        };
+        if let Err(e) = result {
+            error!("Error handling deadline for enforcement {id}: {e}");
+        }
+    }
+}
+
+/// Rebuild the schedule from the stored enforcements, dropping anything stale
+fn resync_schedule(data: &Data) {
+    data.schedule.rebuild(
+        data.pending_enforcements
+            .iter()
+            .chain(data.active_enforcements.iter())
+            .filter_map(|entry| Deadline::of(entry.value())),
+    );
+}
 
+/// Find pending enforcements due for execution and active ones due for reversal
+fn overdue_enforcements(data: &Data, now: DateTime<Utc>) -> (Vec<String>, Vec<String>) {
     // Find pending enforcements that need to be executed
     let mut enforcements_to_execute = Vec::new();
     for entry in &data.pending_enforcements {
         let pending = entry.value();
-        if pending.state == EnforcementState::Pending {
-            let execute_at = pending.execute_at;
-            if execute_at <= now {
-                enforcements_to_execute.push(pending.id.clone());
-            }
+        if pending.state == EnforcementState::Pending && pending.execute_at <= now {
+            enforcements_to_execute.push(pending.id.clone());
         }
     }
 
@@ -135,38 +215,162 @@ async fn check_all_enforcements(http: &Http, data: &Data) -> Result<(), Error> {
     let mut enforcements_to_reverse = Vec::new();
     for entry in &data.active_enforcements {
         let active = entry.value();
-        if active.state == EnforcementState::Active && active.reverse_at.is_some() {
-            if let Some(reverse_at) = active.reverse_at {
-                if reverse_at <= now {
-                    enforcements_to_reverse.push(active.id.clone());
-                }
-            }
+        if active.state == EnforcementState::Active
+            && active
+                .reverse_at
+                .is_some_and(|reverse_at| reverse_at <= now)
+        {
+            enforcements_to_reverse.push(active.id.clone());
         }
     }
 
+    (enforcements_to_execute, enforcements_to_reverse)
+}
+
+/// Check all enforcements (both pending executions and active ones that need reversal)
+async fn check_all_enforcements(backend: &dyn ModerationBackend, data: &Data) -> Result<(), Error> {
+    let (enforcements_to_execute, enforcements_to_reverse) = overdue_enforcements(data, Utc::now());
+
     // Execute pending enforcements
     for id in &enforcements_to_execute {
-        execute_enforcement(http, data, id).await?;
+        if let Err(e) = execute_enforcement(backend, data, id).await {
+            error!("Failed to execute enforcement {id}: {e}");
+        }
     }
 
     // Reverse active enforcements
     for id in &enforcements_to_reverse {
-        reverse_enforcement(http, data, id).await?;
+        if let Err(e) = reverse_enforcement(backend, data, id).await {
+            error!("Failed to reverse enforcement {id}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Process executions and reversals that came due while the bot was offline,
+/// then post a summary of what was done to each guild's altar channel
+async fn catch_up_overdue_enforcements(backend: &dyn ModerationBackend, data: &Data) {
+    let (enforcements_to_execute, enforcements_to_reverse) = overdue_enforcements(data, Utc::now());
+    if enforcements_to_execute.is_empty() && enforcements_to_reverse.is_empty() {
+        return;
+    }
+
+    info!(
+        "Catching up on {} overdue executions and {} overdue reversals",
+        enforcements_to_execute.len(),
+        enforcements_to_reverse.len()
+    );
+
+    // Summary lines per guild
+    let mut summaries: HashMap<u64, Vec<String>> = HashMap::new();
+
+    for id in &enforcements_to_execute {
+        let Some(pending) = data.pending_enforcements.get(id).map(|e| e.value().clone()) else {
+            continue;
+        };
+        let line = match execute_enforcement(backend, data, id).await {
+            Ok(()) => format!(
+                "⚡ {} — <@{}> (was due <t:{}:R>)",
+                pending.action,
+                pending.user_id,
+                pending.execute_at.timestamp()
+            ),
+            Err(e) => {
+                error!("Failed to execute overdue enforcement {id}: {e}");
+                // A transient failure leaves it pending with a retry scheduled
+                let retry_at = data
+                    .pending_enforcements
+                    .get(id)
+                    .filter(|retry| retry.state == EnforcementState::Pending)
+                    .map(|retry| retry.execute_at);
+                match retry_at {
+                    Some(retry_at) => format!(
+                        "⏳ {} for <@{}> failed and will retry <t:{}:R>: {e}",
+                        pending.action,
+                        pending.user_id,
+                        retry_at.timestamp()
+                    ),
+                    None => format!(
+                        "❌ Failed to execute {} for <@{}>: {e}",
+                        pending.action, pending.user_id
+                    ),
+                }
+            }
+        };
+        summaries.entry(pending.guild_id).or_default().push(line);
+    }
+
+    for id in &enforcements_to_reverse {
+        let Some(active) = data.active_enforcements.get(id).map(|e| e.value().clone()) else {
+            continue;
+        };
+        let due = active
+            .reverse_at
+            .map_or(0, |reverse_at| reverse_at.timestamp());
+        let line = match reverse_enforcement(backend, data, id).await {
+            Ok(()) => format!(
+                "🔓 Lifted {} — <@{}> (was due <t:{due}:R>)",
+                active.action, active.user_id
+            ),
+            Err(e) => {
+                error!("Failed to reverse overdue enforcement {id}: {e}");
+                // A transient failure leaves it active with a retry scheduled
+                let retry_at = data
+                    .active_enforcements
+                    .get(id)
+                    .filter(|retry| retry.state == EnforcementState::Active)
+                    .and_then(|retry| retry.reverse_at);
+                match retry_at {
+                    Some(retry_at) => format!(
+                        "⏳ Lifting {} for <@{}> failed and will retry <t:{}:R>: {e}",
+                        active.action,
+                        active.user_id,
+                        retry_at.timestamp()
+                    ),
+                    None => format!(
+                        "❌ Failed to lift {} for <@{}>: {e}",
+                        active.action, active.user_id
+                    ),
+                }
+            }
+        };
+        summaries.entry(active.guild_id).or_default().push(line);
     }
 
-    // Save updated data if anything was executed or reversed
-    if !enforcements_to_execute.is_empty() || !enforcements_to_reverse.is_empty() {
-        if let Err(e) = data.save().await {
-            error!("Failed to save data after enforcement operations: {e}");
+    for (guild_id, lines) in summaries {
+        let log_channel_id = data
+            .guild_configs
+            .get(&GuildId::new(guild_id))
+            .and_then(|config| config.enforcement_log_channel_id);
+        let Some(log_channel_id) = log_channel_id else {
+            continue;
+        };
+
+        let msg_content = format!(
+            "⏳ **THE DAEMON AWAKENS**\n\nWhile I slumbered, these judgments came due:\n{}",
+            lines.join("\n")
+        );
+        if let Err(e) = backend
+            .post_message(ChannelId::new(log_channel_id), &msg_content)
+            .await
+        {
+            error!("Failed to post catch-up summary to guild {guild_id}: {e}");
         }
     }
+}
 
-    Ok(())
+/// Whether a pending enforcement may be executed on request
+///
+/// Requests execute an enforcement straight away, unless it's waiting out the
+/// backoff before a retry.
+fn ready_to_execute(pending: &PendingEnforcement, now: DateTime<Utc>) -> bool {
+    pending.attempts == 0 || pending.execute_at <= now
 }
 
 /// Check enforcements for a specific user in a specific guild
 async fn check_user_enforcements(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     data: &Data,
     user_id: u64,
     guild_id: u64,
@@ -174,51 +378,33 @@ async fn check_user_enforcements(
     let mut enforcements_to_execute = Vec::new();
     let mut enforcements_to_reverse = Vec::new();
 
-    // Find pending enforcements for this user in this guild
-    for entry in &data.pending_enforcements {
-        let pending = entry.value();
-        if pending.state == EnforcementState::Pending
-            && pending.user_id == user_id
-            && pending.guild_id == guild_id
-        {
-            enforcements_to_execute.push(pending.id.clone());
-        }
-    }
-
-    // Find active enforcements for this user that might need reversal
-    for entry in &data.active_enforcements {
-        let active = entry.value();
-        if active.state == EnforcementState::Active
-            && active.user_id == user_id
-            && active.guild_id == guild_id
-        {
-            enforcements_to_reverse.push(active.id.clone());
+    // Sort this user's enforcements into ones to execute and ones that might need reversal.
+    // Enforcements waiting to be retried are left until their retry is due.
+    for enforcement in data.user_enforcements(user_id, guild_id) {
+        match enforcement.state {
+            EnforcementState::Pending if ready_to_execute(&enforcement, Utc::now()) => {
+                enforcements_to_execute.push(enforcement.id);
+            }
+            EnforcementState::Active => enforcements_to_reverse.push(enforcement.id),
+            _ => {}
         }
     }
 
     // Execute pending enforcements
     for id in &enforcements_to_execute {
-        execute_enforcement(http, data, id).await?;
+        execute_enforcement(backend, data, id).await?;
     }
 
     // Check if active enforcements should be reversed
     for id in &enforcements_to_reverse {
         // Only reverse if the time has come
-        if let Some(active) = data.active_enforcements.get(id) {
-            if let Some(reverse_at) = &active.reverse_at {
-                if reverse_at.with_timezone(&Utc) <= Utc::now() {
-                    // Drop the borrow before calling reverse_enforcement
-                    drop(active);
-                    reverse_enforcement(http, data, id).await?;
-                }
-            }
-        }
-    }
-
-    // Save updated data
-    if !enforcements_to_execute.is_empty() || !enforcements_to_reverse.is_empty() {
-        if let Err(e) = data.save().await {
-            error!("Failed to save data after executing user enforcements: {e}");
+        if let Some(active) = data.active_enforcements.get(id)
+            && let Some(reverse_at) = &active.reverse_at
+            && reverse_at.with_timezone(&Utc) <= Utc::now()
+        {
+            // Drop the borrow before calling reverse_enforcement
+            drop(active);
+            reverse_enforcement(backend, data, id).await?;
         }
     }
 
@@ -227,23 +413,19 @@ async fn check_user_enforcements(
 
 /// Check a specific enforcement by ID
 async fn check_specific_enforcement(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     data: &Data,
     enforcement_id: &str,
 ) -> Result<(), Error> {
-    // First check pending enforcements
-    if let Some(pending) = data.pending_enforcements.get(enforcement_id) {
-        if pending.state == EnforcementState::Pending {
-            let id = pending.id.clone();
-            drop(pending); // Drop the borrow before calling execute_enforcement
-            execute_enforcement(http, data, &id).await?;
-
-            // Save data
-            if let Err(e) = data.save().await {
-                error!("Failed to save data after executing specific enforcement: {e}");
-            }
-            return Ok(());
-        }
+    // First check pending enforcements, leaving retries until they're due
+    if let Some(pending) = data.pending_enforcements.get(enforcement_id)
+        && pending.state == EnforcementState::Pending
+        && ready_to_execute(&pending, Utc::now())
+    {
+        let id = pending.id.clone();
+        drop(pending); // Drop the borrow before calling execute_enforcement
+        execute_enforcement(backend, data, &id).await?;
+        return Ok(());
     }
 
     // Then check active enforcements
@@ -259,12 +441,7 @@ async fn check_specific_enforcement(
             if should_reverse {
                 let id = active.id.clone();
                 drop(active); // Drop the borrow before calling reverse_enforcement
-                reverse_enforcement(http, data, &id).await?;
-
-                // Save data
-                if let Err(e) = data.save().await {
-                    error!("Failed to save data after reversing specific enforcement: {e}");
-                }
+                reverse_enforcement(backend, data, &id).await?;
                 return Ok(());
             }
         }
@@ -275,33 +452,14 @@ async fn check_specific_enforcement(
     Ok(())
 }
 
-/// Helper function to get guild and member information
-async fn get_guild_and_member(
-    http: &Http,
-    guild_id: GuildId,
-    user_id: UserId,
-) -> Result<(serenity::all::PartialGuild, serenity::all::Member), Error> {
-    let guild = guild_id
-        .to_partial_guild(http)
-        .await
-        .map_err(|e| Error::from(e.to_string()))?;
-
-    let member = guild
-        .member(http, user_id)
-        .await
-        .map_err(|e| Error::from(e.to_string()))?;
-
-    Ok((guild, member))
-}
-
 /// Handle mute enforcement action
 async fn handle_mute_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     duration: &Option<u64>,
     is_executed: bool,
-) -> Result<(), Error> {
+) -> Result<(), ModerationError> {
     if is_executed {
         info!("Mute period expired for user {user_id} in guild {guild_id}");
         return Ok(());
@@ -310,51 +468,36 @@ async fn handle_mute_action(
     // Apply mute (timeout)
     info!("Muting user {user_id} in guild {guild_id} for {duration:?} seconds");
 
-    if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-        #[allow(clippy::cast_possible_wrap)]
-        let timeout_until = Utc::now() + chrono::Duration::seconds(duration.unwrap_or(0) as i64);
+    let timeout_until = seconds_from_now(duration.unwrap_or(0))
+        .ok_or_else(|| ModerationError::Permanent("Mute duration is too long".into()))?;
 
-        match member
-            .disable_communication_until_datetime(http, timeout_until.into())
-            .await
-        {
-            Ok(()) => {
-                info!("Successfully muted user {user_id} until {timeout_until}");
-            }
-            Err(e) => {
-                error!("Failed to mute user {user_id}: {e}");
-            }
-        }
-    }
+    backend.timeout(guild_id, user_id, timeout_until).await?;
+    info!("Successfully muted user {user_id} until {timeout_until}");
 
     Ok(())
 }
 
 /// Handle ban enforcement action
 async fn _handle_ban_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     duration: &Option<u64>,
     is_executed: bool,
-) -> Result<(), Error> {
+) -> Result<(), ModerationError> {
     if !is_executed {
         // Ban the user
         info!("Banning user {user_id} in guild {guild_id} for {duration:?} seconds");
 
         let reason = format!("Temporary ban from warning system for {duration:?} seconds");
 
-        match guild_id.ban_with_reason(http, user_id, 7, &reason).await {
-            Ok(()) => info!("Successfully banned user {user_id}"),
-            Err(e) => error!("Failed to ban user {user_id}: {e}"),
-        }
+        backend.ban(guild_id, user_id, &reason).await?;
+        info!("Successfully banned user {user_id}");
     } else {
         // Unban the user when duration expires
         info!("Unbanning user {user_id} in guild {guild_id}");
-        match guild_id.unban(http, user_id).await {
-            Ok(()) => info!("Successfully unbanned user {user_id}"),
-            Err(e) => error!("Failed to unban user {user_id}: {e}"),
-        }
+        backend.unban(guild_id, user_id).await?;
+        info!("Successfully unbanned user {user_id}");
     }
 
     Ok(())
@@ -362,23 +505,19 @@ async fn _handle_ban_action(
 
 /// Handle kick enforcement action
 async fn _handle_kick_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     delay: &Option<u64>,
     is_executed: bool,
-) -> Result<(), Error> {
+) -> Result<(), ModerationError> {
     if delay.is_none() || delay.is_some_and(|d| d == 0) || is_executed {
         // Kick immediately or when the delay expires
         info!("Kicking user {user_id} from guild {guild_id}");
 
-        if let Ok((_, member)) = get_guild_and_member(http, guild_id, user_id).await {
-            let reason = "Kicked by warning system";
-            match member.kick_with_reason(http, reason).await {
-                Ok(()) => info!("Successfully kicked user {user_id}"),
-                Err(e) => error!("Failed to kick user {user_id}: {e}"),
-            }
-        }
+        let reason = "Kicked by warning system";
+        backend.kick(guild_id, user_id, reason).await?;
+        info!("Successfully kicked user {user_id}");
     } else {
         // This is a delayed kick that hasn't reached its time yet - do nothing
         info!("Delayed kick for user {user_id} is not ready yet");
@@ -390,43 +529,24 @@ async fn _handle_kick_action(
 
 /// Handle voice mute enforcement action
 async fn handle_voice_mute_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     duration: &Option<u64>,
     is_executed: bool,
-) -> Result<(), Error> {
+) -> Result<(), ModerationError> {
     if !is_executed {
         // Apply voice mute
         info!("Voice muting user {user_id} in guild {guild_id} for {duration:?} seconds");
 
-        if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-            match member.edit(http, EditMember::new().mute(true)).await {
-                Ok(()) => {
-                    info!("Successfully voice muted user {user_id}");
-
-                    // If there's a duration, log that it will need manual removal
-                    if let Some(dur) = duration {
-                        if *dur > 0 {
-                            info!(
-                                "Voice mute will need to be manually removed after {dur} seconds"
-                            );
-                        }
-                    }
-                }
-                Err(e) => error!("Failed to voice mute user {user_id}: {e}"),
-            }
-        }
+        backend.set_voice_mute(guild_id, user_id, true).await?;
+        info!("Successfully voice muted user {user_id}");
     } else {
         // Remove the voice mute
         info!("Voice mute period expired for user {user_id} in guild {guild_id}");
 
-        if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-            match member.edit(http, EditMember::new().mute(false)).await {
-                Ok(()) => info!("Successfully removed voice mute from user {user_id}"),
-                Err(e) => error!("Failed to remove voice mute from user {user_id}: {e}"),
-            }
-        }
+        backend.set_voice_mute(guild_id, user_id, false).await?;
+        info!("Successfully removed voice mute from user {user_id}");
     }
 
     Ok(())
@@ -434,45 +554,24 @@ async fn handle_voice_mute_action(
 
 /// Handle voice deafen enforcement action
 async fn handle_voice_deafen_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     duration: &Option<u64>,
     is_executed: bool,
-) -> Result<(), Error> {
-    use poise::serenity_prelude::builder::EditMember;
-
+) -> Result<(), ModerationError> {
     if !is_executed {
         // Apply voice deafen
         info!("Voice deafening user {user_id} in guild {guild_id} for {duration:?} seconds");
 
-        if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-            match member.edit(http, EditMember::new().deafen(true)).await {
-                Ok(()) => {
-                    info!("Successfully voice deafened user {user_id}");
-
-                    // If there's a duration, log that it will need manual removal
-                    if let Some(dur) = duration {
-                        if *dur > 0 {
-                            info!(
-                                "Voice deafen will need to be manually removed after {dur} seconds"
-                            );
-                        }
-                    }
-                }
-                Err(e) => error!("Failed to voice deafen user {user_id}: {e}"),
-            }
-        }
+        backend.set_voice_deafen(guild_id, user_id, true).await?;
+        info!("Successfully voice deafened user {user_id}");
     } else {
         // Remove the voice deafen
         info!("Voice deafen period expired for user {user_id} in guild {guild_id}");
 
-        if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-            match member.edit(http, EditMember::new().deafen(false)).await {
-                Ok(()) => info!("Successfully removed voice deafen from user {user_id}"),
-                Err(e) => error!("Failed to remove voice deafen from user {user_id}: {e}"),
-            }
-        }
+        backend.set_voice_deafen(guild_id, user_id, false).await?;
+        info!("Successfully removed voice deafen from user {user_id}");
     }
 
     Ok(())
@@ -480,23 +579,18 @@ async fn handle_voice_deafen_action(
 
 /// Handle voice disconnect enforcement action
 async fn handle_voice_disconnect_action(
-    http: &Http,
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
     delay: Option<&u64>,
     is_executed: bool,
-) -> Result<(), Error> {
+) -> Result<(), ModerationError> {
     if delay.is_none() || delay.is_some_and(|d| *d == 0) || is_executed {
         // Disconnect immediately or when the delay expires
         info!("Disconnecting user {user_id} from voice in guild {guild_id}");
 
-        if let Ok((_, member)) = get_guild_and_member(http, guild_id, user_id).await {
-            // Disconnect from voice channel
-            match member.disconnect_from_voice(http).await {
-                Ok(_) => info!("Successfully disconnected user {user_id} from voice"),
-                Err(e) => error!("Failed to disconnect user {user_id} from voice: {e}"),
-            }
-        }
+        backend.disconnect(guild_id, user_id).await?;
+        info!("Successfully disconnected user {user_id} from voice");
     } else {
         // This is a delayed disconnect that hasn't reached its time yet - do nothing
         info!("Delayed voice disconnect for user {user_id} is not ready yet");
@@ -506,297 +600,87 @@ async fn handle_voice_disconnect_action(
     Ok(())
 }
 
-/// Get the current voice channel for a user
-async fn get_user_voice_channel(
-    http: &Http,
+/// Lift an action on Discord once its time is up
+async fn lift_action(
+    backend: &dyn ModerationBackend,
     guild_id: GuildId,
     user_id: UserId,
-) -> Option<ChannelId> {
-    // Fallback to HTTP if the guild is not in cache
-    if let Ok(guild) = guild_id.to_partial_guild(http).await {
-        // Get member to find the voice channel
-        let voice_channels = get_guild_voice_channels(http.http(), &guild).await;
-        info!(
-            "Number of voice channels in guild {guild_id}: {}",
-            voice_channels.len()
-        );
-        for channel_id in voice_channels {
-            if let Ok(channel) = channel_id.to_channel(http.http()).await {
-                let channel_name = channel_id
-                    .name(http.http())
-                    .await
-                    .unwrap_or("Unknown".to_string());
-                info!("Checking channel {channel_name}");
-                let guild_channel = channel.guild()?;
-                let res = http.cache().map(|cache| {
-                    guild_channel
-                        .members(cache)
-                        .map(|members| members.iter().any(|member| member.user.id == user_id))
-                });
-                let res = res.unwrap_or_else(|| Ok(false)).unwrap_or(false);
-                if res {
-                    // User found in this channel
-                    info!("User {user_id} is in voice channel {channel_id}");
-                    return Some(channel_id);
-                }
-                // User not found in this channel, continue searching
-            }
+    action: &EnforcementAction,
+) -> Result<(), ModerationError> {
+    match action {
+        EnforcementAction::Mute { .. } => {
+            // For Discord timeouts, they're automatically removed by Discord
+            // We just need to mark it as reversed in our system
+            info!("Timeout period expired for user {user_id} in guild {guild_id}");
         }
-    }
-    None
-}
-
-/// Get all voice channels in a guild
-async fn get_guild_voice_channels(
-    http: &Http,
-    guild: &serenity::all::PartialGuild,
-) -> Vec<ChannelId> {
-    let channels = guild.channels(http).await.unwrap_or_else(|_| {
-        error!("Failed to get channels for guild {}", guild.id);
-        HashMap::new()
-    });
-
-    let voice_channels = channels
-        .iter()
-        .filter_map(|(id, channel)| {
-            if channel.kind == serenity::all::ChannelType::Voice {
-                Some(*id)
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-
-    voice_channels
-}
-
-/// Handle voice channel haunting enforcement action
-async fn handle_voice_channel_haunt_action(
-    http: &Http,
-    guild_id: GuildId,
-    user_id: UserId,
-    teleport_count: &Option<u64>,
-    interval: &Option<u64>,
-    return_to_origin: &Option<bool>,
-    original_channel_id: &Option<u64>,
-    is_executed: bool,
-) -> Result<(), Error> {
-    // If already executed, nothing to do
-    if is_executed {
-        return Ok(());
-    }
-
-    info!("Beginning voice channel haunting for user {user_id} in guild {guild_id}");
-
-    // Get guild
-    let (guild, _) = match get_guild_and_member(http, guild_id, user_id).await {
-        Ok(result) => result,
-        Err(e) => {
-            error!("Failed to get guild for haunting: {e}");
-            return Ok(());
+        EnforcementAction::Ban { .. } => {
+            // Unban the user
+            info!("Unbanning user {user_id} in guild {guild_id}");
+            backend.unban(guild_id, user_id).await?;
+            info!("Successfully unbanned user {user_id}");
         }
-    };
-
-    // Find the user's current voice channel
-    let current_voice_channel = get_user_voice_channel(http, guild_id, user_id).await;
-
-    let voice_channel_id = if let Some(id) = current_voice_channel {
-        id
-    } else {
-        error!("User {user_id} is not in a voice channel, cannot haunt");
-        return Ok(());
-    };
-
-    // Store the original channel ID if it's not already set
-    let original_id = original_channel_id.unwrap_or(voice_channel_id.get());
-
-    // Get all voice channels in the guild
-    let voice_channels = get_guild_voice_channels(http, &guild).await;
-
-    if voice_channels.is_empty() {
-        error!("No voice channels found in guild {guild_id} for haunting");
-        return Ok(());
-    }
-
-    // Default parameters or use provided ones
-    let (teleport_count, delay_count) = {
-        let mut rng = rand::thread_rng();
-        let count = teleport_count.unwrap_or_else(|| rand::Rng::gen_range(&mut rng, 1..=3));
-        let delay = interval.unwrap_or_else(|| rand::Rng::gen_range(&mut rng, 5..=15));
-        (count, delay)
-    };
-    let return_to_original = return_to_origin.unwrap_or(true);
-
-    // Schedule the haunting for later execution
-    let http_arc = Arc::new(http);
-    let guild_id_copy = guild_id;
-    let user_id_copy = user_id;
-    let voice_channels_copy = voice_channels.clone();
-
-    //tokio::spawn(async move {
-    let mut failed = false;
-
-    for i in 0..teleport_count {
-        if failed {
-            break;
+        EnforcementAction::VoiceMute { .. } => {
+            // Remove voice mute
+            info!("Removing voice mute from user {user_id} in guild {guild_id}");
+            backend.set_voice_mute(guild_id, user_id, false).await?;
+            info!("Successfully removed voice mute from user {user_id}");
         }
-
-        // Pick a random channel (different from current on first teleport)
-        let random_channel =
-            select_random_voice_channel(&voice_channels_copy, i == 0, voice_channel_id);
-
-        // Move the user to the random channel
-        failed = !teleport_user(
-            &http_arc.clone(),
-            guild_id_copy,
-            user_id_copy,
-            random_channel,
-        )
-        .await;
-
-        // Wait before the next teleport if we haven't failed
-        if !failed {
-            tokio::time::sleep(tokio::time::Duration::from_secs(delay_count)).await;
+        EnforcementAction::VoiceDeafen { .. } => {
+            // Remove voice deafen
+            info!("Removing voice deafen from user {user_id} in guild {guild_id}");
+            backend.set_voice_deafen(guild_id, user_id, false).await?;
+            info!("Successfully removed voice deafen from user {user_id}");
         }
+        // These actions don't need reversal as they're one-time actions
+        EnforcementAction::Kick { .. }
+        | EnforcementAction::VoiceDisconnect { .. }
+        | EnforcementAction::VoiceChannelHaunt { .. }
+        | EnforcementAction::None => {}
     }
-
-    // Return the user to their original channel if specified and we haven't failed
-    if return_to_original && !failed {
-        let original_channel = ChannelId::new(original_id);
-        teleport_user(&http_arc, guild_id_copy, user_id_copy, original_channel).await;
-    }
-    //});
-
-    info!("Voice channel haunting scheduled for user {user_id}");
     Ok(())
 }
 
-/// Select a random voice channel, optionally ensuring it's different from the current one
-fn select_random_voice_channel(
-    voice_channels: &[ChannelId],
-    must_be_different: bool,
-    current_channel: ChannelId,
-) -> ChannelId {
-    let rng = &mut rand::thread_rng();
-    if !must_be_different || voice_channels.len() <= 1 {
-        // If we don't need a different channel or there's only one channel, just pick randomly
-        let idx = rand::Rng::gen_range(rng, 0..voice_channels.len());
-        return voice_channels[idx];
-    }
-
-    // We need a different channel and have multiple options
-    loop {
-        let idx = rand::Rng::gen_range(rng, 0..voice_channels.len());
-        let channel = voice_channels[idx];
-
-        if channel != current_channel {
-            return channel;
-        }
-    }
-}
-
-/// Teleport a user to a specific voice channel
-async fn teleport_user(
-    http: &Http,
-    guild_id: GuildId,
-    user_id: UserId,
-    channel_id: ChannelId,
-) -> bool {
-    match guild_id.to_partial_guild(http).await {
-        Ok(guild) => match guild.member(http, user_id).await {
-            Ok(mut member) => {
-                info!("Teleporting user {user_id} to channel {channel_id}");
-                match member
-                    .edit(
-                        http,
-                        serenity::builder::EditMember::new().voice_channel(channel_id),
-                    )
-                    .await
-                {
-                    Ok(()) => true,
-                    Err(e) => {
-                        error!("Failed to teleport user {user_id}: {e}");
-                        false
-                    }
-                }
-            }
-            Err(e) => {
-                error!("Failed to get member {user_id}: {e}");
-                false
-            }
-        },
-        Err(e) => {
-            error!("Failed to get guild {guild_id}: {e}");
-            false
-        }
-    }
-}
-
 /// Reverse an active enforcement action
-async fn reverse_enforcement(http: &Http, data: &Data, enforcement_id: &str) -> Result<(), Error> {
-    if let Some(mut active) = data.active_enforcements.get_mut(enforcement_id) {
+///
+/// A transient failure schedules another attempt with exponential backoff; a
+/// permanent one, or running out of attempts, marks the enforcement
+/// [`EnforcementState::Failed`] and reports it to the guild's altar.
+///
+/// # Errors
+/// Returns an error if the action couldn't be lifted on Discord.
+pub async fn reverse_enforcement(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    enforcement_id: &str,
+) -> Result<(), Error> {
+    let active = data
+        .active_enforcements
+        .get(enforcement_id)
+        .map(|entry| entry.value().clone());
+    if let Some(mut active) = active {
         let guild_id = GuildId::new(active.guild_id);
         let user_id = UserId::new(active.user_id);
         let now = Utc::now();
 
         // Apply the reversal action based on the enforcement type
-        match &active.action {
-            EnforcementAction::Mute { .. } => {
-                // For Discord timeouts, they're automatically removed by Discord
-                // We just need to mark it as reversed in our system
-                info!("Timeout period expired for user {user_id} in guild {guild_id}");
-            }
-            EnforcementAction::Ban { .. } => {
-                // Unban the user
-                info!("Unbanning user {user_id} in guild {guild_id}");
-                match guild_id.unban(http, user_id).await {
-                    Ok(()) => info!("Successfully unbanned user {user_id}"),
-                    Err(e) => error!("Failed to unban user {user_id}: {e}"),
-                }
-            }
-            EnforcementAction::VoiceMute { .. } => {
-                // Remove voice mute
-                info!("Removing voice mute from user {user_id} in guild {guild_id}");
-                if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-                    match member.edit(http, EditMember::new().mute(false)).await {
-                        Ok(()) => info!("Successfully removed voice mute from user {user_id}"),
-                        Err(e) => error!("Failed to remove voice mute from user {user_id}: {e}"),
-                    }
-                }
-            }
-            EnforcementAction::VoiceDeafen { .. } => {
-                // Remove voice deafen
-                info!("Removing voice deafen from user {user_id} in guild {guild_id}");
-                if let Ok((_, mut member)) = get_guild_and_member(http, guild_id, user_id).await {
-                    match member.edit(http, EditMember::new().deafen(false)).await {
-                        Ok(()) => info!("Successfully removed voice deafen from user {user_id}"),
-                        Err(e) => error!("Failed to remove voice deafen from user {user_id}: {e}"),
-                    }
-                }
-            }
-            // These actions don't need reversal as they're one-time actions
-            EnforcementAction::Kick { .. }
-            | EnforcementAction::VoiceDisconnect { .. }
-            | EnforcementAction::VoiceChannelHaunt { .. }
-            | EnforcementAction::None => {}
+        if let Err(e) = lift_action(backend, guild_id, user_id, &active.action).await {
+            return Err(record_failure(backend, data, active, Attempt::Reverse, e).await);
         }
 
         // Update enforcement state
         active.state = EnforcementState::Reversed;
         active.reversed_at = Some(now);
         active.executed = true; // For backward compatibility
+        active.attempts = 0;
+        active.last_error = None;
 
         // Get the enforcement ID and data for later
         let id = active.id.clone();
-        let enforcement_data = active.value().clone();
-        let user_id = enforcement_data.user_id;
-        let guild_id = enforcement_data.guild_id;
-        drop(active); // Drop the mutable borrow
+        let user_id = active.user_id;
+        let guild_id = active.guild_id;
 
         // Move enforcement to completed map
-        data.active_enforcements.remove(&id);
-        data.completed_enforcements
-            .insert(id.clone(), enforcement_data);
+        data.store_enforcement(active);
 
         info!(
             target: crate::COMMAND_TARGET,
@@ -813,92 +697,222 @@ async fn reverse_enforcement(http: &Http, data: &Data, enforcement_id: &str) ->
     Ok(())
 }
 
+/// What a failed attempt at an enforcement was trying to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    /// Carry out the action
+    Execute,
+    /// Lift the action once its time is up
+    Reverse,
+    /// Teleport the user during a haunt
+    Hop,
+}
+
+/// How long to wait before the next attempt, after `attempts` failed ones
+fn retry_delay(attempts: u32) -> chrono::Duration {
+    let factor = 2_i64.saturating_pow(attempts.saturating_sub(1));
+    #[allow(clippy::cast_possible_wrap)]
+    let base = RETRY_BASE_SECONDS as i64;
+    chrono::Duration::seconds(base.saturating_mul(factor))
+}
+
+/// Record a failed attempt at executing or reversing an enforcement
+///
+/// Transient failures move the enforcement's deadline, or its haunt's next hop,
+/// back so it's retried, until [`MAX_ATTEMPTS`] is reached. Anything else marks it
+/// [`EnforcementState::Failed`] and reports it to the guild's altar. Returns
+/// the failure for the caller to pass on.
+pub async fn record_failure(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    mut enforcement: PendingEnforcement,
+    attempt: Attempt,
+    error: ModerationError,
+) -> Error {
+    enforcement.attempts += 1;
+    enforcement.last_error = Some(error.to_string());
+
+    if error.is_transient() && enforcement.attempts < MAX_ATTEMPTS {
+        let retry_at = Utc::now() + retry_delay(enforcement.attempts);
+        warn!(
+            "Attempt {} at enforcement {} failed, retrying at {retry_at}: {error}",
+            enforcement.attempts, enforcement.id
+        );
+        match attempt {
+            Attempt::Execute => enforcement.execute_at = retry_at,
+            Attempt::Reverse => enforcement.reverse_at = Some(retry_at),
+            Attempt::Hop => {
+                if let Some(progress) = &mut enforcement.haunt {
+                    progress.next_hop_at = retry_at;
+                }
+            }
+        }
+        data.store_enforcement(enforcement);
+        return error.into();
+    }
+
+    warn!(
+        target: crate::COMMAND_TARGET,
+        enforcement_id = %enforcement.id,
+        user_id = %enforcement.user_id,
+        guild_id = %enforcement.guild_id,
+        attempts = %enforcement.attempts,
+        event = "enforcement_failed",
+        "Giving up on enforcement action: {error}"
+    );
+    report_failure(backend, data, &enforcement, attempt).await;
+
+    let (user_id, guild_id) = (enforcement.user_id, enforcement.guild_id);
+    enforcement.state = EnforcementState::Failed;
+    data.store_enforcement(enforcement);
+    if attempt == Attempt::Execute {
+        clear_pending_enforcement(data, user_id, guild_id);
+    }
+
+    error.into()
+}
+
+/// Tell the guild's altar that an enforcement was given up on
+async fn report_failure(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    enforcement: &PendingEnforcement,
+    attempt: Attempt,
+) {
+    let log_channel_id = data
+        .guild_configs
+        .get(&GuildId::new(enforcement.guild_id))
+        .and_then(|config| config.enforcement_log_channel_id);
+    let Some(log_channel_id) = log_channel_id else {
+        return;
+    };
+
+    let verb = match attempt {
+        Attempt::Execute => "carry out",
+        Attempt::Reverse => "lift",
+        Attempt::Hop => "finish",
+    };
+    let msg_content = format!(
+        "⚠️ **THE DAEMON IS THWARTED**\n\nI could not {verb} {} for <@{}> after {} attempt(s): {}\nEnforcement `{}` needs a moderator's attention.",
+        enforcement.action,
+        enforcement.user_id,
+        enforcement.attempts,
+        enforcement.last_error.as_deref().unwrap_or("unknown error"),
+        enforcement.id
+    );
+    if let Err(e) = backend
+        .post_message(ChannelId::new(log_channel_id), &msg_content)
+        .await
+    {
+        error!(
+            "Failed to report failed enforcement {} to guild {}: {e}",
+            enforcement.id, enforcement.guild_id
+        );
+    }
+}
+
 /// Clear the pending enforcement from a user's warning state after it has been executed
 fn clear_pending_enforcement(data: &Data, user_id: u64, guild_id: u64) {
-    let key = format!("{user_id}:{guild_id}");
+    let key = user_state_key(user_id, guild_id);
 
-    if let Some(mut state) = data.user_warning_states.get_mut(&key) {
+    let state = data
+        .user_warning_states
+        .get(&key)
+        .map(|entry| entry.value().clone());
+    if let Some(mut updated_state) = state {
         // Clear the pending enforcement
-        if state.pending_enforcement.is_some() {
+        if updated_state.pending_enforcement.is_some() {
             info!("Clearing pending enforcement for user {user_id} in guild {guild_id}");
-            let mut updated_state = state.value().clone();
             updated_state.pending_enforcement = None;
             updated_state.last_updated = Utc::now();
 
             // Update the state
-            *state = updated_state;
+            data.set_user_warning_state(updated_state);
+        }
+    }
+}
+
+/// Carry out an action on Discord
+///
+/// Haunts only begin here; their progress is recorded on the enforcement and
+/// a haunt job does the teleporting.
+async fn apply_action(
+    backend: &dyn ModerationBackend,
+    pending: &mut PendingEnforcement,
+) -> Result<(), ModerationError> {
+    let guild_id = GuildId::new(pending.guild_id);
+    let user_id = UserId::new(pending.user_id);
+
+    // Execute the action based on the type
+    match &pending.action {
+        EnforcementAction::Mute { duration } => {
+            handle_mute_action(backend, guild_id, user_id, duration, false).await?;
+        }
+        EnforcementAction::VoiceChannelHaunt { .. } => {
+            pending.haunt = Some(haunt::begin(backend, pending).await?);
+        }
+        EnforcementAction::Ban { duration } => {
+            let duration = if let Some(dur) = duration { *dur } else { 0 };
+            warn!(
+                target: crate::COMMAND_TARGET,
+                enforcement_id = %pending.id,
+                user_id = %user_id,
+                guild_id = %guild_id,
+                duration = %duration,
+                event = "enforcement_ban",
+                "*NOT* Executing ban action, don't be a pussy and uncomment this call"
+            );
+            //handle_ban_action(backend, guild_id, user_id, duration, false).await?;
+        }
+        EnforcementAction::Kick { delay } => {
+            let delay = if let Some(dur) = delay { *dur } else { 0 };
+            warn!(
+                target: crate::COMMAND_TARGET,
+                enforcement_id = %pending.id,
+                user_id = %user_id,
+                guild_id = %guild_id,
+                delay = %delay,
+                event = "enforcement_kick",
+                "*NOT* Executing kick action, don't be a pussy and uncomment this call"
+            );
+            //handle_kick_action(backend, guild_id, user_id, delay, false).await?;
         }
+        EnforcementAction::VoiceMute { duration } => {
+            handle_voice_mute_action(backend, guild_id, user_id, duration, false).await?;
+        }
+        EnforcementAction::VoiceDeafen { duration } => {
+            handle_voice_deafen_action(backend, guild_id, user_id, duration, false).await?;
+        }
+        EnforcementAction::VoiceDisconnect { delay } => {
+            handle_voice_disconnect_action(backend, guild_id, user_id, delay.as_ref(), false)
+                .await?;
+        }
+        EnforcementAction::None => {}
     }
+    Ok(())
 }
 
 /// Execute a pending enforcement action
-async fn execute_enforcement(http: &Http, data: &Data, enforcement_id: &str) -> Result<(), Error> {
-    // Try to get and remove the pending enforcement
-    if let Some(mut pending) = data.pending_enforcements.get_mut(enforcement_id) {
-        let guild_id = GuildId::new(pending.guild_id);
-        let user_id = UserId::new(pending.user_id);
+///
+/// Failures are retried and reported the same way as in [`reverse_enforcement`].
+///
+/// # Errors
+/// Returns an error if the action couldn't be carried out.
+pub async fn execute_enforcement(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    enforcement_id: &str,
+) -> Result<(), Error> {
+    // Try to get the pending enforcement
+    let pending = data
+        .pending_enforcements
+        .get(enforcement_id)
+        .map(|entry| entry.value().clone());
+    if let Some(mut pending) = pending {
         let now = Utc::now();
 
-        // Execute the action based on the type
-        match &pending.action {
-            EnforcementAction::Mute { duration } => {
-                handle_mute_action(http, guild_id, user_id, duration, false).await?;
-            }
-            EnforcementAction::VoiceChannelHaunt {
-                teleport_count,
-                interval,
-                return_to_origin,
-                original_channel_id,
-            } => {
-                handle_voice_channel_haunt_action(
-                    http,
-                    guild_id,
-                    user_id,
-                    teleport_count,
-                    interval,
-                    return_to_origin,
-                    original_channel_id,
-                    false,
-                )
-                .await?;
-            }
-            EnforcementAction::Ban { duration } => {
-                let duration = if let Some(dur) = duration { *dur } else { 0 };
-                warn!(
-                    target: crate::COMMAND_TARGET,
-                    enforcement_id = %pending.id,
-                    user_id = %user_id,
-                    guild_id = %guild_id,
-                    duration = %duration,
-                    event = "enforcement_ban",
-                    "*NOT* Executing ban action, don't be a pussy and uncomment this call"
-                );
-                //handle_ban_action(http, guild_id, user_id, duration, false).await?;
-            }
-            EnforcementAction::Kick { delay } => {
-                let delay = if let Some(dur) = delay { *dur } else { 0 };
-                warn!(
-                    target: crate::COMMAND_TARGET,
-                    enforcement_id = %pending.id,
-                    user_id = %user_id,
-                    guild_id = %guild_id,
-                    delay = %delay,
-                    event = "enforcement_kick",
-                    "*NOT* Executing kick action, don't be a pussy and uncomment this call"
-                );
-                //handle_kick_action(http, guild_id, user_id, delay, false).await?;
-            }
-            EnforcementAction::VoiceMute { duration } => {
-                handle_voice_mute_action(http, guild_id, user_id, duration, false).await?;
-            }
-            EnforcementAction::VoiceDeafen { duration } => {
-                handle_voice_deafen_action(http, guild_id, user_id, duration, false).await?;
-            }
-            EnforcementAction::VoiceDisconnect { delay } => {
-                handle_voice_disconnect_action(http, guild_id, user_id, delay.as_ref(), false)
-                    .await?;
-            }
-            EnforcementAction::None => {}
+        if let Err(e) = apply_action(backend, &mut pending).await {
+            return Err(record_failure(backend, data, pending, Attempt::Execute, e).await);
         }
 
         // Calculate when to reverse the action (if applicable)
@@ -912,8 +926,10 @@ async fn execute_enforcement(http: &Http, data: &Data, enforcement_id: &str) ->
                 | EnforcementAction::VoiceChannelHaunt { .. }
         );
 
-        // Determine final state based on the action type and reverse_at
+        // Determine final state based on the action type and reverse_at.
+        // Haunts stay active while their job teleports the user around.
         let needs_reversal = !is_one_time && reverse_at_option.is_some();
+        let stays_active = needs_reversal || pending.haunt.is_some();
 
         // Get the enforcement data we need for logging
         let id = pending.id.clone();
@@ -925,25 +941,15 @@ async fn execute_enforcement(http: &Http, data: &Data, enforcement_id: &str) ->
         pending.executed_at = Some(now);
         pending.executed = true; // For backward compatibility
         pending.reverse_at.clone_from(&reverse_at_option);
+        pending.attempts = 0;
+        pending.last_error = None;
 
-        // Clone the enforcement data before dropping the borrow
-        let mut enforcement_data = pending.value().clone();
-        drop(pending); // Drop the mutable borrow
-
-        // Remove from pending enforcements
-        data.pending_enforcements.remove(&id);
-
-        // Determine where to put the enforcement based on whether it needs reversal
-        if needs_reversal {
-            // For actions that will need reversal, move to active
-            data.active_enforcements
-                .insert(id.clone(), enforcement_data);
-        } else {
-            // For actions that don't need reversal, move directly to completed
-            enforcement_data.state = EnforcementState::Completed;
-            data.completed_enforcements
-                .insert(id.clone(), enforcement_data);
+        // Determine where to put the enforcement based on whether it needs reversal.
+        // Actions that will need reversal stay active, the rest are completed.
+        if !stays_active {
+            pending.state = EnforcementState::Completed;
         }
+        data.store_enforcement(pending);
 
         // Clear the pending enforcement from the user's warning state so future warnings
         // can set new appropriate enforcements based on the infraction type
@@ -965,22 +971,23 @@ async fn execute_enforcement(http: &Http, data: &Data, enforcement_id: &str) ->
 }
 
 /// Calculate when an enforcement action should be reversed
+/// The time `seconds` from now, or `None` if that can't be represented
+#[must_use]
+pub fn seconds_from_now(seconds: u64) -> Option<DateTime<Utc>> {
+    let delta = i64::try_from(seconds)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)?;
+    Utc::now().checked_add_signed(delta)
+}
+
 fn calculate_reversal_time(action: &EnforcementAction) -> Option<DateTime<Utc>> {
     match action {
         EnforcementAction::Mute { duration }
         | EnforcementAction::Ban { duration }
         | EnforcementAction::VoiceMute { duration }
         | EnforcementAction::VoiceDeafen { duration } => {
-            if let Some(secs) = duration {
-                if *secs > 0 {
-                    // Add duration to current time
-                    Some(Utc::now() + chrono::Duration::seconds(*secs as i64))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+            // A duration too long to represent is never lifted
+            duration.filter(|secs| *secs > 0).and_then(seconds_from_now)
         }
         // These actions don't require reversal as they're one-time operations
         EnforcementAction::Kick { .. }
@@ -989,3 +996,417 @@ fn calculate_reversal_time(action: &EnforcementAction) -> Option<DateTime<Utc>>
         | EnforcementAction::None => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::GuildConfig;
+    use crate::moderation::{ModerationCall, RecordingBackend};
+
+    const USER: u64 = 12345;
+    const GUILD: u64 = 11111;
+
+    fn pending(id: &str, action: EnforcementAction) -> PendingEnforcement {
+        PendingEnforcement::test(id, action, EnforcementState::Pending)
+    }
+
+    #[tokio::test]
+    async fn test_voice_mute_is_applied_then_lifted() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        data.store_enforcement(pending(
+            "mute",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+        ));
+
+        execute_enforcement(&backend, &data, "mute").await.unwrap();
+        let active = data.active_enforcements.get("mute").unwrap().clone();
+        assert_eq!(active.state, EnforcementState::Active);
+        assert!(active.reverse_at.is_some());
+
+        reverse_enforcement(&backend, &data, "mute").await.unwrap();
+        let reversed = data.completed_enforcements.get("mute").unwrap().clone();
+        assert_eq!(reversed.state, EnforcementState::Reversed);
+        assert!(data.active_enforcements.is_empty());
+
+        let user_id = UserId::new(USER);
+        assert_eq!(
+            backend.calls(),
+            [
+                ModerationCall::VoiceMute {
+                    user_id,
+                    muted: true
+                },
+                ModerationCall::VoiceMute {
+                    user_id,
+                    muted: false
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_one_time_actions_complete_without_reversal() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        backend.join_voice(USER, 1);
+        data.store_enforcement(pending(
+            "disconnect",
+            EnforcementAction::VoiceDisconnect { delay: None },
+        ));
+
+        execute_enforcement(&backend, &data, "disconnect")
+            .await
+            .unwrap();
+        let completed = data
+            .completed_enforcements
+            .get("disconnect")
+            .unwrap()
+            .clone();
+        assert_eq!(completed.state, EnforcementState::Completed);
+        assert_eq!(completed.reverse_at, None);
+        assert_eq!(
+            backend.calls(),
+            [ModerationCall::Disconnect {
+                user_id: UserId::new(USER)
+            }]
+        );
+        assert_eq!(
+            backend
+                .voice_channel(GuildId::new(GUILD), UserId::new(USER))
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_lasts_for_the_mute_duration() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        data.store_enforcement(pending(
+            "timeout",
+            EnforcementAction::Mute {
+                duration: Some(600),
+            },
+        ));
+
+        let before = Utc::now();
+        execute_enforcement(&backend, &data, "timeout")
+            .await
+            .unwrap();
+        let [ModerationCall::Timeout { until, .. }] = backend.calls()[..] else {
+            panic!("expected a single timeout, got {:?}", backend.calls());
+        };
+        assert!(until >= before + chrono::Duration::seconds(600));
+
+        // Discord lifts timeouts itself, so reversing makes no call
+        reverse_enforcement(&backend, &data, "timeout")
+            .await
+            .unwrap();
+        assert_eq!(backend.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_haunt_begins_and_is_left_to_its_job() {
+        let data = Data::new();
+        let backend = RecordingBackend::with_voice_channels(&[1, 2, 3]);
+        backend.join_voice(USER, 1);
+        data.store_enforcement(pending(
+            "haunt",
+            EnforcementAction::VoiceChannelHaunt {
+                teleport_count: None,
+                interval: Some(5),
+                return_to_origin: None,
+                original_channel_id: None,
+            },
+        ));
+
+        // Nobody is teleported yet, the haunt's job does that
+        execute_enforcement(&backend, &data, "haunt").await.unwrap();
+        assert!(backend.calls().is_empty());
+
+        let active = data.active_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(active.reverse_at, None);
+        let progress = active.haunt.unwrap();
+        assert_eq!(progress.origin_channel_id, 1);
+        assert!((1..=3).contains(&progress.teleport_count));
+        assert_eq!(progress.interval, 5);
+        assert!(progress.return_to_origin);
+        assert_eq!(progress.teleports_done, 0);
+        assert!(data.schedule.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_haunt_needs_user_in_voice() {
+        let data = Data::new();
+        let backend = RecordingBackend::with_voice_channels(&[1, 2]);
+        data.store_enforcement(pending(
+            "haunt",
+            EnforcementAction::VoiceChannelHaunt {
+                teleport_count: Some(1),
+                interval: Some(5),
+                return_to_origin: Some(true),
+                original_channel_id: None,
+            },
+        ));
+
+        // Nobody to haunt, so there's nothing to retry
+        assert!(execute_enforcement(&backend, &data, "haunt").await.is_err());
+        assert!(backend.calls().is_empty());
+        let failed = data.completed_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(failed.state, EnforcementState::Failed);
+        assert_eq!(failed.attempts, 1);
+    }
+
+    fn transient() -> ModerationError {
+        ModerationError::Transient("rate limited".into())
+    }
+
+    #[tokio::test]
+    async fn test_transient_failure_is_retried_with_backoff() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        data.store_enforcement(pending(
+            "mute",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+        ));
+
+        backend.fail_next(transient());
+        let before = Utc::now();
+        assert!(execute_enforcement(&backend, &data, "mute").await.is_err());
+        let retrying = data.pending_enforcements.get("mute").unwrap().clone();
+        assert_eq!(retrying.state, EnforcementState::Pending);
+        assert_eq!(retrying.attempts, 1);
+        assert_eq!(retrying.last_error.as_deref(), Some("rate limited"));
+        assert!(retrying.execute_at >= before + retry_delay(1));
+        assert_eq!(data.schedule.next_deadline(), Some(retrying.execute_at));
+
+        // The retry goes through and the failure is forgotten
+        execute_enforcement(&backend, &data, "mute").await.unwrap();
+        let active = data.active_enforcements.get("mute").unwrap().clone();
+        assert_eq!(active.attempts, 0);
+        assert_eq!(active.last_error, None);
+        assert_eq!(backend.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reversal_is_retried_until_attempts_run_out() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        let mut mute = pending(
+            "mute",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+        );
+        mute.state = EnforcementState::Active;
+        mute.reverse_at = Some(Utc::now());
+        data.store_enforcement(mute);
+
+        for attempt in 1..MAX_ATTEMPTS {
+            backend.fail_next(transient());
+            assert!(reverse_enforcement(&backend, &data, "mute").await.is_err());
+            let active = data.active_enforcements.get("mute").unwrap().clone();
+            assert_eq!(active.attempts, attempt);
+        }
+
+        backend.fail_next(transient());
+        assert!(reverse_enforcement(&backend, &data, "mute").await.is_err());
+        let failed = data.completed_enforcements.get("mute").unwrap().clone();
+        assert_eq!(failed.state, EnforcementState::Failed);
+        assert_eq!(failed.attempts, MAX_ATTEMPTS);
+        assert!(data.schedule.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_runs_overdue_enforcements_and_reports_them() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        let mut config = GuildConfig::new(GUILD);
+        config.enforcement_log_channel_id = Some(999);
+        data.set_guild_config(config);
+
+        let mut deafen = pending(
+            "deafen",
+            EnforcementAction::VoiceDeafen {
+                duration: Some(300),
+            },
+        );
+        deafen.execute_at = Utc::now() - chrono::Duration::hours(1);
+        data.store_enforcement(deafen);
+        let mut mute = pending(
+            "mute",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+        );
+        mute.state = EnforcementState::Active;
+        mute.reverse_at = Some(Utc::now() - chrono::Duration::hours(1));
+        data.store_enforcement(mute);
+
+        catch_up_overdue_enforcements(&backend, &data).await;
+
+        let deafen = data.active_enforcements.get("deafen").unwrap().clone();
+        assert_eq!(deafen.state, EnforcementState::Active);
+        let mute = data.completed_enforcements.get("mute").unwrap().clone();
+        assert_eq!(mute.state, EnforcementState::Reversed);
+        assert!(data.pending_enforcements.is_empty());
+
+        let user_id = UserId::new(USER);
+        let calls = backend.calls();
+        assert_eq!(
+            calls[..2],
+            [
+                ModerationCall::VoiceDeafen {
+                    user_id,
+                    deafened: true
+                },
+                ModerationCall::VoiceMute {
+                    user_id,
+                    muted: false
+                },
+            ]
+        );
+        let [
+            _,
+            _,
+            ModerationCall::Message {
+                channel_id,
+                content,
+            },
+        ] = calls.as_slice()
+        else {
+            panic!("expected one catch-up summary, got {calls:?}");
+        };
+        assert_eq!(*channel_id, ChannelId::new(999));
+        assert_eq!(
+            content
+                .lines()
+                .filter(|line| line.contains(&format!("<@{USER}>")))
+                .count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_reports_transient_failures_as_retrying() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        let mut config = GuildConfig::new(GUILD);
+        config.enforcement_log_channel_id = Some(999);
+        data.set_guild_config(config);
+        data.store_enforcement(PendingEnforcement {
+            execute_at: Utc::now() - chrono::Duration::hours(1),
+            ..pending(
+                "deafen",
+                EnforcementAction::VoiceDeafen {
+                    duration: Some(300),
+                },
+            )
+        });
+        data.store_enforcement(PendingEnforcement {
+            reverse_at: Some(Utc::now() - chrono::Duration::hours(1)),
+            ..PendingEnforcement::test(
+                "mute",
+                EnforcementAction::VoiceMute {
+                    duration: Some(300),
+                },
+                EnforcementState::Active,
+            )
+        });
+        backend.fail_next(ModerationError::Transient("rate limited".into()));
+        backend.fail_next(ModerationError::Transient("rate limited".into()));
+
+        catch_up_overdue_enforcements(&backend, &data).await;
+
+        assert!(data.pending_enforcements.contains_key("deafen"));
+        assert!(data.active_enforcements.contains_key("mute"));
+        let calls = backend.calls();
+        let Some(ModerationCall::Message { content, .. }) = calls.last() else {
+            panic!("expected a catch-up summary, got {calls:?}");
+        };
+        assert!(!content.contains("❌"), "{content}");
+        assert_eq!(content.matches("will retry").count(), 2, "{content}");
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failure_is_reported_to_the_altar() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        let mut config = GuildConfig::new(GUILD);
+        config.enforcement_log_channel_id = Some(999);
+        data.set_guild_config(config);
+        data.store_enforcement(pending(
+            "deafen",
+            EnforcementAction::VoiceDeafen {
+                duration: Some(300),
+            },
+        ));
+
+        backend.fail_next(ModerationError::Permanent("Missing Permissions".into()));
+        assert!(
+            execute_enforcement(&backend, &data, "deafen")
+                .await
+                .is_err()
+        );
+        let failed = data.completed_enforcements.get("deafen").unwrap().clone();
+        assert_eq!(failed.state, EnforcementState::Failed);
+        assert_eq!(failed.last_error.as_deref(), Some("Missing Permissions"));
+
+        let calls = backend.calls();
+        assert_eq!(calls.len(), 2);
+        let ModerationCall::Message {
+            channel_id,
+            content,
+        } = &calls[1]
+        else {
+            panic!("expected a report to the altar, got {:?}", calls[1]);
+        };
+        assert_eq!(*channel_id, ChannelId::new(999));
+        assert!(content.contains("Missing Permissions"));
+    }
+
+    #[tokio::test]
+    async fn test_requests_wait_for_a_retry_to_come_due() {
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        data.store_enforcement(pending(
+            "mute",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+        ));
+        backend.fail_next(transient());
+        assert!(execute_enforcement(&backend, &data, "mute").await.is_err());
+
+        check_specific_enforcement(&backend, &data, "mute")
+            .await
+            .unwrap();
+        check_user_enforcements(&backend, &data, USER, GUILD)
+            .await
+            .unwrap();
+        assert_eq!(backend.calls().len(), 1);
+        assert_eq!(data.pending_enforcements.get("mute").unwrap().attempts, 1);
+    }
+
+    #[test]
+    fn test_durations_too_long_to_represent_are_never_lifted() {
+        assert!(seconds_from_now(u64::MAX).is_none());
+        assert!(seconds_from_now(i64::MAX as u64).is_none());
+        let ban = EnforcementAction::Ban {
+            duration: Some(100_000_000 * 86400),
+        };
+        assert_eq!(calculate_reversal_time(&ban), None);
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1), chrono::Duration::seconds(5));
+        assert_eq!(retry_delay(2), chrono::Duration::seconds(10));
+        assert_eq!(retry_delay(4), chrono::Duration::seconds(40));
+    }
+}
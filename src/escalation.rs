@@ -0,0 +1,329 @@
+//! Per-guild escalation ladders
+//!
+//! A guild can give each infraction category an ordered ladder of punishments.
+//! A user's nth offense in a category within the lookback window gets the nth
+//! rung, and once the ladder runs out the last rung repeats. Categories without
+//! a ladder keep the daemon's built-in choices.
+
+use crate::data::{EnforcementAction, UserWarningState};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Default number of days offenses count toward climbing a ladder
+pub const DEFAULT_LOOKBACK_DAYS: u32 = 30;
+/// Most rungs a ladder can have
+pub const MAX_RUNGS: usize = 10;
+/// Most days offenses can count toward climbing a ladder
+pub const MAX_LOOKBACK_DAYS: u32 = 365;
+/// Longest duration or delay a punishment can have, in seconds
+pub const MAX_DURATION_SECONDS: u64 = 365 * 86400;
+/// Longest timeout Discord allows, in seconds
+pub const MAX_TIMEOUT_SECONDS: u64 = 28 * 86400;
+
+/// A guild's escalation ladders, as stored in its config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationSettings {
+    /// Ladder for each infraction category
+    #[serde(default)]
+    pub ladders: BTreeMap<String, Vec<EnforcementAction>>,
+    /// Days an offense counts toward climbing the ladder
+    #[serde(default = "default_lookback_days")]
+    pub lookback_days: u32,
+}
+
+impl Default for EscalationSettings {
+    fn default() -> Self {
+        Self {
+            ladders: BTreeMap::new(),
+            lookback_days: DEFAULT_LOOKBACK_DAYS,
+        }
+    }
+}
+
+fn default_lookback_days() -> u32 {
+    DEFAULT_LOOKBACK_DAYS
+}
+
+impl EscalationSettings {
+    /// The punishment for a user's latest offense in `category`, or `None` if
+    /// the category has no ladder
+    #[must_use]
+    pub fn select(
+        &self,
+        state: &UserWarningState,
+        category: &str,
+        now: DateTime<Utc>,
+    ) -> Option<EnforcementAction> {
+        let ladder = self
+            .ladders
+            .get(category)
+            .filter(|ladder| !ladder.is_empty())?;
+        let offenses = self.offense_count(state, category, now);
+        let rung = offenses.clamp(1, ladder.len()) - 1;
+        Some(ladder[rung].clone())
+    }
+
+    /// How many of a user's warnings in `category` fall within the lookback window
+    #[must_use]
+    pub fn offense_count(
+        &self,
+        state: &UserWarningState,
+        category: &str,
+        now: DateTime<Utc>,
+    ) -> usize {
+        // A lookback reaching past the start of time counts every offense
+        let since = now
+            .checked_sub_signed(Duration::days(i64::from(self.lookback_days)))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        state
+            .for_category(category)
+            .warning_timestamps
+            .iter()
+            .filter(|timestamp| **timestamp >= since)
+            .count()
+    }
+}
+
+/// Parse a ladder such as `voice_mute 5m, deafen 15m, haunt, disconnect`
+///
+/// Rungs are separated by commas or `>`. Each is an action name optionally
+/// followed by a duration (`30s`, `5m`, `2h`, `1d`, or plain seconds), which is
+/// the delay for `kick` and `disconnect`.
+///
+/// # Errors
+/// Returns an error describing the first rung that can't be parsed, or if the
+/// ladder is empty or longer than [`MAX_RUNGS`].
+pub fn parse_ladder(ladder: &str) -> Result<Vec<EnforcementAction>, String> {
+    let rungs = ladder
+        .split([',', '>'])
+        .map(str::trim)
+        .filter(|rung| !rung.is_empty())
+        .map(parse_action)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if rungs.is_empty() {
+        return Err("The ladder needs at least one punishment".to_string());
+    }
+    if rungs.len() > MAX_RUNGS {
+        return Err(format!("A ladder can have at most {MAX_RUNGS} rungs"));
+    }
+    Ok(rungs)
+}
+
+/// Parse a single punishment such as `deafen 15m` or `haunt`
+///
+/// # Errors
+/// Returns an error if the action name is unknown, the duration can't be read
+/// or a `mute` runs past Discord's 28 day timeout limit.
+pub fn parse_action(rung: &str) -> Result<EnforcementAction, String> {
+    let mut parts = rung.split_whitespace();
+    let name = parts.next().unwrap_or_default().to_lowercase();
+    let duration = parts.next().map(parse_duration).transpose()?;
+    if parts.next().is_some() {
+        return Err(format!("Too many words in `{rung}`"));
+    }
+
+    let action = match name.as_str() {
+        "mute" if duration.is_some_and(|seconds| seconds > MAX_TIMEOUT_SECONDS) => {
+            return Err(format!(
+                "`{rung}` is too long, Discord can't time anyone out for more than 28d"
+            ));
+        }
+        "mute" => EnforcementAction::Mute { duration },
+        "ban" => EnforcementAction::Ban { duration },
+        "kick" => EnforcementAction::Kick { delay: duration },
+        "voice_mute" | "voicemute" => EnforcementAction::VoiceMute { duration },
+        "deafen" | "voice_deafen" | "voicedeafen" => EnforcementAction::VoiceDeafen { duration },
+        "disconnect" | "voice_disconnect" | "voicedisconnect" => {
+            EnforcementAction::VoiceDisconnect { delay: duration }
+        }
+        "haunt" if duration.is_none() => EnforcementAction::VoiceChannelHaunt {
+            teleport_count: None,
+            interval: None,
+            return_to_origin: None,
+            original_channel_id: None,
+        },
+        "haunt" => return Err("`haunt` doesn't take a duration".to_string()),
+        other => {
+            return Err(format!(
+                "Unknown punishment `{other}`. Use `mute`, `ban`, `kick`, `voice_mute`, `deafen`, `disconnect` or `haunt`."
+            ));
+        }
+    };
+    Ok(action)
+}
+
+/// Parse a duration like `90`, `30s`, `5m`, `2h` or `1d` into seconds
+///
+/// # Errors
+/// Returns an error if the number or unit can't be read, or the duration is
+/// longer than [`MAX_DURATION_SECONDS`].
+pub fn parse_duration(value: &str) -> Result<u64, String> {
+    let value = value.to_lowercase();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value.as_str(), "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => {
+            return Err(format!(
+                "Unknown duration `{value}`. Use e.g. `30s`, `5m`, `2h` or `1d`."
+            ));
+        }
+    };
+    let seconds = number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| {
+            format!("Unknown duration `{value}`. Use e.g. `30s`, `5m`, `2h` or `1d`.")
+        })?;
+    if seconds > MAX_DURATION_SECONDS {
+        return Err(format!(
+            "`{value}` is too long, durations can be at most {}",
+            format_duration(MAX_DURATION_SECONDS)
+        ));
+    }
+    Ok(seconds)
+}
+
+/// Write a ladder back out in the form [`parse_ladder`] reads
+#[must_use]
+pub fn format_ladder(ladder: &[EnforcementAction]) -> String {
+    ladder
+        .iter()
+        .map(format_action)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write a single punishment back out in the form [`parse_action`] reads
+#[must_use]
+pub fn format_action(action: &EnforcementAction) -> String {
+    let (name, duration) = match action {
+        EnforcementAction::None => ("none", None),
+        EnforcementAction::Mute { duration } => ("mute", *duration),
+        EnforcementAction::Ban { duration } => ("ban", *duration),
+        EnforcementAction::Kick { delay } => ("kick", *delay),
+        EnforcementAction::VoiceMute { duration } => ("voice_mute", *duration),
+        EnforcementAction::VoiceDeafen { duration } => ("deafen", *duration),
+        EnforcementAction::VoiceDisconnect { delay } => ("disconnect", *delay),
+        EnforcementAction::VoiceChannelHaunt { .. } => ("haunt", None),
+    };
+    match duration {
+        Some(seconds) => format!("{name} {}", format_duration(seconds)),
+        None => name.to_string(),
+    }
+}
+
+/// Write seconds in the largest whole unit [`parse_duration`] reads
+#[must_use]
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0 => "0s".to_string(),
+        _ if seconds.is_multiple_of(86400) => format!("{}d", seconds / 86400),
+        _ if seconds.is_multiple_of(3600) => format!("{}h", seconds / 3600),
+        _ if seconds.is_multiple_of(60) => format!("{}m", seconds / 60),
+        _ => format!("{seconds}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ladder() {
+        let ladder = parse_ladder("voice_mute 5m, deafen 15m > haunt, disconnect").unwrap();
+        assert_eq!(ladder.len(), 4);
+        assert_eq!(
+            ladder[0],
+            EnforcementAction::VoiceMute {
+                duration: Some(300)
+            }
+        );
+        assert_eq!(
+            ladder[3],
+            EnforcementAction::VoiceDisconnect { delay: None }
+        );
+        assert_eq!(
+            format_ladder(&ladder),
+            "voice_mute 5m, deafen 15m, haunt, disconnect"
+        );
+        assert_eq!(parse_ladder(&format_ladder(&ladder)), Ok(ladder));
+
+        assert!(parse_ladder("").is_err());
+        assert!(parse_ladder("mute 5 minutes").is_err());
+        assert!(parse_ladder("mute 5w").is_err());
+        assert!(parse_ladder("haunt 3m").is_err());
+        assert!(parse_ladder("smite").is_err());
+        assert!(parse_ladder("mute 28d").is_ok());
+        assert!(parse_ladder("mute 29d").is_err());
+        assert!(parse_ladder("ban 29d").is_ok());
+        assert!(parse_ladder("ban 365d").is_ok());
+        assert!(parse_ladder("ban 366d").is_err());
+        assert!(parse_ladder("ban 100000000d").is_err());
+        assert!(parse_ladder("kick 99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_select_walks_the_ladder_within_the_lookback() {
+        let now = Utc::now();
+        let mut settings = EscalationSettings::default();
+        settings.ladders.insert(
+            "voice".to_string(),
+            parse_ladder("voice_mute 5m, deafen 15m, disconnect").unwrap(),
+        );
+
+        let mut state = UserWarningState {
+            user_id: 12345,
+            guild_id: 11111,
+            warning_timestamps: vec![now - Duration::days(40)],
+            warning_reasons: Vec::new(),
+            mod_issuers: Vec::new(),
+            warning_severities: Vec::new(),
+            warning_categories: vec![Some("voice".to_string())],
+            pending_enforcement: None,
+            last_updated: now,
+            purged_warnings: 0,
+        };
+        // Only a stale offense: the current one is still the first
+        assert!(settings.select(&state, "text", now).is_none());
+        assert_eq!(
+            settings.select(&state, "voice", now),
+            Some(EnforcementAction::VoiceMute {
+                duration: Some(300)
+            })
+        );
+
+        for _ in 0..2 {
+            state.warning_timestamps.push(now);
+            state.warning_categories.push(Some("voice".to_string()));
+        }
+        assert_eq!(
+            settings.select(&state, "voice", now),
+            Some(EnforcementAction::VoiceDeafen {
+                duration: Some(900)
+            })
+        );
+
+        // The last rung repeats
+        for _ in 0..3 {
+            state.warning_timestamps.push(now);
+            state.warning_categories.push(Some("voice".to_string()));
+        }
+        assert_eq!(
+            settings.select(&state, "voice", now),
+            Some(EnforcementAction::VoiceDisconnect { delay: None })
+        );
+
+        // A lookback reaching past the start of time counts everything
+        settings.lookback_days = u32::MAX;
+        assert_eq!(settings.offense_count(&state, "voice", now), 6);
+    }
+}
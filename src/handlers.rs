@@ -25,12 +25,12 @@ impl EventHandler for Handler {
             );
         }
 
-        let arc_cache = ctx.cache.clone();
-        if let Some(data) = ctx.data.write().await.get_mut::<Data>() {
+        let data = ctx.data.read().await.get::<Data>().cloned();
+        if let Some(data) = data {
             // Initialize the cache with the current guilds
             info!("Initializing cache...");
-            data.cache = arc_cache.clone();
-            data.status.write().await.initialize_from_cache(data);
+            data.set_cache(ctx.cache.clone());
+            data.status.write().await.initialize_from_cache(&data);
         } else {
             warn!("Could not get user data from context");
         }
@@ -102,7 +102,6 @@ mod tests {
     fn test_handler_creation() {
         let _handler = Handler;
         let _another_handler = Handler;
-        assert!(true, "Handler can be created");
     }
 
     // Since we can't easily mock Context and Ready objects due to their complex structure,
@@ -0,0 +1,522 @@
+//! Voice channel haunts as jobs of their own
+//!
+//! Executing a haunt only works out where the user is and how the haunt will
+//! go, and leaves the enforcement active with its [`HauntProgress`]. Each haunt
+//! then runs as its own task, one teleport at a time, saving its progress after
+//! every hop. The enforcement task calls [`HauntJobs::supervise`] to start jobs
+//! for new haunts, pick haunts back up after a restart and restart jobs that
+//! died. [`cancel`] stops a haunt midway and sends the user back to where it
+//! started.
+
+use crate::data::{EnforcementAction, EnforcementState, PendingEnforcement};
+use crate::enforcement::{Attempt, record_failure};
+use crate::moderation::{ModerationBackend, ModerationError};
+use crate::{Data, Error};
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// How far a haunt has got, saved after every teleport
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HauntProgress {
+    /// Voice channel the user was in when the haunt began
+    pub origin_channel_id: u64,
+    /// Teleports the haunt will make in total
+    pub teleport_count: u64,
+    /// Seconds between teleports
+    pub interval: u64,
+    /// Whether the user is sent back to the origin channel at the end
+    pub return_to_origin: bool,
+    /// Teleports made so far
+    pub teleports_done: u64,
+    /// When the next teleport, or the return home, is due
+    pub next_hop_at: DateTime<Utc>,
+}
+
+impl HauntProgress {
+    /// Whether every teleport has been made
+    #[must_use]
+    pub fn teleports_finished(&self) -> bool {
+        self.teleports_done >= self.teleport_count
+    }
+}
+
+/// Work out how a haunt will go, starting from the user's current voice channel
+///
+/// Missing parameters are rolled here, so a resumed haunt carries on exactly as
+/// it began.
+///
+/// # Errors
+/// Fails permanently if the user isn't in voice or the guild has no voice
+/// channels to haunt them through.
+pub async fn begin(
+    backend: &dyn ModerationBackend,
+    enforcement: &PendingEnforcement,
+) -> Result<HauntProgress, ModerationError> {
+    let EnforcementAction::VoiceChannelHaunt {
+        teleport_count,
+        interval,
+        return_to_origin,
+        original_channel_id,
+    } = &enforcement.action
+    else {
+        return Err(ModerationError::Permanent(
+            format!("Enforcement {} is not a haunt", enforcement.id).into(),
+        ));
+    };
+    let guild_id = GuildId::new(enforcement.guild_id);
+    let user_id = UserId::new(enforcement.user_id);
+
+    info!("Beginning voice channel haunting for user {user_id} in guild {guild_id}");
+
+    // Find the user's current voice channel
+    let Some(voice_channel_id) = backend.voice_channel(guild_id, user_id).await else {
+        return Err(ModerationError::Permanent(
+            format!("User {user_id} is not in a voice channel, cannot haunt").into(),
+        ));
+    };
+
+    if backend.voice_channels(guild_id).await.is_empty() {
+        return Err(ModerationError::Permanent(
+            format!("No voice channels found in guild {guild_id} for haunting").into(),
+        ));
+    }
+
+    // Default parameters or use provided ones
+    let (teleport_count, interval) = {
+        let mut rng = rand::thread_rng();
+        let count = teleport_count.unwrap_or_else(|| rand::Rng::gen_range(&mut rng, 1..=3));
+        let delay = interval.unwrap_or_else(|| rand::Rng::gen_range(&mut rng, 5..=15));
+        (count, delay)
+    };
+
+    Ok(HauntProgress {
+        origin_channel_id: original_channel_id.unwrap_or(voice_channel_id.get()),
+        teleport_count,
+        interval,
+        return_to_origin: return_to_origin.unwrap_or(true),
+        teleports_done: 0,
+        next_hop_at: Utc::now(),
+    })
+}
+
+/// Running haunt jobs, by enforcement ID
+#[derive(Debug, Default)]
+pub struct HauntJobs {
+    jobs: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl HauntJobs {
+    /// Start a job for every active haunt that doesn't have one running
+    pub async fn supervise(&self, backend: &Arc<dyn ModerationBackend>, data: &Data) {
+        let mut jobs = self.jobs.lock().await;
+        jobs.retain(|_, job| !job.is_finished());
+
+        let haunts: Vec<String> = data
+            .active_enforcements
+            .iter()
+            .filter(|entry| entry.haunt.is_some() && !jobs.contains_key(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for id in haunts {
+            info!("Starting haunt job for enforcement {id}");
+            let job = tokio::spawn(run(Arc::clone(backend), data.clone(), id.clone()));
+            jobs.insert(id, job);
+        }
+    }
+}
+
+/// The active haunt with this ID, if it still is one
+fn active_haunt(data: &Data, enforcement_id: &str) -> Option<(PendingEnforcement, HauntProgress)> {
+    let enforcement = data
+        .active_enforcements
+        .get(enforcement_id)
+        .map(|entry| entry.value().clone())?;
+    let progress = enforcement.haunt.clone()?;
+    Some((enforcement, progress))
+}
+
+/// Carry out a haunt until it's finished, gives up or is no longer active
+pub async fn run(backend: Arc<dyn ModerationBackend>, data: Data, enforcement_id: String) {
+    while let Some((_, progress)) = active_haunt(&data, &enforcement_id) {
+        let delay = (progress.next_hop_at - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(delay).await;
+
+        // Cancellation aborts the job, but anything else may have moved the
+        // enforcement on while it slept
+        let Some((mut enforcement, mut progress)) = active_haunt(&data, &enforcement_id) else {
+            break;
+        };
+        let guild_id = GuildId::new(enforcement.guild_id);
+        let user_id = UserId::new(enforcement.user_id);
+
+        let result = if progress.teleports_finished() {
+            if progress.return_to_origin {
+                return_home(backend.as_ref(), guild_id, user_id, &progress).await
+            } else {
+                Ok(())
+            }
+        } else {
+            hop(backend.as_ref(), guild_id, user_id, &progress).await
+        };
+
+        match result {
+            Ok(()) if progress.teleports_finished() => {
+                finish(&data, enforcement);
+                break;
+            }
+            Ok(()) => {
+                progress.teleports_done += 1;
+                #[allow(clippy::cast_possible_wrap)]
+                let interval = chrono::Duration::seconds(progress.interval as i64);
+                progress.next_hop_at = Utc::now() + interval;
+                enforcement.attempts = 0;
+                enforcement.last_error = None;
+                enforcement.haunt = Some(progress);
+                data.store_enforcement(enforcement);
+            }
+            Err(HopError::Fled) => {
+                info!("User {user_id} left voice, ending haunt {enforcement_id}");
+                finish(&data, enforcement);
+                break;
+            }
+            Err(HopError::Moderation(e)) => {
+                // Retried on the next loop, unless the haunt was given up on
+                record_failure(backend.as_ref(), &data, enforcement, Attempt::Hop, e).await;
+            }
+        }
+    }
+}
+
+/// Mark a haunt completed
+fn finish(data: &Data, mut enforcement: PendingEnforcement) {
+    enforcement.state = EnforcementState::Completed;
+    enforcement.attempts = 0;
+    enforcement.last_error = None;
+    let (id, user_id, guild_id) = (
+        enforcement.id.clone(),
+        enforcement.user_id,
+        enforcement.guild_id,
+    );
+    data.store_enforcement(enforcement);
+
+    info!(
+        target: crate::COMMAND_TARGET,
+        enforcement_id = %id,
+        user_id = %user_id,
+        guild_id = %guild_id,
+        event = "haunt_completed",
+        "Voice channel haunt completed"
+    );
+}
+
+/// Why a teleport didn't happen
+enum HopError {
+    /// The user isn't in voice any more
+    Fled,
+    Moderation(ModerationError),
+}
+
+impl From<ModerationError> for HopError {
+    fn from(error: ModerationError) -> Self {
+        Self::Moderation(error)
+    }
+}
+
+/// Teleport the user to a random voice channel
+async fn hop(
+    backend: &dyn ModerationBackend,
+    guild_id: GuildId,
+    user_id: UserId,
+    progress: &HauntProgress,
+) -> Result<(), HopError> {
+    let Some(current_channel) = backend.voice_channel(guild_id, user_id).await else {
+        return Err(HopError::Fled);
+    };
+    let voice_channels = backend.voice_channels(guild_id).await;
+    if voice_channels.is_empty() {
+        return Err(ModerationError::Permanent(
+            format!("No voice channels found in guild {guild_id} for haunting").into(),
+        )
+        .into());
+    }
+
+    // Pick a random channel (different from current on first teleport)
+    let random_channel = select_random_voice_channel(
+        &voice_channels,
+        progress.teleports_done == 0,
+        current_channel,
+    );
+    teleport_user(backend, guild_id, user_id, random_channel).await?;
+    Ok(())
+}
+
+/// Send the user back to the channel the haunt started from
+async fn return_home(
+    backend: &dyn ModerationBackend,
+    guild_id: GuildId,
+    user_id: UserId,
+    progress: &HauntProgress,
+) -> Result<(), HopError> {
+    if backend.voice_channel(guild_id, user_id).await.is_none() {
+        return Err(HopError::Fled);
+    }
+    let origin = ChannelId::new(progress.origin_channel_id);
+    teleport_user(backend, guild_id, user_id, origin).await?;
+    Ok(())
+}
+
+/// Stop a haunt midway and send the user back to the channel it started from
+///
+/// The enforcement is marked cancelled before the user is moved, so the haunt
+/// can't be picked up again.
+///
+/// # Errors
+/// Returns an error if the user couldn't be moved back.
+pub async fn cancel(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    enforcement_id: &str,
+) -> Result<(), Error> {
+    // Hold the jobs so the haunt can't be restarted until it's marked cancelled
+    let mut jobs = data.haunts.jobs.lock().await;
+    if let Some(job) = jobs.remove(enforcement_id) {
+        job.abort();
+        // Wait for the job to stop in case it's in the middle of a teleport
+        let _ = job.await;
+    }
+
+    let Some((mut enforcement, progress)) = active_haunt(data, enforcement_id) else {
+        return Ok(());
+    };
+    enforcement.state = EnforcementState::Cancelled;
+    enforcement.executed = true; // For backward compatibility
+    let guild_id = GuildId::new(enforcement.guild_id);
+    let user_id = UserId::new(enforcement.user_id);
+    data.store_enforcement(enforcement);
+    drop(jobs);
+
+    info!(
+        "Cancelled haunt {enforcement_id} after {} teleports",
+        progress.teleports_done
+    );
+
+    let origin = ChannelId::new(progress.origin_channel_id);
+    match backend.voice_channel(guild_id, user_id).await {
+        Some(current) if current != origin => {
+            teleport_user(backend, guild_id, user_id, origin).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Select a random voice channel, optionally ensuring it's different from the current one
+fn select_random_voice_channel(
+    voice_channels: &[ChannelId],
+    must_be_different: bool,
+    current_channel: ChannelId,
+) -> ChannelId {
+    let rng = &mut rand::thread_rng();
+    if !must_be_different || voice_channels.len() <= 1 {
+        // If we don't need a different channel or there's only one channel, just pick randomly
+        let idx = rand::Rng::gen_range(rng, 0..voice_channels.len());
+        return voice_channels[idx];
+    }
+
+    // We need a different channel and have multiple options
+    loop {
+        let idx = rand::Rng::gen_range(rng, 0..voice_channels.len());
+        let channel = voice_channels[idx];
+
+        if channel != current_channel {
+            return channel;
+        }
+    }
+}
+
+/// Teleport a user to a specific voice channel
+async fn teleport_user(
+    backend: &dyn ModerationBackend,
+    guild_id: GuildId,
+    user_id: UserId,
+    channel_id: ChannelId,
+) -> Result<(), ModerationError> {
+    info!("Teleporting user {user_id} to channel {channel_id}");
+    backend.move_to_channel(guild_id, user_id, channel_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moderation::{ModerationCall, RecordingBackend};
+    use std::time::Duration;
+
+    const USER: u64 = 12345;
+    const GUILD: u64 = 11111;
+
+    /// An active haunt that has made `teleports_done` of its teleports
+    fn haunt(teleport_count: u64, teleports_done: u64) -> PendingEnforcement {
+        let now = Utc::now();
+        PendingEnforcement {
+            haunt: Some(HauntProgress {
+                origin_channel_id: 1,
+                teleport_count,
+                interval: 10,
+                return_to_origin: true,
+                teleports_done,
+                next_hop_at: now,
+            }),
+            ..PendingEnforcement::test(
+                "haunt",
+                EnforcementAction::VoiceChannelHaunt {
+                    teleport_count: Some(teleport_count),
+                    interval: Some(10),
+                    return_to_origin: Some(true),
+                    original_channel_id: None,
+                },
+                EnforcementState::Active,
+            )
+        }
+    }
+
+    fn moves(backend: &RecordingBackend) -> Vec<u64> {
+        backend
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                ModerationCall::Move { channel_id, .. } => Some(channel_id.get()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_haunt_runs_to_completion() {
+        let data = Data::new();
+        let backend = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        backend.join_voice(USER, 1);
+        data.store_enforcement(haunt(2, 0));
+
+        run(backend.clone(), data.clone(), "haunt".to_string()).await;
+
+        let moves = moves(&backend);
+        assert_eq!(moves.len(), 3);
+        assert_ne!(moves[0], 1);
+        assert_eq!(moves[2], 1);
+        let completed = data.completed_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(completed.state, EnforcementState::Completed);
+        assert_eq!(completed.haunt.unwrap().teleports_done, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_haunt_resumes_where_it_left_off() {
+        let data = Data::new();
+        let recording = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        recording.join_voice(USER, 2);
+        // Two of three teleports were made before the restart
+        data.store_enforcement(haunt(3, 2));
+
+        let backend: Arc<dyn ModerationBackend> = recording.clone();
+        data.haunts.supervise(&backend, &data).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+
+        // Only the last teleport is made, then the trip home
+        let moves = moves(&recording);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[1], 1);
+        let completed = data.completed_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(completed.state, EnforcementState::Completed);
+        assert_eq!(completed.haunt.unwrap().teleports_done, 3);
+        assert_eq!(
+            backend
+                .voice_channel(GuildId::new(GUILD), UserId::new(USER))
+                .await,
+            Some(ChannelId::new(1))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelling_returns_user_to_origin() {
+        let data = Data::new();
+        let recording = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        recording.join_voice(USER, 1);
+        data.store_enforcement(haunt(5, 0));
+
+        let backend: Arc<dyn ModerationBackend> = recording.clone();
+        data.haunts.supervise(&backend, &data).await;
+        tokio::time::sleep(Duration::from_secs(15)).await;
+        assert_eq!(moves(&recording).len(), 2);
+
+        cancel(backend.as_ref(), &data, "haunt").await.unwrap();
+        assert_eq!(
+            backend
+                .voice_channel(GuildId::new(GUILD), UserId::new(USER))
+                .await,
+            Some(ChannelId::new(1))
+        );
+        let moved = moves(&recording).len();
+        let cancelled = data.completed_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(cancelled.state, EnforcementState::Cancelled);
+        assert_eq!(cancelled.haunt.unwrap().teleports_done, 2);
+
+        // The job is gone and isn't started again
+        data.haunts.supervise(&backend, &data).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(moves(&recording).len(), moved);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_haunt_ends_when_user_leaves_voice() {
+        let data = Data::new();
+        let backend = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        data.store_enforcement(haunt(3, 1));
+
+        run(backend.clone(), data.clone(), "haunt".to_string()).await;
+
+        assert!(backend.calls().is_empty());
+        assert_eq!(
+            data.completed_enforcements.get("haunt").unwrap().state,
+            EnforcementState::Completed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_trip_home_after_user_leaves_voice() {
+        let data = Data::new();
+        let backend = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        data.store_enforcement(haunt(2, 2));
+
+        run(backend.clone(), data.clone(), "haunt".to_string()).await;
+
+        assert!(backend.calls().is_empty());
+        assert_eq!(
+            data.completed_enforcements.get("haunt").unwrap().state,
+            EnforcementState::Completed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_hop_is_retried() {
+        let data = Data::new();
+        let backend = Arc::new(RecordingBackend::with_voice_channels(&[1, 2, 3]));
+        backend.join_voice(USER, 1);
+        backend.fail_next(ModerationError::Transient("rate limited".into()));
+        data.store_enforcement(haunt(1, 0));
+
+        run(backend.clone(), data.clone(), "haunt".to_string()).await;
+
+        // One failed hop, the retry, then the return home
+        assert_eq!(moves(&backend).len(), 3);
+        let completed = data.completed_enforcements.get("haunt").unwrap().clone();
+        assert_eq!(completed.state, EnforcementState::Completed);
+        assert_eq!(completed.attempts, 0);
+    }
+}
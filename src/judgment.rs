@@ -0,0 +1,172 @@
+//! Reproducible chaos for the daemon's judgments
+//!
+//! Every summon draws a fresh seed and makes all of its random choices (the
+//! chaos roll, then the punishment for a repeat voice offender) from an RNG
+//! seeded with it. The seed and the numbers that went into the decision are
+//! stored on the warning as a [`Judgment`], so the decision can be replayed
+//! later and explained.
+
+use crate::data::EnforcementAction;
+use crate::punishment::PunishmentTable;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// The random choices and numbers behind one summon's decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Judgment {
+    /// Seed for the RNG all of the summon's random choices were drawn from
+    pub seed: u64,
+    /// The guild's chaos factor at the time
+    pub chaos_factor: f32,
+    /// Infraction category the warning was scored in
+    pub category: String,
+    /// Category score before the chaos roll
+    pub base_score: f64,
+    /// Random amount added by the chaos roll
+    pub roll: f64,
+    /// Score the decision was made on
+    pub adjusted_score: f64,
+    /// Threshold the adjusted score was compared against
+    pub threshold: f64,
+    /// Whether the punishment was drawn from the RNG after the roll
+    pub drew_punishment: bool,
+    /// Name of the policy rule that decided, if the guild has a policy
+    #[serde(default)]
+    pub rule: Option<String>,
+    /// The punishment table the punishment was drawn from, if one was drawn
+    #[serde(default)]
+    pub table: Option<PunishmentTable>,
+}
+
+/// The outcome of rerunning a [`Judgment`] from its seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub roll: f64,
+    pub adjusted_score: f64,
+    pub enforced: bool,
+    /// The punishment drawn, if the original judgment drew one
+    pub punishment: Option<EnforcementAction>,
+}
+
+impl Replay {
+    /// Whether the replay reached the same numbers as the recorded judgment
+    #[must_use]
+    pub fn matches(&self, judgment: &Judgment) -> bool {
+        (self.roll - judgment.roll).abs() < f64::EPSILON
+            && (self.adjusted_score - judgment.adjusted_score).abs() < f64::EPSILON
+    }
+}
+
+/// Draw a seed for a new judgment
+#[must_use]
+pub fn new_seed() -> u64 {
+    rand::thread_rng().r#gen()
+}
+
+/// The RNG for a judgment's random choices
+///
+/// `StdRng` is only guaranteed to give the same stream for the same version of
+/// `rand`, so judgments recorded before a `rand` upgrade may replay differently.
+#[must_use]
+pub fn rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// Roll the random amount added to a score, up to the chaos factor
+pub fn roll_chaos(rng: &mut impl Rng, chaos_factor: f32) -> f64 {
+    if chaos_factor <= 0.0 {
+        return 0.0;
+    }
+    rng.gen_range(0.0..f64::from(chaos_factor))
+}
+
+/// Chance that the chaos roll lifts `base_score` above `threshold`
+///
+/// The roll is uniform up to the chaos factor, so this is the share of that
+/// range that ends above the threshold.
+#[must_use]
+pub fn enforcement_probability(base_score: f64, chaos_factor: f32, threshold: f64) -> f64 {
+    let chaos = f64::from(chaos_factor);
+    if chaos <= 0.0 {
+        return if base_score > threshold { 1.0 } else { 0.0 };
+    }
+    ((base_score + chaos - threshold) / chaos).clamp(0.0, 1.0)
+}
+
+/// Rerun a judgment's random choices from its seed
+#[must_use]
+pub fn replay(judgment: &Judgment) -> Replay {
+    let mut rng = rng(judgment.seed);
+    let roll = roll_chaos(&mut rng, judgment.chaos_factor);
+    let adjusted_score = judgment.base_score + roll;
+    let punishment = judgment.drew_punishment.then(|| {
+        judgment
+            .table
+            .clone()
+            .unwrap_or_default()
+            .draw(&mut rng, judgment.chaos_factor)
+    });
+
+    Replay {
+        roll,
+        adjusted_score,
+        enforced: adjusted_score > judgment.threshold,
+        punishment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_reproduces_the_judgment() {
+        let seed = new_seed();
+        let mut rng = rng(seed);
+        let roll = roll_chaos(&mut rng, 0.8);
+        let table = PunishmentTable::default();
+        let punishment = table.draw(&mut rng, 0.8);
+        let judgment = Judgment {
+            seed,
+            chaos_factor: 0.8,
+            category: "voice".to_string(),
+            base_score: 1.5,
+            roll,
+            adjusted_score: 1.5 + roll,
+            threshold: 2.0,
+            drew_punishment: true,
+            rule: None,
+            table: Some(table),
+        };
+
+        let replay = replay(&judgment);
+        assert!(replay.matches(&judgment));
+        assert_eq!(replay.enforced, judgment.adjusted_score > 2.0);
+        assert_eq!(replay.punishment, Some(punishment));
+
+        // A different seed is a different judgment
+        let other = Judgment {
+            seed: seed.wrapping_add(1),
+            ..judgment.clone()
+        };
+        assert!(!super::replay(&other).matches(&judgment));
+    }
+
+    #[test]
+    fn test_enforcement_probability() {
+        assert!((enforcement_probability(2.5, 1.0, 3.0) - 0.5).abs() < 1e-9);
+        assert!((enforcement_probability(1.0, 1.0, 3.0)).abs() < f64::EPSILON);
+        assert!((enforcement_probability(3.5, 1.0, 3.0) - 1.0).abs() < f64::EPSILON);
+
+        // Without chaos the threshold alone decides
+        assert!((enforcement_probability(3.5, 0.0, 3.0) - 1.0).abs() < f64::EPSILON);
+        assert!((enforcement_probability(3.0, 0.0, 3.0)).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_no_chaos_rolls_nothing() {
+        let mut rng = rng(42);
+        assert!(roll_chaos(&mut rng, 0.0).abs() < f64::EPSILON);
+    }
+}
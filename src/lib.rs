@@ -1,10 +1,23 @@
+pub mod category;
 pub mod commands;
 pub mod daemon_response;
 pub mod data;
 pub mod enforcement;
+pub mod escalation;
 pub mod handlers;
+pub mod haunt;
+pub mod judgment;
 pub mod logging;
+pub mod moderation;
+pub mod persistence;
+pub mod policy;
+pub mod punishment;
+pub mod retention;
+pub mod schedule;
+pub mod scoring;
 pub mod status;
+pub mod storage;
+pub mod transfer;
 
 pub use data::{Data, DataInner};
 pub use data::{EnforcementAction, EnforcementState, PendingEnforcement};
@@ -1,10 +1,23 @@
+mod category;
 mod commands;
 mod daemon_response;
 mod data;
 mod enforcement;
+mod escalation;
 mod handlers;
+mod haunt;
+mod judgment;
 mod logging;
+mod moderation;
+mod persistence;
+mod policy;
+mod punishment;
+mod retention;
+mod schedule;
+mod scoring;
 mod status;
+mod storage;
+mod transfer;
 
 use crate::data::Data;
 use std::env;
@@ -40,32 +53,44 @@ async fn async_main() -> Result<(), Error> {
 
     // Load the bot's data from file
     info!("Loading bot data...");
-    let mut data = Data::load().await;
+    let storage = storage::from_env()?;
+    info!("Using {} storage backend", storage.name());
+    let policy = storage::CorruptionPolicy::from_env()?;
+    let data = Data::load(storage, policy).await?;
 
     // Create enforcement channel first
     info!("Creating enforcement channel...");
     let enforcement_tx = enforcement::create_enforcement_channel();
-
-    // Set the enforcement sender in data BEFORE wrapping in Arc
     data.set_enforcement_tx(enforcement_tx);
 
+    // Changes are written in the background from here on
+    let autosave = persistence::Autosave::start(data.clone(), persistence::debounce_from_env());
+
     // Now wrap the data in Arc for thread-safe sharing
     // let data = Arc::new(data);
     let data_cloned = data.clone();
 
-    // Start the enforcement task with the receiver
-    if let Some(rx) = enforcement::take_enforcement_receiver() {
+    // Start the enforcement task with the receiver. Read-only data can't record
+    // what the task does, so it takes no actions until the data is repaired.
+    if data.read_only {
+        error!("Data is read-only, so no enforcements will be carried out or lifted");
+    } else if let Some(rx) = enforcement::take_enforcement_receiver() {
         info!("Starting enforcement task...");
         enforcement::start_task_with_receiver(
             serenity::Http::new(&token).into(),
             data_cloned.clone(),
             rx,
-            60, // Check interval in seconds
+            enforcement::SAFETY_SCAN_SECONDS,
         );
     } else {
         error!("Failed to get enforcement receiver");
     }
 
+    // Drop records past each guild's retention period
+    if !data.read_only {
+        retention::start_sweep_task(data.clone(), retention::sweep_interval_from_env());
+    }
+
     // Configure the Poise framework
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -75,11 +100,25 @@ async fn async_main() -> Result<(), Error> {
                 commands::warn(),
                 commands::appease(),
                 commands::summon_daemon(),
+                commands::daemon_preview(),
                 commands::daemon_altar(),
                 commands::chaos_ritual(),
+                commands::scoring_ritual(),
+                commands::judgment_ritual(),
+                commands::escalation_ritual(),
+                commands::punishment_ritual(),
+                commands::category_ritual(),
                 commands::judgment_history(),
                 commands::daemon_status(),
+                commands::daemon_export(),
+                commands::daemon_import(),
+                commands::daemon_retention(),
+                commands::daemon_erase(),
+                commands::daemon_replay(),
+                commands::daemon_policy(),
+                commands::policy_explain(),
             ],
+            command_check: Some(|ctx| Box::pin(commands::check_writable(ctx))),
             pre_command: |ctx| {
                 Box::pin(async move {
                     // Log the start of command execution
@@ -146,11 +185,9 @@ async fn async_main() -> Result<(), Error> {
         }
     }
 
-    // Save data before shutting down
+    // Flush anything the autosave task hasn't written yet
     info!("Saving bot data...");
-    if let Err(err) = data.save().await {
-        eprintln!("Error saving bot data: {err}");
-    }
+    autosave.shutdown().await;
 
     info!("Bot shutdown complete");
     Ok(())
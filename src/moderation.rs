@@ -0,0 +1,524 @@
+//! What the enforcement task does to users on Discord
+//!
+//! The [`ModerationBackend`] trait covers every moderation call the daemon
+//! makes: timeouts, bans, kicks, voice mutes and deafens, moving users between
+//! voice channels and disconnecting them. [`SerenityBackend`] makes the calls
+//! over the Discord API, while the tests run the enforcement lifecycle against
+//! a `RecordingBackend` that only writes down what it was asked to do.
+//!
+//! Failed calls come back as a [`ModerationError`] saying whether the call is
+//! worth retrying.
+
+use crate::Error;
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{self as serenity, ChannelId, GuildId, Http, UserId};
+use serenity::all::CacheHttp;
+use serenity::builder::{CreateMessage, EditMember};
+use serenity::http::HttpError;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// A failed moderation call
+#[derive(Debug)]
+pub enum ModerationError {
+    /// Rate limits, Discord server errors and dropped connections
+    Transient(Error),
+    /// Anything that will fail the same way next time, like missing permissions
+    Permanent(Error),
+}
+
+impl ModerationError {
+    /// Whether trying the call again later might succeed
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Transient(_))
+    }
+}
+
+impl std::fmt::Display for ModerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transient(e) | Self::Permanent(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ModerationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transient(e) | Self::Permanent(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<serenity::Error> for ModerationError {
+    fn from(error: serenity::Error) -> Self {
+        let transient = match &error {
+            serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+                response.status_code.as_u16() == 429 || response.status_code.is_server_error()
+            }
+            serenity::Error::Http(HttpError::Request(_)) => true,
+            _ => false,
+        };
+        if transient {
+            Self::Transient(error.into())
+        } else {
+            Self::Permanent(error.into())
+        }
+    }
+}
+
+/// Moderation actions against members of a guild
+#[serenity::async_trait]
+pub trait ModerationBackend: Send + Sync {
+    /// Time a member out until `until`
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or timed out.
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> Result<(), ModerationError>;
+
+    /// Ban a user from the guild
+    ///
+    /// # Errors
+    /// Returns an error if the user can't be banned.
+    #[allow(dead_code)] // Bans and kicks aren't carried out yet
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        reason: &str,
+    ) -> Result<(), ModerationError>;
+
+    /// Lift a user's ban
+    ///
+    /// # Errors
+    /// Returns an error if the ban can't be lifted.
+    async fn unban(&self, guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError>;
+
+    /// Kick a member from the guild
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or kicked.
+    #[allow(dead_code)] // Bans and kicks aren't carried out yet
+    async fn kick(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        reason: &str,
+    ) -> Result<(), ModerationError>;
+
+    /// Server mute or unmute a member in voice
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or edited.
+    async fn set_voice_mute(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        muted: bool,
+    ) -> Result<(), ModerationError>;
+
+    /// Server deafen or undeafen a member in voice
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or edited.
+    async fn set_voice_deafen(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        deafened: bool,
+    ) -> Result<(), ModerationError>;
+
+    /// Move a member to another voice channel
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or moved.
+    async fn move_to_channel(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), ModerationError>;
+
+    /// Disconnect a member from voice
+    ///
+    /// # Errors
+    /// Returns an error if the member can't be found or disconnected.
+    async fn disconnect(&self, guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError>;
+
+    /// The voice channel a member is in, if any
+    async fn voice_channel(&self, guild_id: GuildId, user_id: UserId) -> Option<ChannelId>;
+
+    /// Every voice channel in the guild
+    async fn voice_channels(&self, guild_id: GuildId) -> Vec<ChannelId>;
+
+    /// Post a message to a channel
+    ///
+    /// # Errors
+    /// Returns an error if the message can't be sent.
+    async fn post_message(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<(), ModerationError>;
+}
+
+/// Moderation over the Discord API
+#[derive(Clone)]
+pub struct SerenityBackend {
+    http: Arc<Http>,
+}
+
+impl SerenityBackend {
+    #[must_use]
+    pub fn new(http: Arc<Http>) -> Self {
+        Self { http }
+    }
+
+    async fn member(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<serenity::Member, ModerationError> {
+        let guild = guild_id.to_partial_guild(&self.http).await?;
+        Ok(guild.member(&self.http, user_id).await?)
+    }
+}
+
+#[serenity::async_trait]
+impl ModerationBackend for SerenityBackend {
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> Result<(), ModerationError> {
+        let mut member = self.member(guild_id, user_id).await?;
+        member
+            .disable_communication_until_datetime(&self.http, until.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        reason: &str,
+    ) -> Result<(), ModerationError> {
+        guild_id
+            .ban_with_reason(&self.http, user_id, 7, reason)
+            .await?;
+        Ok(())
+    }
+
+    async fn unban(&self, guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError> {
+        guild_id.unban(&self.http, user_id).await?;
+        Ok(())
+    }
+
+    async fn kick(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        reason: &str,
+    ) -> Result<(), ModerationError> {
+        let member = self.member(guild_id, user_id).await?;
+        member.kick_with_reason(&self.http, reason).await?;
+        Ok(())
+    }
+
+    async fn set_voice_mute(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        muted: bool,
+    ) -> Result<(), ModerationError> {
+        let mut member = self.member(guild_id, user_id).await?;
+        member
+            .edit(&self.http, EditMember::new().mute(muted))
+            .await?;
+        Ok(())
+    }
+
+    async fn set_voice_deafen(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        deafened: bool,
+    ) -> Result<(), ModerationError> {
+        let mut member = self.member(guild_id, user_id).await?;
+        member
+            .edit(&self.http, EditMember::new().deafen(deafened))
+            .await?;
+        Ok(())
+    }
+
+    async fn move_to_channel(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), ModerationError> {
+        let mut member = self.member(guild_id, user_id).await?;
+        member
+            .edit(&self.http, EditMember::new().voice_channel(channel_id))
+            .await?;
+        Ok(())
+    }
+
+    async fn disconnect(&self, guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError> {
+        let member = self.member(guild_id, user_id).await?;
+        member.disconnect_from_voice(&self.http).await?;
+        Ok(())
+    }
+
+    async fn voice_channel(&self, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+        let voice_channels = self.voice_channels(guild_id).await;
+        info!(
+            "Number of voice channels in guild {guild_id}: {}",
+            voice_channels.len()
+        );
+        for channel_id in voice_channels {
+            if let Ok(channel) = channel_id.to_channel(&self.http).await {
+                let channel_name = channel_id
+                    .name(&self.http)
+                    .await
+                    .unwrap_or("Unknown".to_string());
+                info!("Checking channel {channel_name}");
+                let guild_channel = channel.guild()?;
+                let res = self.http.cache().is_some_and(|cache| {
+                    guild_channel
+                        .members(cache)
+                        .is_ok_and(|members| members.iter().any(|member| member.user.id == user_id))
+                });
+                if res {
+                    // User found in this channel
+                    info!("User {user_id} is in voice channel {channel_id}");
+                    return Some(channel_id);
+                }
+                // User not found in this channel, continue searching
+            }
+        }
+        None
+    }
+
+    async fn voice_channels(&self, guild_id: GuildId) -> Vec<ChannelId> {
+        let channels = match guild_id.channels(&self.http).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                error!("Failed to get channels for guild {guild_id}: {e}");
+                return Vec::new();
+            }
+        };
+
+        channels
+            .iter()
+            .filter(|(_, channel)| channel.kind == serenity::ChannelType::Voice)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    async fn post_message(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<(), ModerationError> {
+        channel_id
+            .send_message(&self.http, CreateMessage::new().content(content))
+            .await?;
+        Ok(())
+    }
+}
+
+/// A moderation call made to a [`RecordingBackend`]
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationCall {
+    Timeout {
+        user_id: UserId,
+        until: DateTime<Utc>,
+    },
+    Ban {
+        user_id: UserId,
+    },
+    Unban {
+        user_id: UserId,
+    },
+    Kick {
+        user_id: UserId,
+    },
+    VoiceMute {
+        user_id: UserId,
+        muted: bool,
+    },
+    VoiceDeafen {
+        user_id: UserId,
+        deafened: bool,
+    },
+    Move {
+        user_id: UserId,
+        channel_id: ChannelId,
+    },
+    Disconnect {
+        user_id: UserId,
+    },
+    Message {
+        channel_id: ChannelId,
+        content: String,
+    },
+}
+
+/// In-memory guild that records every call made to it, for tests
+///
+/// Members are in voice wherever [`RecordingBackend::join_voice`] put them,
+/// and moving or disconnecting them updates that. Calls fail with whatever
+/// [`RecordingBackend::fail_next`] queued up.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingBackend {
+    calls: std::sync::Mutex<Vec<ModerationCall>>,
+    failures: std::sync::Mutex<std::collections::VecDeque<ModerationError>>,
+    voice_channels: std::sync::Mutex<Vec<ChannelId>>,
+    voice_states: dashmap::DashMap<UserId, ChannelId>,
+}
+
+#[cfg(test)]
+impl RecordingBackend {
+    /// A guild with these voice channels
+    #[must_use]
+    pub fn with_voice_channels(channels: &[u64]) -> Self {
+        let backend = Self::default();
+        *backend.voice_channels.lock().unwrap() =
+            channels.iter().copied().map(ChannelId::new).collect();
+        backend
+    }
+
+    /// Put a member in a voice channel
+    pub fn join_voice(&self, user_id: u64, channel_id: u64) {
+        self.voice_states
+            .insert(UserId::new(user_id), ChannelId::new(channel_id));
+    }
+
+    /// Every call made so far, in order
+    #[must_use]
+    pub fn calls(&self) -> Vec<ModerationCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Make the next moderation call fail with `error`
+    ///
+    /// Queued failures are used up one call at a time, in order.
+    pub fn fail_next(&self, error: ModerationError) {
+        self.failures.lock().unwrap().push_back(error);
+    }
+
+    fn record(&self, call: ModerationCall) -> Result<(), ModerationError> {
+        self.calls.lock().unwrap().push(call);
+        self.failures
+            .lock()
+            .unwrap()
+            .pop_front()
+            .map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+#[serenity::async_trait]
+impl ModerationBackend for RecordingBackend {
+    async fn timeout(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Timeout { user_id, until })
+    }
+
+    async fn ban(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        _reason: &str,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Ban { user_id })
+    }
+
+    async fn unban(&self, _guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Unban { user_id })
+    }
+
+    async fn kick(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        _reason: &str,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Kick { user_id })?;
+        self.voice_states.remove(&user_id);
+        Ok(())
+    }
+
+    async fn set_voice_mute(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        muted: bool,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::VoiceMute { user_id, muted })
+    }
+
+    async fn set_voice_deafen(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        deafened: bool,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::VoiceDeafen { user_id, deafened })
+    }
+
+    async fn move_to_channel(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Move {
+            user_id,
+            channel_id,
+        })?;
+        self.voice_states.insert(user_id, channel_id);
+        Ok(())
+    }
+
+    async fn disconnect(&self, _guild_id: GuildId, user_id: UserId) -> Result<(), ModerationError> {
+        self.record(ModerationCall::Disconnect { user_id })?;
+        self.voice_states.remove(&user_id);
+        Ok(())
+    }
+
+    async fn voice_channel(&self, _guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+        self.voice_states.get(&user_id).map(|entry| *entry.value())
+    }
+
+    async fn voice_channels(&self, _guild_id: GuildId) -> Vec<ChannelId> {
+        self.voice_channels.lock().unwrap().clone()
+    }
+
+    async fn post_message(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<(), ModerationError> {
+        self.calls.lock().unwrap().push(ModerationCall::Message {
+            channel_id,
+            content: content.to_string(),
+        });
+        Ok(())
+    }
+}
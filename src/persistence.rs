@@ -0,0 +1,166 @@
+//! Background task that writes changed records to storage
+//!
+//! Commands and the enforcement task only mark records as changed. This task
+//! waits for the first change, lets further changes pile up for the debounce
+//! interval and then writes them all in one save, so disk I/O never sits in
+//! command latency and there is only ever one writer. Stopping the task flushes
+//! whatever is still unsaved.
+
+use crate::Data;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{error, info};
+
+/// Default number of seconds changes are collected before they are saved
+pub const DEFAULT_AUTOSAVE_SECONDS: u64 = 5;
+
+/// Handle to the running autosave task
+pub struct Autosave {
+    shutdown_tx: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Autosave {
+    /// Start saving changes made to `data` in the background
+    #[must_use]
+    pub fn start(data: Data, debounce: Duration) -> Self {
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let handle = tokio::spawn(autosave_task(data, debounce, shutdown_rx));
+        Self {
+            shutdown_tx,
+            handle,
+        }
+    }
+
+    /// Stop the task after writing every change that hasn't been saved yet
+    pub async fn shutdown(self) {
+        let _ = self.shutdown_tx.send(());
+        if let Err(e) = self.handle.await {
+            error!("Autosave task failed: {e}");
+        }
+    }
+}
+
+/// Read the debounce interval from `DATA_AUTOSAVE_SECONDS`
+#[must_use]
+pub fn debounce_from_env() -> Duration {
+    let seconds = std::env::var("DATA_AUTOSAVE_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_AUTOSAVE_SECONDS);
+    Duration::from_secs(seconds)
+}
+
+async fn autosave_task(data: Data, debounce: Duration, mut shutdown_rx: oneshot::Receiver<()>) {
+    info!(
+        "Starting autosave task with {}s debounce",
+        debounce.as_secs()
+    );
+
+    loop {
+        // Wait for something to change
+        tokio::select! {
+            () = data.dirty.changed.notified() => {}
+            _ = &mut shutdown_rx => break,
+        }
+
+        // Give related changes a moment to arrive so they are written together
+        tokio::select! {
+            () = tokio::time::sleep(debounce) => {}
+            _ = &mut shutdown_rx => break,
+        }
+
+        // A failed save marks its records dirty again, which wakes us for a retry
+        if let Err(e) = data.save().await {
+            error!("Failed to save data: {e}");
+        }
+    }
+
+    info!("Flushing unsaved data before shutdown");
+    if let Err(e) = data.save().await {
+        error!("Failed to save data on shutdown: {e}");
+    }
+    info!("Autosave task shut down");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use crate::data::GuildConfig;
+    use crate::storage::{ChangeSet, CorruptionPolicy, Snapshot, Storage};
+    use poise::serenity_prelude as serenity;
+    use std::sync::{Arc, Mutex};
+
+    /// Storage that records every batch it is asked to write
+    #[derive(Default)]
+    struct RecordingStorage {
+        batches: Mutex<Vec<ChangeSet>>,
+    }
+
+    #[serenity::async_trait]
+    impl Storage for RecordingStorage {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        async fn load(&self) -> Result<Snapshot, Error> {
+            Ok(Snapshot::default())
+        }
+
+        async fn apply(&self, changes: ChangeSet) -> Result<(), Error> {
+            self.batches.lock().unwrap().push(changes);
+            Ok(())
+        }
+
+        async fn repair(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_changes_are_coalesced_and_flushed() {
+        let storage = Arc::new(RecordingStorage::default());
+        let data = Data::load(storage.clone(), CorruptionPolicy::Fail)
+            .await
+            .unwrap();
+        let autosave = Autosave::start(data.clone(), Duration::from_secs(5));
+
+        data.set_guild_config(GuildConfig::new(1));
+        data.set_guild_config(GuildConfig::new(2));
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(storage.batches.lock().unwrap().len(), 1);
+        assert_eq!(storage.batches.lock().unwrap()[0].guild_configs.len(), 2);
+
+        // Changes still waiting out the debounce are written on shutdown
+        data.set_guild_config(GuildConfig::new(3));
+        autosave.shutdown().await;
+        assert_eq!(storage.batches.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_changes_after_wiring_the_enforcement_task_are_saved() {
+        let storage = Arc::new(RecordingStorage::default());
+        let data = Data::load(storage.clone(), CorruptionPolicy::Fail)
+            .await
+            .unwrap();
+        let autosave = Autosave::start(data.clone(), Duration::from_secs(5));
+
+        // Setting the sender must not split the bot's handle from autosave's
+        let before = data.clone();
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        data.set_enforcement_tx(tx);
+        assert!(Arc::ptr_eq(&before.0, &data.0));
+        assert!(before.enforcement_tx.get().is_some());
+
+        data.set_guild_config(GuildConfig::new(1));
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        autosave.shutdown().await;
+
+        let batches = storage.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].guild_configs.len(), 1);
+        assert!(batches[0].removed_warnings.is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+//! Moderation policies written as data
+//!
+//! A guild can upload a YAML policy with `/daemon_policy`. When it has one,
+//! `/summon_daemon` evaluates the rules in order and the first rule whose
+//! conditions all hold decides the punishment, in place of the threshold and
+//! the daemon's own choices. If no rule matches the user is only warned.
+//!
+//! ```yaml
+//! rules:
+//!   - name: repeat voice offender
+//!     when:
+//!       category: voice
+//!       score_above: 3
+//!       enforced_within_hours: 24
+//!     then:
+//!       action: deafen 30m
+//!       notify: public
+//! ```
+
+use crate::data::{Data, EnforcementAction, NotificationMethod, UserWarningState};
+use crate::escalation;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Most rules a policy can have
+pub const MAX_RULES: usize = 50;
+
+/// A guild's moderation policy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub rules: Vec<PolicyRule>,
+}
+
+/// One rule: when every condition holds, the outcome applies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    pub name: String,
+    #[serde(default)]
+    pub when: Conditions,
+    pub then: Outcome,
+}
+
+/// Conditions of a rule; those left out always hold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Conditions {
+    /// Infraction category the user was summoned for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// The category score, after the chaos roll, is greater than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_above: Option<f64>,
+    /// The category score, after the chaos roll, is at most this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_at_most: Option<f64>,
+    /// The warning's severity is at least this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity_at_least: Option<f64>,
+    /// The user has at least this many warnings in the category, counting this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warnings_at_least: Option<usize>,
+    /// A punishment was carried out on the user within this many hours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enforced_within_hours: Option<u32>,
+    /// No punishment was carried out on the user within this many hours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_enforced_within_hours: Option<u32>,
+}
+
+/// What happens when a rule fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Outcome {
+    /// A punishment in the form escalation ladders use, e.g. `deafen 30m`, or `none`
+    pub action: String,
+    /// How the user is told, overriding the summon's notification method
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify: Option<PolicyNotify>,
+}
+
+/// Notification methods a policy can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyNotify {
+    Dm,
+    Public,
+}
+
+impl From<PolicyNotify> for NotificationMethod {
+    fn from(notify: PolicyNotify) -> Self {
+        match notify {
+            PolicyNotify::Dm => Self::DirectMessage,
+            PolicyNotify::Public => Self::PublicWithMention,
+        }
+    }
+}
+
+impl Outcome {
+    /// The punishment this outcome hands out, `None` for a warning only
+    #[must_use]
+    pub fn enforcement(&self) -> Option<EnforcementAction> {
+        if self.action.trim().eq_ignore_ascii_case("none") {
+            return None;
+        }
+        escalation::parse_action(&self.action).ok()
+    }
+}
+
+/// What the rules are evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub struct Facts {
+    pub category: String,
+    /// Category score after the chaos roll
+    pub score: f64,
+    pub severity: f64,
+    /// Warnings in the category, counting the current one
+    pub warnings: usize,
+    /// When a punishment was last carried out on the user
+    pub last_enforced: Option<DateTime<Utc>>,
+    pub now: DateTime<Utc>,
+}
+
+impl Facts {
+    /// Gather the facts about a user's latest warning
+    #[must_use]
+    pub fn gather(
+        data: &Data,
+        state: &UserWarningState,
+        category: &str,
+        score: f64,
+        severity: f64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let last_enforced = data
+            .user_enforcements(state.user_id, state.guild_id)
+            .into_iter()
+            .filter_map(|enforcement| enforcement.executed_at)
+            .max();
+
+        Self {
+            category: category.to_string(),
+            score,
+            severity,
+            warnings: state.for_category(category).warning_timestamps.len(),
+            last_enforced,
+            now,
+        }
+    }
+
+    fn enforced_within(&self, hours: u32) -> bool {
+        self.last_enforced
+            .is_some_and(|at| at >= self.now - Duration::hours(i64::from(hours)))
+    }
+}
+
+impl Conditions {
+    /// Describe each condition and whether it holds for `facts`
+    #[must_use]
+    pub fn check(&self, facts: &Facts) -> Vec<(String, bool)> {
+        let mut checks = Vec::new();
+        if let Some(category) = &self.category {
+            checks.push((
+                format!("category is {category}"),
+                facts.category == *category,
+            ));
+        }
+        if let Some(score) = self.score_above {
+            checks.push((format!("score above {score}"), facts.score > score));
+        }
+        if let Some(score) = self.score_at_most {
+            checks.push((format!("score at most {score}"), facts.score <= score));
+        }
+        if let Some(severity) = self.severity_at_least {
+            checks.push((
+                format!("severity at least {severity}"),
+                facts.severity >= severity,
+            ));
+        }
+        if let Some(count) = self.warnings_at_least {
+            checks.push((
+                format!("at least {count} warnings"),
+                facts.warnings >= count,
+            ));
+        }
+        if let Some(hours) = self.enforced_within_hours {
+            checks.push((
+                format!("punished within {hours}h"),
+                facts.enforced_within(hours),
+            ));
+        }
+        if let Some(hours) = self.not_enforced_within_hours {
+            checks.push((
+                format!("not punished within {hours}h"),
+                !facts.enforced_within(hours),
+            ));
+        }
+        checks
+    }
+
+    /// Whether every condition holds for `facts`
+    #[must_use]
+    pub fn matches(&self, facts: &Facts) -> bool {
+        self.check(facts).iter().all(|(_, holds)| *holds)
+    }
+}
+
+impl Policy {
+    /// Read and validate a policy file
+    ///
+    /// # Errors
+    /// Returns every problem found, each naming the rule it is in.
+    pub fn parse(yaml: &str) -> Result<Self, Vec<String>> {
+        let policy: Self = serde_yaml::from_str(yaml).map_err(|e| vec![e.to_string()])?;
+        let problems = policy.validate();
+        if problems.is_empty() {
+            Ok(policy)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Check the policy makes sense, returning a readable line for each problem
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.rules.is_empty() {
+            problems.push("The policy has no rules".to_string());
+        }
+        if self.rules.len() > MAX_RULES {
+            problems.push(format!("A policy can have at most {MAX_RULES} rules"));
+        }
+
+        let mut names = HashSet::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let label = format!("Rule {} (`{}`)", index + 1, rule.name);
+            if rule.name.trim().is_empty() {
+                problems.push(format!("Rule {} has no name", index + 1));
+            } else if !names.insert(rule.name.trim().to_lowercase()) {
+                problems.push(format!("{label}: another rule has the same name"));
+            }
+
+            let when = &rule.when;
+            if when.category.as_ref().is_some_and(|category| {
+                category.trim().is_empty() || *category != category.trim().to_lowercase()
+            }) {
+                problems.push(format!(
+                    "{label}: `category` must be a lowercase name such as `voice`"
+                ));
+            }
+            for (field, value) in [
+                ("score_above", when.score_above),
+                ("score_at_most", when.score_at_most),
+                ("severity_at_least", when.severity_at_least),
+            ] {
+                if value.is_some_and(|value| !(value.is_finite() && value >= 0.0)) {
+                    problems.push(format!("{label}: `{field}` can't be negative"));
+                }
+            }
+            if when
+                .score_above
+                .zip(when.score_at_most)
+                .is_some_and(|(above, at_most)| above >= at_most)
+            {
+                problems.push(format!(
+                    "{label}: `score_above` must be less than `score_at_most`, or the rule can never fire"
+                ));
+            }
+            if when.enforced_within_hours.is_some() && when.not_enforced_within_hours.is_some() {
+                problems.push(format!(
+                    "{label}: use only one of `enforced_within_hours` and `not_enforced_within_hours`"
+                ));
+            }
+
+            let action_is_none = rule.then.action.trim().eq_ignore_ascii_case("none");
+            if let Some(e) = escalation::parse_action(&rule.then.action)
+                .err()
+                .filter(|_| !action_is_none)
+            {
+                problems.push(format!("{label}: {e}"));
+            }
+        }
+        problems
+    }
+
+    /// The first rule whose conditions all hold
+    #[must_use]
+    pub fn evaluate(&self, facts: &Facts) -> Option<&PolicyRule> {
+        self.rules.iter().find(|rule| rule.when.matches(facts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r"
+rules:
+  - name: repeat voice offender
+    when:
+      category: voice
+      score_above: 3
+      enforced_within_hours: 24
+    then:
+      action: deafen 30m
+      notify: public
+  - name: too loud
+    when:
+      score_above: 2
+    then:
+      action: voice_mute 5m
+  - name: otherwise
+    then:
+      action: none
+";
+
+    fn facts(score: f64, last_enforced: Option<DateTime<Utc>>) -> Facts {
+        Facts {
+            category: "voice".to_string(),
+            score,
+            severity: 1.0,
+            warnings: 3,
+            last_enforced,
+            now: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_fires() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let recently = Some(Utc::now() - Duration::hours(2));
+
+        let rule = policy.evaluate(&facts(3.5, recently)).unwrap();
+        assert_eq!(rule.name, "repeat voice offender");
+        assert_eq!(
+            rule.then.enforcement(),
+            Some(EnforcementAction::VoiceDeafen {
+                duration: Some(1800)
+            })
+        );
+        assert_eq!(rule.then.notify, Some(PolicyNotify::Public));
+
+        // Not punished lately, so the next rule applies
+        let rule = policy.evaluate(&facts(3.5, None)).unwrap();
+        assert_eq!(rule.name, "too loud");
+
+        let rule = policy.evaluate(&facts(1.0, recently)).unwrap();
+        assert_eq!(rule.name, "otherwise");
+        assert_eq!(rule.then.enforcement(), None);
+    }
+
+    #[test]
+    fn test_validation_errors_are_readable() {
+        let problems = Policy::parse(
+            r"
+rules:
+  - name: a
+    when:
+      score_above: 4
+      score_at_most: 2
+    then:
+      action: smite
+  - name: a
+    then:
+      action: kick
+",
+        )
+        .unwrap_err();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("Rule 1 (`a`): `score_above` must be less"));
+        assert!(problems[1].contains("Unknown punishment `smite`"));
+        assert!(problems[2].starts_with("Rule 2 (`a`): another rule"));
+
+        // Typos in field names are caught rather than ignored
+        let problems = Policy::parse(
+            "rules:\n  - name: a\n    when:\n      scor_above: 1\n    then:\n      action: kick\n",
+        )
+        .unwrap_err();
+        assert!(problems[0].contains("unknown field `scor_above`"));
+
+        assert!(Policy::parse("rules: []").is_err());
+    }
+}
@@ -0,0 +1,461 @@
+//! Weighted punishment tables for repeat voice offenders
+//!
+//! Each guild has a table of candidate punishments, each with a weight and the
+//! ranges its parameters are drawn from. The guild's chaos factor reshapes the
+//! weights before drawing: at 0.5 they apply as written, lower values sharpen
+//! them toward the heaviest-weighted punishment and higher values flatten them
+//! toward an even draw.
+
+use crate::data::EnforcementAction;
+use crate::escalation::{MAX_TIMEOUT_SECONDS, format_duration, parse_duration};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Most candidates a table can have
+pub const MAX_CANDIDATES: usize = 10;
+
+/// Most teleports a haunt in a table can make
+pub const MAX_TELEPORTS: u64 = 10;
+
+/// An inclusive range a parameter is drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Span {
+    /// A range of a single value
+    #[must_use]
+    pub const fn fixed(value: u64) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    fn draw(self, rng: &mut impl Rng) -> u64 {
+        if self.min >= self.max {
+            self.min
+        } else {
+            rng.gen_range(self.min..=self.max)
+        }
+    }
+
+    fn format(self, unit: fn(u64) -> String) -> String {
+        if self.min == self.max {
+            unit(self.min)
+        } else {
+            format!("{}-{}", unit(self.min), unit(self.max))
+        }
+    }
+}
+
+/// A punishment the daemon can draw, with ranges for its parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Candidate {
+    VoiceMute {
+        duration: Span,
+    },
+    VoiceDeafen {
+        duration: Span,
+    },
+    VoiceDisconnect {
+        delay: Span,
+    },
+    Haunt {
+        teleports: Span,
+        interval: Span,
+        /// Chance of returning the user to where they started, from 0 to 1
+        return_chance: f64,
+    },
+    Mute {
+        duration: Span,
+    },
+    Ban {
+        duration: Span,
+    },
+    Kick {
+        delay: Span,
+    },
+}
+
+impl Candidate {
+    /// Draw the candidate's parameters
+    pub fn draw(&self, rng: &mut impl Rng) -> EnforcementAction {
+        match self {
+            Self::VoiceMute { duration } => EnforcementAction::VoiceMute {
+                duration: Some(duration.draw(rng)),
+            },
+            Self::VoiceDeafen { duration } => EnforcementAction::VoiceDeafen {
+                duration: Some(duration.draw(rng)),
+            },
+            Self::VoiceDisconnect { delay } => EnforcementAction::VoiceDisconnect {
+                delay: Some(delay.draw(rng)),
+            },
+            Self::Haunt {
+                teleports,
+                interval,
+                return_chance,
+            } => EnforcementAction::VoiceChannelHaunt {
+                teleport_count: Some(teleports.draw(rng)),
+                interval: Some(interval.draw(rng)),
+                return_to_origin: Some(rng.gen_bool(return_chance.clamp(0.0, 1.0))),
+                original_channel_id: None, // No original channel for teleport
+            },
+            Self::Mute { duration } => EnforcementAction::Mute {
+                duration: Some(duration.draw(rng)),
+            },
+            Self::Ban { duration } => EnforcementAction::Ban {
+                duration: Some(duration.draw(rng)),
+            },
+            Self::Kick { delay } => EnforcementAction::Kick {
+                delay: Some(delay.draw(rng)),
+            },
+        }
+    }
+
+    /// Write the candidate in the form [`PunishmentTable::parse`] reads, without its weight
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let seconds = |span: &Span| span.format(format_duration);
+        match self {
+            Self::VoiceMute { duration } => format!("voice_mute duration={}", seconds(duration)),
+            Self::VoiceDeafen { duration } => format!("deafen duration={}", seconds(duration)),
+            Self::VoiceDisconnect { delay } => format!("disconnect delay={}", seconds(delay)),
+            Self::Haunt {
+                teleports,
+                interval,
+                return_chance,
+            } => format!(
+                "haunt teleports={} interval={} return={return_chance}",
+                teleports.format(|count| count.to_string()),
+                seconds(interval)
+            ),
+            Self::Mute { duration } => format!("mute duration={}", seconds(duration)),
+            Self::Ban { duration } => format!("ban duration={}", seconds(duration)),
+            Self::Kick { delay } => format!("kick delay={}", seconds(delay)),
+        }
+    }
+}
+
+/// A candidate and how likely it is to be drawn, relative to the others
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedCandidate {
+    pub weight: f64,
+    pub candidate: Candidate,
+}
+
+/// A guild's punishments for repeat voice offenders, as stored in its config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PunishmentTable {
+    pub entries: Vec<WeightedCandidate>,
+}
+
+impl Default for PunishmentTable {
+    fn default() -> Self {
+        let entry = |candidate| WeightedCandidate {
+            weight: 1.0,
+            candidate,
+        };
+        Self {
+            entries: vec![
+                entry(Candidate::Haunt {
+                    teleports: Span { min: 1, max: 4 },
+                    interval: Span { min: 5, max: 10 },
+                    return_chance: 0.5,
+                }),
+                entry(Candidate::VoiceDeafen {
+                    duration: Span::fixed(900), // 15 minutes of deafening
+                }),
+                entry(Candidate::VoiceDisconnect {
+                    delay: Span::fixed(0), // Immediate disconnection
+                }),
+                entry(Candidate::VoiceMute {
+                    duration: Span::fixed(1200), // 20 minutes of voice mute
+                }),
+            ],
+        }
+    }
+}
+
+/// Exponent applied to the weights for a chaos factor: 4 at 0, 1 at 0.5 and 0.25 at 1
+fn sharpness(chaos_factor: f32) -> f64 {
+    4f64.powf(1.0 - 2.0 * f64::from(chaos_factor.clamp(0.0, 1.0)))
+}
+
+impl PunishmentTable {
+    /// Chance of drawing each entry under a chaos factor
+    #[must_use]
+    pub fn probabilities(&self, chaos_factor: f32) -> Vec<f64> {
+        let exponent = sharpness(chaos_factor);
+        let shaped: Vec<f64> = self
+            .entries
+            .iter()
+            .map(|entry| entry.weight.max(0.0).powf(exponent))
+            .collect();
+        let total: f64 = shaped.iter().sum();
+        if total > 0.0 {
+            shaped.iter().map(|weight| weight / total).collect()
+        } else {
+            vec![0.0; shaped.len()]
+        }
+    }
+
+    /// Draw a punishment under a chaos factor
+    pub fn draw(&self, rng: &mut impl Rng, chaos_factor: f32) -> EnforcementAction {
+        let probabilities = self.probabilities(chaos_factor);
+        let mut roll: f64 = rng.gen_range(0.0..1.0);
+        let chosen = self
+            .entries
+            .iter()
+            .zip(&probabilities)
+            .find(|(_, probability)| {
+                if roll < **probability {
+                    return true;
+                }
+                roll -= **probability;
+                false
+            })
+            .or_else(|| {
+                // Rounding can leave the roll just past the end
+                self.entries
+                    .iter()
+                    .zip(&probabilities)
+                    .rev()
+                    .find(|(_, probability)| **probability > 0.0)
+            });
+
+        match chosen {
+            Some((entry, _)) => entry.candidate.draw(rng),
+            None => EnforcementAction::VoiceMute {
+                duration: Some(1200),
+            },
+        }
+    }
+
+    /// Parse a table such as `haunt 2 teleports=1-4 interval=5s-10s return=0.5, deafen 1 duration=15m`
+    ///
+    /// Entries are separated by commas. Each is an action, its weight and
+    /// optional `key=value` parameters, where a value is a single value or a
+    /// `min-max` range. Parameters left out keep the daemon's usual values.
+    ///
+    /// # Errors
+    /// Returns an error describing the first entry that can't be parsed, or if
+    /// no entry has a weight above 0.
+    pub fn parse(table: &str) -> Result<Self, String> {
+        let entries = table
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(parse_entry)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if entries.len() > MAX_CANDIDATES {
+            return Err(format!(
+                "A table can have at most {MAX_CANDIDATES} punishments"
+            ));
+        }
+        if !entries.iter().any(|entry| entry.weight > 0.0) {
+            return Err("At least one punishment needs a weight above 0".to_string());
+        }
+        Ok(Self { entries })
+    }
+}
+
+fn parse_entry(entry: &str) -> Result<WeightedCandidate, String> {
+    let mut parts = entry.split_whitespace();
+    let name = parts.next().unwrap_or_default().to_lowercase();
+    let weight = parts
+        .next()
+        .and_then(|weight| weight.parse::<f64>().ok())
+        .filter(|weight| weight.is_finite() && *weight >= 0.0)
+        .ok_or_else(|| format!("`{entry}` needs a weight of 0 or more after the action"))?;
+
+    let mut candidate = match name.as_str() {
+        "voice_mute" | "voicemute" => Candidate::VoiceMute {
+            duration: Span::fixed(1200),
+        },
+        "deafen" | "voice_deafen" | "voicedeafen" => Candidate::VoiceDeafen {
+            duration: Span::fixed(900),
+        },
+        "disconnect" | "voice_disconnect" | "voicedisconnect" => Candidate::VoiceDisconnect {
+            delay: Span::fixed(0),
+        },
+        "haunt" => Candidate::Haunt {
+            teleports: Span { min: 1, max: 4 },
+            interval: Span { min: 5, max: 10 },
+            return_chance: 0.5,
+        },
+        "mute" => Candidate::Mute {
+            duration: Span::fixed(600),
+        },
+        "ban" => Candidate::Ban {
+            duration: Span::fixed(86400),
+        },
+        "kick" => Candidate::Kick {
+            delay: Span::fixed(0),
+        },
+        other => {
+            return Err(format!(
+                "Unknown punishment `{other}`. Use `voice_mute`, `deafen`, `disconnect`, `haunt`, `mute`, `ban` or `kick`."
+            ));
+        }
+    };
+
+    for parameter in parts {
+        let (key, value) = parameter
+            .split_once('=')
+            .ok_or_else(|| format!("Expected `key=value` in `{entry}`, found `{parameter}`"))?;
+        match (&mut candidate, key) {
+            (Candidate::Mute { duration }, "duration") => {
+                *duration = parse_span(value, |timeout| {
+                    Some(parse_duration(timeout)?)
+                        .filter(|seconds| *seconds <= MAX_TIMEOUT_SECONDS)
+                        .ok_or_else(|| {
+                            format!(
+                                "`{timeout}` is too long, Discord can't time anyone out for more than 28d"
+                            )
+                        })
+                })?;
+            }
+            (
+                Candidate::VoiceMute { duration }
+                | Candidate::VoiceDeafen { duration }
+                | Candidate::Ban { duration },
+                "duration",
+            )
+            | (
+                Candidate::VoiceDisconnect { delay: duration }
+                | Candidate::Kick { delay: duration },
+                "delay",
+            ) => *duration = parse_span(value, parse_duration)?,
+            (Candidate::Haunt { teleports, .. }, "teleports") => {
+                *teleports = parse_span(value, |count| {
+                    count
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|count| (1..=MAX_TELEPORTS).contains(count))
+                        .ok_or_else(|| {
+                            format!("A haunt makes 1 to {MAX_TELEPORTS} teleports, not `{count}`")
+                        })
+                })?;
+            }
+            (Candidate::Haunt { interval, .. }, "interval") => {
+                *interval = parse_span(value, |interval| {
+                    Some(parse_duration(interval)?)
+                        .filter(|seconds| *seconds > 0)
+                        .ok_or_else(|| {
+                            format!("A haunt's interval must be above 0s, not `{interval}`")
+                        })
+                })?;
+            }
+            (Candidate::Haunt { return_chance, .. }, "return") => {
+                *return_chance = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|chance| (0.0..=1.0).contains(chance))
+                    .ok_or_else(|| {
+                        format!("`return` must be a chance from 0 to 1, not `{value}`")
+                    })?;
+            }
+            _ => return Err(format!("`{name}` has no `{key}` parameter")),
+        }
+    }
+
+    Ok(WeightedCandidate { weight, candidate })
+}
+
+fn parse_span(value: &str, parse: impl Fn(&str) -> Result<u64, String>) -> Result<Span, String> {
+    let span = match value.split_once('-') {
+        Some((min, max)) => Span {
+            min: parse(min)?,
+            max: parse(max)?,
+        },
+        None => Span::fixed(parse(value)?),
+    };
+    if span.min > span.max {
+        return Err(format!("The range `{value}` runs backwards"));
+    }
+    Ok(span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_parse_and_describe() {
+        let table = PunishmentTable::parse(
+            "haunt 2 teleports=2-3 interval=5s-10s return=0, deafen 1 duration=10m-15m, disconnect 0",
+        )
+        .unwrap();
+        assert_eq!(table.entries.len(), 3);
+        assert_eq!(
+            table.entries[0].candidate.describe(),
+            "haunt teleports=2-3 interval=5s-10s return=0"
+        );
+        assert_eq!(
+            table.entries[1].candidate,
+            Candidate::VoiceDeafen {
+                duration: Span { min: 600, max: 900 }
+            }
+        );
+
+        assert!(PunishmentTable::parse("disconnect 0").is_err());
+        assert!(PunishmentTable::parse("deafen").is_err());
+        assert!(PunishmentTable::parse("deafen 1 teleports=3").is_err());
+        assert!(PunishmentTable::parse("deafen 1 duration=15m-5m").is_err());
+        assert!(PunishmentTable::parse("haunt 1 return=2").is_err());
+        assert!(PunishmentTable::parse("haunt 1 interval=0s-10s").is_err());
+        assert!(PunishmentTable::parse("haunt 1 teleports=0").is_err());
+        assert!(PunishmentTable::parse("haunt 1 teleports=2-1000000").is_err());
+        assert!(PunishmentTable::parse("haunt 1 teleports=10 interval=1s").is_ok());
+        assert!(PunishmentTable::parse("mute 1 duration=28d").is_ok());
+        assert!(PunishmentTable::parse("mute 1 duration=1h-29d").is_err());
+    }
+
+    #[test]
+    fn test_chaos_reshapes_the_weights() {
+        let table = PunishmentTable::parse("deafen 3, disconnect 1, voice_mute 0").unwrap();
+
+        let written = table.probabilities(0.5);
+        assert!((written[0] - 0.75).abs() < 1e-9);
+        assert!(written[2].abs() < f64::EPSILON);
+
+        // Less chaos sharpens toward the heaviest weight, more flattens the draw
+        assert!(table.probabilities(0.0)[0] > 0.95);
+        let flat = table.probabilities(1.0);
+        assert!(flat[0] < 0.6 && flat[1] > 0.4);
+    }
+
+    #[test]
+    fn test_draw_stays_within_the_table() {
+        let table = PunishmentTable::parse("haunt 1 teleports=2-3 return=1, disconnect 0").unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            match table.draw(&mut rng, 0.3) {
+                EnforcementAction::VoiceChannelHaunt {
+                    teleport_count: Some(count),
+                    return_to_origin: Some(true),
+                    ..
+                } => assert!((2..=3).contains(&count)),
+                other => panic!("drew {other:?}"),
+            }
+        }
+
+        // The default table can draw every one of its punishments, voice mute included
+        let defaults = PunishmentTable::default();
+        let mut muted = false;
+        for _ in 0..200 {
+            muted |= matches!(
+                defaults.draw(&mut rng, 0.5),
+                EnforcementAction::VoiceMute { .. }
+            );
+        }
+        assert!(muted);
+    }
+}
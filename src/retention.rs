@@ -0,0 +1,511 @@
+//! Retention sweeps and erasing a user's records
+//!
+//! Each guild can limit how long warnings and finished enforcements are kept.
+//! A periodic sweep removes anything older, adding what it removed to the
+//! guild's `purged` totals and each user's `purged_warnings` so counts survive
+//! the records themselves. Pending and active enforcements are never swept.
+
+use crate::data::{Data, EnforcementState, PendingEnforcement};
+use crate::moderation::ModerationBackend;
+use crate::{enforcement, haunt};
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use tracing::{error, info};
+
+/// Default number of seconds between retention sweeps
+pub const DEFAULT_SWEEP_INTERVAL_SECONDS: u64 = 3600;
+
+/// Most days a guild can keep records before they are swept
+pub const MAX_RETENTION_DAYS: u32 = 3650;
+
+/// Records removed from one guild by a sweep
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    pub guild_id: u64,
+    pub warnings: u64,
+    pub enforcements: u64,
+}
+
+/// Records removed by erasing a user
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErasureReport {
+    pub warnings: usize,
+    pub enforcements: usize,
+    pub warning_states: usize,
+}
+
+/// Punishments lifted before erasing a user
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiftReport {
+    pub lifted: usize,
+    /// Enforcements that couldn't be lifted, with the reason, whose records are kept
+    pub failed: Vec<(String, String)>,
+}
+
+impl LiftReport {
+    /// Ids of the enforcements that must not be erased
+    #[must_use]
+    pub fn kept(&self) -> Vec<String> {
+        self.failed.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    /// A line listing the punishments that couldn't be lifted, if any
+    #[must_use]
+    pub fn describe_failures(&self) -> Option<String> {
+        if self.failed.is_empty() {
+            return None;
+        }
+        let failures = self
+            .failed
+            .iter()
+            .map(|(id, error)| format!("`{id}` ({error})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(format!(
+            "⚠️ {} punishment(s) could not be lifted and their records were kept: {failures}",
+            self.failed.len()
+        ))
+    }
+}
+
+impl ErasureReport {
+    /// Whether nothing was found to erase
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.warnings == 0 && self.enforcements == 0 && self.warning_states == 0
+    }
+}
+
+/// Start sweeping every guild on a fixed interval
+pub fn start_sweep_task(data: Data, interval_seconds: u64) {
+    tokio::spawn(async move {
+        info!("Starting retention sweep task with {interval_seconds}s interval");
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_seconds));
+        loop {
+            interval.tick().await;
+            for report in sweep(&data, Utc::now()) {
+                info!(
+                    "Retention sweep removed {} warnings and {} enforcements from guild {}",
+                    report.warnings, report.enforcements, report.guild_id
+                );
+            }
+        }
+    });
+}
+
+/// Read the sweep interval from `DATA_RETENTION_SWEEP_SECONDS`
+#[must_use]
+pub fn sweep_interval_from_env() -> u64 {
+    std::env::var("DATA_RETENTION_SWEEP_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(DEFAULT_SWEEP_INTERVAL_SECONDS)
+}
+
+/// When a finished enforcement finished
+fn finished_at(enforcement: &PendingEnforcement) -> DateTime<Utc> {
+    enforcement
+        .reversed_at
+        .or(enforcement.executed_at)
+        .unwrap_or(enforcement.created_at)
+}
+
+/// The time `days` before `now`, if it can be represented
+fn cutoff(now: DateTime<Utc>, days: u32) -> Option<DateTime<Utc>> {
+    now.checked_sub_signed(Duration::days(i64::from(days)))
+}
+
+/// Remove records older than each guild's retention settings
+///
+/// Returns a report for every guild that had something removed.
+pub fn sweep(data: &Data, now: DateTime<Utc>) -> Vec<SweepReport> {
+    let configs: Vec<_> = data
+        .guild_configs
+        .iter()
+        .map(|entry| entry.value().clone())
+        .filter(|config| {
+            config.retention.warning_days.is_some() || config.retention.enforcement_days.is_some()
+        })
+        .collect();
+
+    let mut reports = Vec::new();
+    for mut config in configs {
+        let guild_id = config.guild_id;
+        let mut report = SweepReport {
+            guild_id,
+            ..Default::default()
+        };
+
+        // A cutoff before the start of time has nothing older to sweep
+        if let Some(cutoff) = config
+            .retention
+            .warning_days
+            .and_then(|days| cutoff(now, days))
+        {
+            report.warnings = sweep_warnings(data, guild_id, cutoff);
+        }
+
+        if let Some(cutoff) = config
+            .retention
+            .enforcement_days
+            .and_then(|days| cutoff(now, days))
+        {
+            let expired: Vec<String> = data
+                .completed_enforcements
+                .iter()
+                .filter(|entry| {
+                    entry.guild_id == guild_id
+                        && !matches!(
+                            entry.state,
+                            EnforcementState::Pending | EnforcementState::Active
+                        )
+                        && finished_at(entry.value()) < cutoff
+                })
+                .map(|entry| entry.key().clone())
+                .collect();
+            for id in expired {
+                if data.remove_enforcement(&id).is_some() {
+                    report.enforcements += 1;
+                }
+            }
+        }
+
+        if report.warnings > 0 || report.enforcements > 0 {
+            config.purged.warnings += report.warnings;
+            config.purged.enforcements += report.enforcements;
+            data.set_guild_config(config);
+            reports.push(report);
+        }
+    }
+
+    reports
+}
+
+/// Remove a guild's warnings older than `cutoff`, including the matching
+/// entries in users' warning states, and return how many warnings went
+fn sweep_warnings(data: &Data, guild_id: u64, cutoff: DateTime<Utc>) -> u64 {
+    let expired: Vec<String> = data
+        .warnings
+        .iter()
+        .filter(|entry| entry.guild_id == guild_id && entry.timestamp < cutoff)
+        .map(|entry| entry.key().clone())
+        .collect();
+    let mut removed = 0;
+    for id in expired {
+        if data.remove_warning(&id).is_some() {
+            removed += 1;
+        }
+    }
+
+    let states: Vec<_> = data
+        .user_warning_states
+        .iter()
+        .filter(|entry| {
+            entry.guild_id == guild_id
+                && entry
+                    .warning_timestamps
+                    .iter()
+                    .any(|timestamp| *timestamp < cutoff)
+        })
+        .map(|entry| entry.value().clone())
+        .collect();
+    for mut state in states {
+        let keep: Vec<bool> = state
+            .warning_timestamps
+            .iter()
+            .map(|timestamp| *timestamp >= cutoff)
+            .collect();
+        state.retain_warnings(&keep);
+
+        state.purged_warnings += keep.iter().filter(|kept| !**kept).count() as u64;
+        data.set_user_warning_state(state);
+    }
+
+    removed
+}
+
+/// Lift a user's punishments still in force, since nothing reverses them once erased
+///
+/// A haunt underway is cancelled, which sends the user back to the channel it
+/// started from. Punishments that fail to lift are reported so their records
+/// can be kept.
+pub async fn lift_active(
+    backend: &dyn ModerationBackend,
+    data: &Data,
+    guild_id: u64,
+    user_id: u64,
+) -> LiftReport {
+    let active: Vec<PendingEnforcement> = data
+        .user_enforcements(user_id, guild_id)
+        .into_iter()
+        .filter(|enforcement| enforcement.state == EnforcementState::Active)
+        .collect();
+    let mut report = LiftReport::default();
+    for active in &active {
+        let id = &active.id;
+        let result = if active.haunt.is_some() {
+            haunt::cancel(backend, data, id).await
+        } else {
+            enforcement::reverse_enforcement(backend, data, id).await
+        };
+        match result {
+            Ok(()) => report.lifted += 1,
+            Err(e) => {
+                error!("Failed to lift enforcement {id} before erasing user {user_id}: {e}");
+                report.failed.push((id.clone(), e.to_string()));
+            }
+        }
+    }
+    report
+}
+
+/// Remove every record about a user in a guild, except the enforcements in `keep`
+///
+/// Active enforcements should be lifted first, since erasing them loses track
+/// of when they were due to be reversed. Any that couldn't be lifted belong in
+/// `keep`.
+pub fn erase_user(data: &Data, guild_id: u64, user_id: u64, keep: &[String]) -> ErasureReport {
+    let mut report = ErasureReport::default();
+
+    for id in data.warnings_by_user.ids(guild_id, user_id) {
+        if data.remove_warning(&id).is_some() {
+            report.warnings += 1;
+        }
+    }
+
+    for id in data.enforcements_by_user.ids(guild_id, user_id) {
+        if keep.contains(&id) {
+            continue;
+        }
+        if data.remove_enforcement(&id).is_some() {
+            report.enforcements += 1;
+        }
+    }
+
+    if data.remove_user_warning_state(user_id, guild_id).is_some() {
+        report.warning_states += 1;
+    }
+
+    report
+}
+
+/// Post an erasure to the guild's altar channel, if one is set
+pub async fn log_erasure(
+    http: &serenity::Http,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    user_id: u64,
+    moderator: serenity::UserId,
+    report: &ErasureReport,
+    lifts: &LiftReport,
+) {
+    let Some(log_channel_id) = data.get_guild_config(guild_id).enforcement_log_channel_id else {
+        return;
+    };
+
+    let mut content = format!(
+        "🗑️ **RECORDS ERASED**\n\nEvery record of <@{user_id}> has been struck from the daemon's scrolls.\n\nErased by: <@{}>\nWarnings: {}\nEnforcements: {}\nWarning states: {}\nPunishments lifted: {}",
+        moderator.get(),
+        report.warnings,
+        report.enforcements,
+        report.warning_states,
+        lifts.lifted
+    );
+    if let Some(failures) = lifts.describe_failures() {
+        content.push_str(&format!("\n\n{failures}"));
+    }
+    let channel_id = serenity::ChannelId::new(log_channel_id);
+    let message = serenity::CreateMessage::new().content(content);
+    if let Err(e) = channel_id.send_message(http, message).await {
+        error!("Failed to log erasure to the altar: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{EnforcementAction, GuildConfig, NotificationMethod, Warning};
+    use crate::haunt::HauntProgress;
+    use crate::moderation::{ModerationError, RecordingBackend};
+
+    fn warning(id: &str, user_id: u64, timestamp: DateTime<Utc>) -> Warning {
+        Warning {
+            id: id.to_string(),
+            user_id,
+            issuer_id: 67890,
+            guild_id: 11111,
+            reason: "Being loud".to_string(),
+            timestamp,
+            notification_method: NotificationMethod::DirectMessage,
+            enforcement: None,
+            severity: 1.0,
+            judgment: None,
+            category: None,
+        }
+    }
+
+    fn enforcement(id: &str, state: EnforcementState, at: DateTime<Utc>) -> PendingEnforcement {
+        PendingEnforcement {
+            execute_at: at,
+            created_at: at,
+            executed_at: Some(at),
+            ..PendingEnforcement::test(
+                id,
+                EnforcementAction::VoiceMute { duration: Some(60) },
+                state,
+            )
+        }
+    }
+
+    #[test]
+    fn test_sweep_removes_old_records_and_keeps_counts() {
+        let now = Utc::now();
+        let old = now - Duration::days(40);
+        let data = Data::new();
+
+        let mut config = GuildConfig::new(11111);
+        config.retention.warning_days = Some(30);
+        config.retention.enforcement_days = Some(30);
+        data.set_guild_config(config);
+
+        data.insert_warning(warning("old", 12345, old));
+        data.insert_warning(warning("new", 12345, now));
+        data.store_enforcement(enforcement("done", EnforcementState::Completed, old));
+        data.store_enforcement(enforcement("waiting", EnforcementState::Pending, old));
+
+        let mut state = data.get_or_create_user_warning_state(12345, 11111);
+        state.warning_timestamps = vec![old, now];
+        state.warning_reasons = vec!["old".to_string(), "new".to_string()];
+        state.mod_issuers = vec![1, 2];
+        data.set_user_warning_state(state);
+
+        let reports = sweep(&data, now);
+        assert_eq!(
+            reports,
+            vec![SweepReport {
+                guild_id: 11111,
+                warnings: 1,
+                enforcements: 1,
+            }]
+        );
+        assert!(!data.warnings.contains_key("old"));
+        assert!(data.warnings.contains_key("new"));
+        assert!(data.pending_enforcements.contains_key("waiting"));
+
+        let state = data.get_or_create_user_warning_state(12345, 11111);
+        assert_eq!(state.warning_reasons, vec!["new".to_string()]);
+        assert_eq!(state.mod_issuers, vec![2]);
+        assert_eq!(state.purged_warnings, 1);
+        assert_eq!(
+            data.get_guild_config(serenity::GuildId::new(11111)).purged,
+            crate::data::PurgedCounts {
+                warnings: 1,
+                enforcements: 1,
+            }
+        );
+
+        // Nothing left to sweep
+        assert!(sweep(&data, now).is_empty());
+
+        // A retention reaching past the start of time keeps everything
+        let mut config = data.get_guild_config(serenity::GuildId::new(11111));
+        config.retention.warning_days = Some(u32::MAX);
+        config.retention.enforcement_days = Some(u32::MAX);
+        data.set_guild_config(config);
+        assert!(sweep(&data, now).is_empty());
+        assert!(data.warnings.contains_key("new"));
+    }
+
+    #[test]
+    fn test_erase_user() {
+        let now = Utc::now();
+        let data = Data::new();
+        data.insert_warning(warning("mine", 12345, now));
+        data.insert_warning(warning("theirs", 54321, now));
+        data.store_enforcement(enforcement("done", EnforcementState::Completed, now));
+        let _ =
+            data.add_to_user_warning_state(12345, 11111, "Being loud".to_string(), 1, 1.0, "voice");
+        let _ = data.take_changes();
+
+        let report = erase_user(&data, 11111, 12345, &[]);
+        assert_eq!(
+            report,
+            ErasureReport {
+                warnings: 1,
+                enforcements: 1,
+                warning_states: 1,
+            }
+        );
+        assert!(data.warnings.contains_key("theirs"));
+
+        // The removals reach the storage backend
+        let changes = data.take_changes();
+        assert_eq!(changes.removed_warnings, vec!["mine".to_string()]);
+        assert_eq!(changes.removed_enforcements, vec!["done".to_string()]);
+        assert_eq!(changes.removed_user_warning_states.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_erasing_a_user_mid_haunt_sends_them_home() {
+        let now = Utc::now();
+        let data = Data::new();
+        let backend = RecordingBackend::with_voice_channels(&[1, 2, 3]);
+        backend.join_voice(12345, 3);
+        data.store_enforcement(PendingEnforcement {
+            action: EnforcementAction::VoiceChannelHaunt {
+                teleport_count: Some(3),
+                interval: Some(10),
+                return_to_origin: Some(true),
+                original_channel_id: None,
+            },
+            haunt: Some(HauntProgress {
+                origin_channel_id: 1,
+                teleport_count: 3,
+                interval: 10,
+                return_to_origin: true,
+                teleports_done: 1,
+                next_hop_at: now,
+            }),
+            ..enforcement("haunt", EnforcementState::Active, now)
+        });
+
+        let lifts = lift_active(&backend, &data, 11111, 12345).await;
+        assert_eq!(lifts.lifted, 1);
+        let report = erase_user(&data, 11111, 12345, &lifts.kept());
+        assert_eq!(report.enforcements, 1);
+        assert_eq!(
+            backend
+                .voice_channel(serenity::GuildId::new(11111), serenity::UserId::new(12345))
+                .await,
+            Some(serenity::ChannelId::new(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_punishments_that_fail_to_lift_are_kept() {
+        let now = Utc::now();
+        let data = Data::new();
+        let backend = RecordingBackend::default();
+        let mut mute = enforcement("mute", EnforcementState::Active, now);
+        mute.reverse_at = Some(now + Duration::minutes(5));
+        data.store_enforcement(mute);
+        data.store_enforcement(enforcement("done", EnforcementState::Completed, now));
+
+        backend.fail_next(ModerationError::Permanent("Missing Permissions".into()));
+        let lifts = lift_active(&backend, &data, 11111, 12345).await;
+        assert_eq!(lifts.lifted, 0);
+        assert_eq!(lifts.kept(), vec!["mute".to_string()]);
+        assert!(
+            lifts
+                .describe_failures()
+                .unwrap()
+                .contains("Missing Permissions")
+        );
+
+        let report = erase_user(&data, 11111, 12345, &lifts.kept());
+        assert_eq!(report.enforcements, 1);
+        assert!(data.completed_enforcements.contains_key("mute"));
+        assert!(!data.completed_enforcements.contains_key("done"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Deadlines for the enforcement task
+//!
+//! Every pending enforcement has a deadline to be executed and every active one
+//! with a duration has a deadline to be reversed. The schedule keeps them in a
+//! min-heap so the enforcement task can sleep until exactly the next one, and
+//! wakes the task whenever a deadline is added, moved or cancelled so it can
+//! re-arm its timer.
+
+use crate::data::{EnforcementState, PendingEnforcement};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// What happens to an enforcement when its deadline passes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeadlineKind {
+    Execute,
+    Reverse,
+}
+
+/// When something is due to happen to an enforcement
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline {
+    pub at: DateTime<Utc>,
+    pub kind: DeadlineKind,
+    pub enforcement_id: String,
+}
+
+impl Deadline {
+    /// The enforcement's next deadline, if anything is still due to happen to it
+    #[must_use]
+    pub fn of(enforcement: &PendingEnforcement) -> Option<Self> {
+        let (at, kind) = match enforcement.state {
+            EnforcementState::Pending => (enforcement.execute_at, DeadlineKind::Execute),
+            EnforcementState::Active => (enforcement.reverse_at?, DeadlineKind::Reverse),
+            _ => return None,
+        };
+        Some(Self {
+            at,
+            kind,
+            enforcement_id: enforcement.id.clone(),
+        })
+    }
+
+    /// Whether the deadline still matches the enforcement as it is now stored
+    #[must_use]
+    pub fn is_current(&self, enforcement: &PendingEnforcement) -> bool {
+        Self::of(enforcement).as_ref() == Some(self)
+    }
+}
+
+/// Time-ordered queue of enforcement deadlines
+#[derive(Debug, Default)]
+pub struct Schedule {
+    queue: Mutex<BinaryHeap<Reverse<Deadline>>>,
+    rearm: Notify,
+}
+
+impl Schedule {
+    /// Replace any deadline the enforcement had with its current one
+    pub fn update(&self, enforcement: &PendingEnforcement) {
+        {
+            let mut queue = self.lock();
+            queue.retain(|Reverse(deadline)| deadline.enforcement_id != enforcement.id);
+            if let Some(deadline) = Deadline::of(enforcement) {
+                queue.push(Reverse(deadline));
+            }
+        }
+        self.rearm.notify_one();
+    }
+
+    /// Cancel an enforcement's deadline
+    pub fn remove(&self, enforcement_id: &str) {
+        self.lock()
+            .retain(|Reverse(deadline)| deadline.enforcement_id != enforcement_id);
+        self.rearm.notify_one();
+    }
+
+    /// Throw away every deadline and schedule these instead
+    pub fn rebuild(&self, deadlines: impl IntoIterator<Item = Deadline>) {
+        let queue: BinaryHeap<_> = deadlines.into_iter().map(Reverse).collect();
+        *self.lock() = queue;
+        self.rearm.notify_one();
+    }
+
+    /// When the earliest deadline is
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<DateTime<Utc>> {
+        self.lock().peek().map(|Reverse(deadline)| deadline.at)
+    }
+
+    /// Take every deadline that has passed by `now`, earliest first
+    #[must_use]
+    pub fn pop_due(&self, now: DateTime<Utc>) -> Vec<Deadline> {
+        let mut queue = self.lock();
+        let mut due = Vec::new();
+        while queue
+            .peek()
+            .is_some_and(|Reverse(deadline)| deadline.at <= now)
+        {
+            if let Some(Reverse(deadline)) = queue.pop() {
+                due.push(deadline);
+            }
+        }
+        due
+    }
+
+    /// Number of scheduled deadlines
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether nothing is scheduled
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Wait until a deadline is added, moved or cancelled
+    pub async fn rearmed(&self) {
+        self.rearm.notified().await;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BinaryHeap<Reverse<Deadline>>> {
+        // The queue is left consistent even if a holder panicked
+        self.queue
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::EnforcementAction;
+    use chrono::Duration;
+
+    fn enforcement(id: &str, execute_at: DateTime<Utc>) -> PendingEnforcement {
+        PendingEnforcement {
+            execute_at,
+            created_at: execute_at,
+            ..PendingEnforcement::test(
+                id,
+                EnforcementAction::VoiceMute {
+                    duration: Some(300),
+                },
+                EnforcementState::Pending,
+            )
+        }
+    }
+
+    #[test]
+    fn test_deadlines_come_due_in_order() {
+        let now = Utc::now();
+        let schedule = Schedule::default();
+        schedule.update(&enforcement("late", now + Duration::minutes(5)));
+        schedule.update(&enforcement("soon", now + Duration::seconds(30)));
+        schedule.update(&enforcement("overdue", now - Duration::seconds(1)));
+        assert_eq!(schedule.next_deadline(), Some(now - Duration::seconds(1)));
+
+        let due = schedule.pop_due(now + Duration::minutes(1));
+        let ids: Vec<_> = due.iter().map(|d| d.enforcement_id.as_str()).collect();
+        assert_eq!(ids, ["overdue", "soon"]);
+        assert_eq!(schedule.next_deadline(), Some(now + Duration::minutes(5)));
+    }
+
+    #[test]
+    fn test_updates_replace_and_cancel_deadlines() {
+        let now = Utc::now();
+        let schedule = Schedule::default();
+        let mut mute = enforcement("mute", now);
+        schedule.update(&mute);
+
+        // Executed: the deadline moves to the reversal
+        mute.state = EnforcementState::Active;
+        mute.reverse_at = Some(now + Duration::minutes(5));
+        schedule.update(&mute);
+        assert_eq!(schedule.len(), 1);
+        assert_eq!(schedule.next_deadline(), mute.reverse_at);
+        assert!(schedule.pop_due(now).is_empty());
+
+        // Reversed: nothing left to do
+        mute.state = EnforcementState::Reversed;
+        schedule.update(&mute);
+        assert!(schedule.is_empty());
+
+        schedule.update(&enforcement("cancelled", now));
+        schedule.remove("cancelled");
+        assert!(schedule.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_rearm_the_waiter() {
+        let schedule = Schedule::default();
+        schedule.update(&enforcement("mute", Utc::now()));
+        // The wake-up is kept until someone waits for it
+        tokio::time::timeout(std::time::Duration::from_secs(1), schedule.rearmed())
+            .await
+            .unwrap();
+    }
+}
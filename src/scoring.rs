@@ -0,0 +1,386 @@
+//! Warning score strategies
+//!
+//! A user's warning score is compared against the guild's threshold to decide
+//! whether the daemon enforces. How the score is worked out from the user's
+//! warning history is up to the strategy the guild picked in its config.
+
+use crate::data::UserWarningState;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Default warning score threshold
+pub const WARNING_THRESHOLD: f64 = 2.0;
+/// Default decay rate per hour for [`ExponentialDecay`]; higher values mean faster decay
+pub const DECAY_RATE: f64 = 0.05;
+/// Default bonus per extra moderator for [`ExponentialDecay`]
+pub const MOD_DIVERSITY_BONUS: f64 = 0.5;
+/// Weight of a minor warning
+pub const SEVERITY_MINOR: f64 = 0.5;
+/// Weight of a moderate warning, and of warnings recorded before severities existed
+pub const SEVERITY_MODERATE: f64 = 1.0;
+/// Weight of a severe warning, enough to cross the default threshold on its own
+pub const SEVERITY_SEVERE: f64 = 3.0;
+/// Largest numeric severity accepted
+pub const MAX_SEVERITY: f64 = 10.0;
+/// Default window for [`SlidingWindow`]
+pub const DEFAULT_WINDOW_HOURS: u32 = 24;
+/// Default points for [`PointsWithExpiry`]
+pub const DEFAULT_POINTS_PER_WARNING: f64 = 1.0;
+/// Default lifetime of points for [`PointsWithExpiry`]
+pub const DEFAULT_EXPIRY_HOURS: u32 = 72;
+/// How far ahead [`time_below`] looks before giving up
+pub const FORECAST_HORIZON_DAYS: i64 = 365;
+/// Precision of [`time_below`], in seconds
+const FORECAST_PRECISION_SECONDS: i64 = 60;
+
+/// Parse a severity name (`minor`, `moderate`, `severe`) or a numeric weight
+///
+/// # Errors
+/// Returns an error if the value is neither a known name nor a weight between
+/// 0 (exclusive) and [`MAX_SEVERITY`].
+pub fn parse_severity(value: &str) -> Result<f64, String> {
+    match value.trim().to_lowercase().as_str() {
+        "minor" => Ok(SEVERITY_MINOR),
+        "moderate" => Ok(SEVERITY_MODERATE),
+        "severe" => Ok(SEVERITY_SEVERE),
+        other => other
+            .parse::<f64>()
+            .ok()
+            .filter(|weight| *weight > 0.0 && *weight <= MAX_SEVERITY)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown severity `{value}`. Use `minor`, `moderate`, `severe` or a weight up to {MAX_SEVERITY}."
+                )
+            }),
+    }
+}
+
+/// Name a severity for display
+#[must_use]
+pub fn severity_label(severity: f64) -> String {
+    if (severity - SEVERITY_MINOR).abs() < f64::EPSILON {
+        "minor".to_string()
+    } else if (severity - SEVERITY_MODERATE).abs() < f64::EPSILON {
+        "moderate".to_string()
+    } else if (severity - SEVERITY_SEVERE).abs() < f64::EPSILON {
+        "severe".to_string()
+    } else {
+        format!("×{severity:.2}")
+    }
+}
+
+/// A way of turning a user's warning history into a score
+pub trait ScoringStrategy {
+    /// Score the warnings in `state` as of `now`
+    fn score(&self, state: &UserWarningState, now: DateTime<Utc>) -> f64;
+
+    /// One-line description for moderators
+    fn describe(&self) -> String;
+}
+
+/// Every warning counts its severity when issued and fades exponentially with age.
+/// Warnings from more than one moderator add a bonus per extra moderator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialDecay {
+    pub decay_rate: f64,
+    pub diversity_bonus: f64,
+}
+
+impl ScoringStrategy for ExponentialDecay {
+    fn score(&self, state: &UserWarningState, now: DateTime<Utc>) -> f64 {
+        let mut total_score: f64 = state
+            .weighted_warnings()
+            .map(|(timestamp, severity)| {
+                let age_hours = (now - timestamp).num_seconds() as f64 / 3600.0;
+                severity * (-self.decay_rate * age_hours).exp()
+            })
+            .sum();
+
+        // Only moderators with a matching warning count
+        let unique_mods: HashSet<u64> = state
+            .mod_issuers
+            .iter()
+            .take(state.warning_timestamps.len())
+            .copied()
+            .collect();
+        if unique_mods.len() > 1 {
+            total_score += self.diversity_bonus * (unique_mods.len() as f64 - 1.0);
+        }
+
+        total_score
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "exponential decay ({:.3}/hour, +{:.2} per extra moderator)",
+            self.decay_rate, self.diversity_bonus
+        )
+    }
+}
+
+/// The score is the total severity of the warnings in the last `window_hours`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlidingWindow {
+    pub window_hours: u32,
+}
+
+impl ScoringStrategy for SlidingWindow {
+    fn score(&self, state: &UserWarningState, now: DateTime<Utc>) -> f64 {
+        let since = now - Duration::hours(i64::from(self.window_hours));
+        state
+            .weighted_warnings()
+            .filter(|(timestamp, _)| *timestamp > since)
+            .map(|(_, severity)| severity)
+            .sum()
+    }
+
+    fn describe(&self) -> String {
+        format!("warnings in the last {} hours", self.window_hours)
+    }
+}
+
+/// Every warning is worth a fixed number of points, times its severity, until it expires
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointsWithExpiry {
+    pub points_per_warning: f64,
+    pub expiry_hours: u32,
+}
+
+impl ScoringStrategy for PointsWithExpiry {
+    fn score(&self, state: &UserWarningState, now: DateTime<Utc>) -> f64 {
+        let lifetime = Duration::hours(i64::from(self.expiry_hours));
+        state
+            .weighted_warnings()
+            .filter(|(timestamp, _)| *timestamp + lifetime > now)
+            .map(|(_, severity)| severity * self.points_per_warning)
+            .sum()
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{:.2} points per warning, expiring after {} hours",
+            self.points_per_warning, self.expiry_hours
+        )
+    }
+}
+
+/// Project a score forward to find when it first drops below `target`
+///
+/// Every strategy only ever loses score as warnings age, so the time can be
+/// found by bisection. Returns `now` if the score is already below `target`,
+/// and `None` if it won't get there within [`FORECAST_HORIZON_DAYS`], such as
+/// when the moderator diversity bonus alone keeps it above.
+#[must_use]
+pub fn time_below(
+    strategy: &dyn ScoringStrategy,
+    state: &UserWarningState,
+    now: DateTime<Utc>,
+    target: f64,
+) -> Option<DateTime<Utc>> {
+    if strategy.score(state, now) < target {
+        return Some(now);
+    }
+    let mut high = now + Duration::days(FORECAST_HORIZON_DAYS);
+    if strategy.score(state, high) >= target {
+        return None;
+    }
+
+    // `low` is always at or above the target, `high` always below it
+    let mut low = now;
+    while (high - low).num_seconds() > FORECAST_PRECISION_SECONDS {
+        let mid = low + (high - low) / 2;
+        if strategy.score(state, mid) < target {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Some(high)
+}
+
+/// Describe a [`time_below`] result as a Discord relative timestamp
+#[must_use]
+pub fn format_forecast(at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> String {
+    match at {
+        Some(at) if at <= now => "already".to_string(),
+        Some(at) => format!("<t:{}:R>", at.timestamp()),
+        None => format!("not within {FORECAST_HORIZON_DAYS} days"),
+    }
+}
+
+/// The strategy a guild scores warnings with, as stored in its config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum ScoringConfig {
+    /// See [`ExponentialDecay`]
+    #[default]
+    Decay,
+    /// See [`SlidingWindow`]
+    SlidingWindow { window_hours: u32 },
+    /// See [`PointsWithExpiry`]
+    Points {
+        points_per_warning: f64,
+        expiry_hours: u32,
+    },
+}
+
+impl ScoringConfig {
+    /// Build a config from a strategy name and optional parameters
+    ///
+    /// # Errors
+    /// Returns an error naming the valid strategies if `name` isn't one of them,
+    /// or if a parameter is out of range.
+    pub fn parse(name: &str, hours: Option<u32>, points: Option<f64>) -> Result<Self, String> {
+        if hours == Some(0) {
+            return Err("The number of hours must be at least 1".to_string());
+        }
+        if points.is_some_and(|points| !(points.is_finite() && points > 0.0)) {
+            return Err("Points per warning must be greater than 0".to_string());
+        }
+
+        match name.trim().to_lowercase().as_str() {
+            "decay" => Ok(Self::Decay),
+            "sliding_window" | "window" => Ok(Self::SlidingWindow {
+                window_hours: hours.unwrap_or(DEFAULT_WINDOW_HOURS),
+            }),
+            "points" => Ok(Self::Points {
+                points_per_warning: points.unwrap_or(DEFAULT_POINTS_PER_WARNING),
+                expiry_hours: hours.unwrap_or(DEFAULT_EXPIRY_HOURS),
+            }),
+            other => Err(format!(
+                "Unknown scoring strategy `{other}`. Use `decay`, `sliding_window` or `points`."
+            )),
+        }
+    }
+
+    /// The strategy this config selects, decaying with the guild's settings
+    #[must_use]
+    pub fn strategy(&self, decay_rate: f64, diversity_bonus: f64) -> Box<dyn ScoringStrategy> {
+        match *self {
+            Self::Decay => Box::new(ExponentialDecay {
+                decay_rate,
+                diversity_bonus,
+            }),
+            Self::SlidingWindow { window_hours } => Box::new(SlidingWindow { window_hours }),
+            Self::Points {
+                points_per_warning,
+                expiry_hours,
+            } => Box::new(PointsWithExpiry {
+                points_per_warning,
+                expiry_hours,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(ages_in_hours: &[i64], mods: &[u64]) -> UserWarningState {
+        let now = Utc::now();
+        UserWarningState {
+            user_id: 12345,
+            guild_id: 11111,
+            warning_timestamps: ages_in_hours
+                .iter()
+                .map(|hours| now - Duration::hours(*hours))
+                .collect(),
+            warning_reasons: Vec::new(),
+            mod_issuers: mods.to_vec(),
+            warning_severities: Vec::new(),
+            warning_categories: Vec::new(),
+            pending_enforcement: None,
+            last_updated: now,
+            purged_warnings: 0,
+        }
+    }
+
+    #[test]
+    fn test_strategies_score_the_same_history_differently() {
+        let now = Utc::now();
+        let history = state(&[1, 30, 100], &[1, 1, 2]);
+
+        let decay = ScoringConfig::Decay
+            .strategy(DECAY_RATE, MOD_DIVERSITY_BONUS)
+            .score(&history, now);
+        // Two moderators add one bonus on top of the decayed warnings
+        assert!(decay > MOD_DIVERSITY_BONUS + 0.9 && decay < MOD_DIVERSITY_BONUS + 1.3);
+
+        let window = ScoringConfig::SlidingWindow { window_hours: 48 }.strategy(0.0, 0.0);
+        assert!((window.score(&history, now) - 2.0).abs() < f64::EPSILON);
+
+        let points = ScoringConfig::Points {
+            points_per_warning: 1.5,
+            expiry_hours: 200,
+        }
+        .strategy(0.0, 0.0);
+        assert!((points.score(&history, now) - 4.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_severity_weights_each_warning() {
+        let now = Utc::now();
+        let mut history = state(&[0, 0], &[1, 1]);
+        // The second warning predates severities and counts as moderate
+        history.warning_severities = vec![SEVERITY_SEVERE];
+
+        let window = SlidingWindow { window_hours: 1 };
+        assert!((window.score(&history, now) - 4.0).abs() < f64::EPSILON);
+        let decay = ExponentialDecay {
+            decay_rate: DECAY_RATE,
+            diversity_bonus: MOD_DIVERSITY_BONUS,
+        };
+        assert!(decay.score(&history, now) > WARNING_THRESHOLD);
+
+        assert_eq!(parse_severity("Severe"), Ok(SEVERITY_SEVERE));
+        assert_eq!(parse_severity("2.5"), Ok(2.5));
+        assert!(parse_severity("0").is_err());
+        assert!(parse_severity("loud").is_err());
+    }
+
+    #[test]
+    fn test_time_below() {
+        let now = Utc::now();
+        let history = state(&[0], &[1]);
+        let decay = ExponentialDecay {
+            decay_rate: DECAY_RATE,
+            diversity_bonus: MOD_DIVERSITY_BONUS,
+        };
+
+        // One moderate warning halves in ln(2) / 0.05 ≈ 13.9 hours
+        let half = time_below(&decay, &history, now, 0.5).unwrap();
+        let hours = (half - now).num_seconds() as f64 / 3600.0;
+        assert!((hours - 2f64.ln() / DECAY_RATE).abs() < 0.05);
+        assert!(decay.score(&history, half) < 0.5);
+
+        assert_eq!(time_below(&decay, &history, now, 2.0), Some(now));
+
+        // Two moderators keep a bonus that never decays
+        let reported_twice = state(&[0, 0], &[1, 2]);
+        assert_eq!(time_below(&decay, &reported_twice, now, 0.5), None);
+
+        let window = SlidingWindow { window_hours: 24 };
+        let cleared = time_below(&window, &history, now, 0.5).unwrap();
+        assert!((cleared - (now + Duration::hours(24))).num_seconds().abs() <= 60);
+    }
+
+    #[test]
+    fn test_parse_and_serialize() {
+        assert_eq!(
+            ScoringConfig::parse("window", Some(6), None),
+            Ok(ScoringConfig::SlidingWindow { window_hours: 6 })
+        );
+        assert!(ScoringConfig::parse("vibes", None, None).is_err());
+        assert!(ScoringConfig::parse("points", Some(0), None).is_err());
+
+        let config = ScoringConfig::parse("points", None, Some(2.0)).unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("strategy: points"));
+        assert_eq!(
+            serde_yaml::from_str::<ScoringConfig>(&yaml).unwrap(),
+            config
+        );
+    }
+}
@@ -1,5 +1,7 @@
-use crate::data::{Data, EnforcementAction, EnforcementState};
+use crate::data::{Data, EnforcementAction, EnforcementState, ScoreForecast};
+use crate::scoring;
 use ::serenity::all::CacheHttp;
+use chrono::Utc;
 use dashmap::DashMap;
 use poise::serenity_prelude as serenity;
 use serenity::builder::CreateEmbed;
@@ -186,7 +188,7 @@ impl BotStatus {
 
         // Get channel name from cache if available
         let channel = data
-            .cache
+            .get_cache()
             .guild(guild_id)
             .and_then(|g| g.channels.get(&channel_id).cloned());
 
@@ -211,20 +213,20 @@ impl BotStatus {
             .insert(channel_id, channel_status);
 
         // Calculate user status
-        let has_warnings = data
-            .warnings
+        let has_warnings = !data
+            .warnings_by_user
+            .ids(guild_id.get(), user_id.get())
+            .is_empty();
+
+        let has_enforcements = data
+            .user_enforcements(user_id.get(), guild_id.get())
             .iter()
-            .any(|w| w.value().user_id == user_id.get() && w.value().guild_id == guild_id.get());
-
-        let has_enforcements = data.active_enforcements.iter().any(|e| {
-            e.value().user_id == user_id.get()
-                && e.value().guild_id == guild_id.get()
-                && e.value().state == EnforcementState::Active
-        }) || data.pending_enforcements.iter().any(|e| {
-            e.value().user_id == user_id.get()
-                && e.value().guild_id == guild_id.get()
-                && e.value().state == EnforcementState::Pending
-        });
+            .any(|e| {
+                matches!(
+                    e.state,
+                    EnforcementState::Active | EnforcementState::Pending
+                )
+            });
 
         let warning_score = data.calculate_warning_score(user_id.get(), guild_id.get());
 
@@ -418,7 +420,7 @@ pub fn format_active_channels(bot_status: &BotStatus, data: &Data) -> String {
 
             // Try to get guild name from cache
             let guild_name = data
-                .cache
+                .get_cache()
                 .guild(channel.guild_id)
                 .map(|g| g.name.clone())
                 .unwrap_or_else(|| format!("Guild {}", channel.guild_id));
@@ -471,6 +473,7 @@ pub async fn format_problematic_users(
 
     let mut result = String::new();
     result.push_str("## Users with Warnings or Enforcements\n\n");
+    let now = Utc::now();
 
     // Sort by warning score (highest first)
     let mut users = problematic_users;
@@ -483,7 +486,7 @@ pub async fn format_problematic_users(
     for user in users {
         // Try to get user name from cache
         let user_name = data
-            .cache
+            .get_cache()
             .user(user.user_id)
             .map(|u| u.name.clone())
             .unwrap_or_else(|| format!("User {}", user.user_id));
@@ -513,11 +516,18 @@ pub async fn format_problematic_users(
             "⚪"
         };
 
-        // Add user info with score
+        // Add user info with score and when they'll be in the clear
         result.push_str(&format!(
             "- {status} **{user_name}** (Score: {:.2}){channel_info}",
             user.warning_score
         ));
+        let forecasts = data.forecast(user.user_id.get(), user.guild_id.get(), now);
+        if !forecasts.is_empty() {
+            result.push_str(&format!(
+                ", in the clear {}",
+                scoring::format_forecast(ScoreForecast::all_clear(&forecasts), now)
+            ));
+        }
 
         result.push('\n');
     }
@@ -555,7 +565,7 @@ pub fn format_enforcement_status(data: &Data) -> String {
         for enforcement in pending {
             let user_id = enforcement.user_id;
             let user_name = data
-                .cache
+                .get_cache()
                 .user(UserId::new(user_id))
                 .map(|u| u.name.clone())
                 .unwrap_or_else(|| format!("User {user_id}"));
@@ -579,7 +589,7 @@ pub fn format_enforcement_status(data: &Data) -> String {
         for enforcement in active {
             let user_id = enforcement.user_id;
             let user_name = data
-                .cache
+                .get_cache()
                 .user(UserId::new(user_id))
                 .map(|u| u.name.clone())
                 .unwrap_or_else(|| format!("User {user_id}"));
@@ -796,7 +806,7 @@ pub fn _create_status_embed(bot_status: &BotStatus, data: &Data) -> CreateEmbed
         let mut user_list = String::new();
         for user in top_users.iter().take(5) {
             let user_name = data
-                .cache
+                .get_cache()
                 .user(user.user_id)
                 .map(|u| u.name.clone())
                 .unwrap_or_else(|| format!("User {}", user.user_id));
@@ -0,0 +1,328 @@
+//! Persistence backends for the bot's moderation data
+//!
+//! The [`Storage`] trait covers guild configs, warnings, enforcements and user
+//! warning states. [`YamlStorage`] keeps the original one-file-per-collection
+//! layout under `data/`, while [`SqliteStorage`] keeps everything in an embedded
+//! database so that each changed record is written on its own. [`JournalStorage`]
+//! appends every change to a journal of events and keeps the full history.
+
+pub mod atomic;
+mod journal;
+pub mod migrations;
+mod sqlite;
+mod yaml;
+
+pub use journal::JournalStorage;
+pub use sqlite::SqliteStorage;
+pub use yaml::YamlStorage;
+
+use crate::Error;
+use crate::data::{GuildConfig, PendingEnforcement, UserWarningState, Warning};
+use dashmap::DashSet;
+use poise::serenity_prelude as serenity;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Default directory for persisted data
+pub const DEFAULT_DATA_DIR: &str = "data";
+/// File name of the `SQLite` database inside the data directory
+pub const SQLITE_FILE: &str = "daemon.sqlite3";
+/// Directory of the journal inside the data directory
+pub const JOURNAL_DIR: &str = "journal";
+
+/// Everything the bot persists, as plain records
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub guild_configs: Vec<GuildConfig>,
+    pub warnings: Vec<Warning>,
+    /// Pending, active and completed enforcements together
+    pub enforcements: Vec<PendingEnforcement>,
+    pub user_warning_states: Vec<UserWarningState>,
+    /// Damaged data found while loading; the records above are what could be salvaged
+    pub corruption: Vec<Corruption>,
+}
+
+/// Damaged data found while loading a collection
+#[derive(Debug, Clone)]
+pub struct Corruption {
+    /// File or table the data came from
+    pub source: String,
+    /// Why it couldn't be read
+    pub error: String,
+    /// Records recovered from the damaged data or a backup
+    pub salvaged: usize,
+    /// Records known to be lost, if they could be counted
+    pub lost: Option<usize>,
+    /// Where the damaged data was moved to
+    pub quarantined_to: Option<String>,
+}
+
+impl std::fmt::Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({} records salvaged",
+            self.source, self.error, self.salvaged
+        )?;
+        if let Some(lost) = self.lost {
+            write!(f, ", {lost} lost")?;
+        }
+        write!(f, ")")?;
+        if let Some(quarantined_to) = &self.quarantined_to {
+            write!(f, ", quarantined to {quarantined_to}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What to do on startup when persisted data turns out to be damaged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptionPolicy {
+    /// Refuse to start
+    #[default]
+    Fail,
+    /// Start with whatever could be salvaged, but never write anything back
+    ReadOnly,
+    /// Start with whatever could be salvaged and keep saving on top of it
+    Salvage,
+}
+
+impl CorruptionPolicy {
+    /// Read the policy from `DATA_ON_CORRUPTION` (`fail`, `read_only` or `salvage`)
+    ///
+    /// # Errors
+    /// Returns an error if the variable holds an unknown policy.
+    pub fn from_env() -> Result<Self, Error> {
+        match std::env::var("DATA_ON_CORRUPTION") {
+            Err(_) => Ok(Self::default()),
+            Ok(value) => match value.to_lowercase().as_str() {
+                "fail" => Ok(Self::Fail),
+                "read_only" | "readonly" => Ok(Self::ReadOnly),
+                "salvage" => Ok(Self::Salvage),
+                other => Err(format!(
+                    "Unknown DATA_ON_CORRUPTION policy: {other} (expected fail, read_only or salvage)"
+                )
+                .into()),
+            },
+        }
+    }
+}
+
+/// A batch of record-level changes to write to a backend
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    pub guild_configs: Vec<GuildConfig>,
+    pub warnings: Vec<Warning>,
+    pub removed_warnings: Vec<String>,
+    pub enforcements: Vec<PendingEnforcement>,
+    pub removed_enforcements: Vec<String>,
+    pub user_warning_states: Vec<UserWarningState>,
+    /// Keys in the `user_id:guild_id` format
+    pub removed_user_warning_states: Vec<String>,
+}
+
+impl ChangeSet {
+    /// Returns true if there is nothing to write
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.guild_configs.is_empty()
+            && self.warnings.is_empty()
+            && self.removed_warnings.is_empty()
+            && self.enforcements.is_empty()
+            && self.removed_enforcements.is_empty()
+            && self.user_warning_states.is_empty()
+            && self.removed_user_warning_states.is_empty()
+    }
+}
+
+/// Every record keyed the way it is stored, for backends that need the whole
+/// data set in memory to rewrite it
+#[derive(Debug, Default)]
+pub(crate) struct Records {
+    pub guild_configs: BTreeMap<u64, GuildConfig>,
+    pub warnings: BTreeMap<String, Warning>,
+    pub enforcements: BTreeMap<String, PendingEnforcement>,
+    pub user_warning_states: BTreeMap<String, UserWarningState>,
+}
+
+impl Records {
+    pub fn insert_guild_config(&mut self, config: GuildConfig) {
+        self.guild_configs.insert(config.guild_id, config);
+    }
+
+    pub fn insert_warning(&mut self, warning: Warning) {
+        self.warnings.insert(warning.id.clone(), warning);
+    }
+
+    pub fn insert_enforcement(&mut self, enforcement: PendingEnforcement) {
+        self.enforcements
+            .insert(enforcement.id.clone(), enforcement);
+    }
+
+    pub fn insert_user_warning_state(&mut self, state: UserWarningState) {
+        let key = user_state_key(state.user_id, state.guild_id);
+        self.user_warning_states.insert(key, state);
+    }
+
+    /// Apply a batch of changes
+    pub fn apply(&mut self, changes: ChangeSet) {
+        for config in changes.guild_configs {
+            self.insert_guild_config(config);
+        }
+        for warning in changes.warnings {
+            self.insert_warning(warning);
+        }
+        for id in &changes.removed_warnings {
+            self.warnings.remove(id);
+        }
+        for enforcement in changes.enforcements {
+            self.insert_enforcement(enforcement);
+        }
+        for id in &changes.removed_enforcements {
+            self.enforcements.remove(id);
+        }
+        for state in changes.user_warning_states {
+            self.insert_user_warning_state(state);
+        }
+        for key in &changes.removed_user_warning_states {
+            self.user_warning_states.remove(key);
+        }
+    }
+
+    /// Copy every record out as a snapshot
+    pub fn to_snapshot(&self) -> Snapshot {
+        Snapshot {
+            guild_configs: self.guild_configs.values().cloned().collect(),
+            warnings: self.warnings.values().cloned().collect(),
+            enforcements: self.enforcements.values().cloned().collect(),
+            user_warning_states: self.user_warning_states.values().cloned().collect(),
+            corruption: Vec::new(),
+        }
+    }
+}
+
+/// A persistence backend for the bot's data
+#[serenity::async_trait]
+pub trait Storage: Send + Sync {
+    /// Short name of the backend, used in logs
+    fn name(&self) -> &'static str;
+
+    /// Load every persisted record
+    ///
+    /// Missing data is not an error and loads as empty. Damaged data is moved
+    /// aside, whatever can be salvaged is returned, and the damage is listed in
+    /// [`Snapshot::corruption`].
+    ///
+    /// # Errors
+    /// Returns an error if the backend cannot be read at all.
+    async fn load(&self) -> Result<Snapshot, Error>;
+
+    /// Write a batch of changed records
+    ///
+    /// # Errors
+    /// Returns an error if the changes cannot be written.
+    async fn apply(&self, changes: ChangeSet) -> Result<(), Error>;
+
+    /// Replace damaged data with the records salvaged by the last load
+    ///
+    /// Until this is called, damaged data stays where it is and is reported
+    /// again on every load.
+    ///
+    /// # Errors
+    /// Returns an error if the salvaged records cannot be written.
+    async fn repair(&self) -> Result<(), Error>;
+
+    /// Where records removed from the live data can still be found, if anywhere
+    ///
+    /// Erasing a user only removes the live records, so this is shown to the
+    /// moderator to say what the erasure didn't reach.
+    fn retained_copies(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Keys of records that changed since the last save
+#[derive(Debug, Default)]
+pub struct DirtyRecords {
+    pub guild_configs: DashSet<u64>,
+    pub warnings: DashSet<String>,
+    pub enforcements: DashSet<String>,
+    /// Keys in the `user_id:guild_id` format
+    pub user_warning_states: DashSet<String>,
+    /// Woken whenever a record is marked, to schedule a save
+    pub changed: Notify,
+}
+
+/// Take every key out of a dirty set
+pub(crate) fn drain<K>(set: &DashSet<K>) -> Vec<K>
+where
+    K: Eq + std::hash::Hash + Clone,
+{
+    let keys: Vec<K> = set.iter().map(|key| key.key().clone()).collect();
+    for key in &keys {
+        set.remove(key);
+    }
+    keys
+}
+
+/// Create the storage backend selected by the environment
+///
+/// `DATA_STORAGE` picks the backend (`yaml`, the default, `sqlite` or `journal`)
+/// and `DATA_DIR` the directory it lives in (defaults to `data`).
+/// `DATA_BACKUP_GENERATIONS` sets how many backups the YAML backend keeps per file.
+/// `DATA_JOURNAL_COMPACT_EVERY` sets how many entries the journal takes between
+/// snapshots, and `DATA_JOURNAL_REPLAY_UNTIL` (an RFC 3339 time) recovers the
+/// journaled data as it was at that time.
+///
+/// # Errors
+/// Returns an error if the backend name is unknown or the database cannot be opened.
+pub fn from_env() -> Result<Arc<dyn Storage>, Error> {
+    let dir = std::env::var("DATA_DIR").unwrap_or_else(|_| DEFAULT_DATA_DIR.to_string());
+    let backend = std::env::var("DATA_STORAGE").unwrap_or_else(|_| "yaml".to_string());
+
+    match backend.to_lowercase().as_str() {
+        "yaml" => {
+            let generations = match std::env::var("DATA_BACKUP_GENERATIONS") {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|e| format!("Invalid DATA_BACKUP_GENERATIONS {value}: {e}"))?,
+                Err(_) => atomic::DEFAULT_BACKUP_GENERATIONS,
+            };
+            Ok(Arc::new(YamlStorage::with_backup_generations(
+                dir,
+                generations,
+            )))
+        }
+        "sqlite" => {
+            let path = std::path::Path::new(&dir).join(SQLITE_FILE);
+            Ok(Arc::new(SqliteStorage::open(path)?))
+        }
+        "journal" => {
+            let compact_every = match std::env::var("DATA_JOURNAL_COMPACT_EVERY") {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|e| format!("Invalid DATA_JOURNAL_COMPACT_EVERY {value}: {e}"))?,
+                Err(_) => journal::DEFAULT_COMPACT_EVERY,
+            };
+            let mut storage =
+                JournalStorage::new(std::path::Path::new(&dir).join(JOURNAL_DIR), compact_every);
+            if let Ok(value) = std::env::var("DATA_JOURNAL_REPLAY_UNTIL") {
+                let until = chrono::DateTime::parse_from_rfc3339(&value)
+                    .map_err(|e| format!("Invalid DATA_JOURNAL_REPLAY_UNTIL {value}: {e}"))?;
+                storage = storage.replay_until(until.with_timezone(&chrono::Utc));
+            }
+            Ok(Arc::new(storage))
+        }
+        other => Err(format!(
+            "Unknown DATA_STORAGE backend: {other} (expected yaml, sqlite or journal)"
+        )
+        .into()),
+    }
+}
+
+/// Key used for a user's warning state
+#[must_use]
+pub fn user_state_key(user_id: u64, guild_id: u64) -> String {
+    format!("{user_id}:{guild_id}")
+}
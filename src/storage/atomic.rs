@@ -0,0 +1,241 @@
+//! Crash-safe file writes with rotating backup generations
+//!
+//! A save writes to a temporary file, syncs it to disk and renames it over the
+//! primary file, so a crash or a full disk never leaves a truncated file behind.
+//! The previous primary is kept as `<file>.1`, the one before as `<file>.2`, and
+//! so on up to the configured number of generations. Files that turn out to
+//! be damaged are moved into a `quarantine` directory before anything can
+//! overwrite them.
+
+use crate::Error;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Default number of backup generations kept per file
+pub const DEFAULT_BACKUP_GENERATIONS: usize = 3;
+/// Directory damaged files are moved to, next to the files themselves
+pub const QUARANTINE_DIR: &str = "quarantine";
+/// Format of the time appended to a quarantined file's name
+const QUARANTINE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Path of the n-th backup generation of a file (1 is the newest)
+#[must_use]
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{generation}"));
+    PathBuf::from(name)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Atomically replace a file's contents, keeping older versions as backups
+///
+/// # Errors
+/// Returns an error if the temporary file cannot be written and synced, or if
+/// it cannot be renamed into place. The primary file is untouched in that case.
+pub async fn write_atomic(path: &Path, contents: &[u8], generations: usize) -> Result<(), Error> {
+    let tmp = temp_path(path);
+
+    // Write and flush the new contents before touching anything else
+    let mut file = tokio::fs::File::create(&tmp).await?;
+    if let Err(e) = write_and_sync(&mut file, contents).await {
+        drop(file);
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    drop(file);
+
+    if generations > 0 && tokio::fs::try_exists(path).await.unwrap_or(false) {
+        rotate_backups(path, generations).await?;
+    }
+
+    tokio::fs::rename(&tmp, path).await?;
+    sync_parent_dir(path).await;
+    Ok(())
+}
+
+async fn write_and_sync(file: &mut tokio::fs::File, contents: &[u8]) -> Result<(), Error> {
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Shift `<file>.n` to `<file>.n+1`, dropping the oldest, and move the primary to `<file>.1`
+async fn rotate_backups(path: &Path, generations: usize) -> Result<(), Error> {
+    let oldest = backup_path(path, generations);
+    if tokio::fs::try_exists(&oldest).await.unwrap_or(false) {
+        tokio::fs::remove_file(&oldest).await?;
+    }
+
+    for generation in (1..generations).rev() {
+        let from = backup_path(path, generation);
+        if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+            tokio::fs::rename(&from, backup_path(path, generation + 1)).await?;
+        }
+    }
+
+    tokio::fs::rename(path, backup_path(path, 1)).await?;
+    Ok(())
+}
+
+/// Make the renames durable. Not every platform allows syncing a directory,
+/// so failures here are ignored.
+async fn sync_parent_dir(path: &Path) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    if let Ok(dir) = tokio::fs::File::open(dir).await {
+        let _ = dir.sync_all().await;
+    }
+}
+
+/// Read and parse the newest backup generation of a file that parses
+///
+/// Returns the backup's path along with its contents, or `None` if no backup
+/// can be read.
+pub async fn read_newest_backup<T, F>(
+    path: &Path,
+    generations: usize,
+    parse: F,
+) -> Option<(PathBuf, T)>
+where
+    F: Fn(&str) -> Result<T, Error>,
+{
+    for generation in 1..=generations {
+        let backup = backup_path(path, generation);
+        let Ok(contents) = tokio::fs::read_to_string(&backup).await else {
+            continue;
+        };
+        match parse(&contents) {
+            Ok(value) => return Some((backup, value)),
+            Err(e) => warn!("Backup {} is also unreadable: {e}", backup.display()),
+        }
+    }
+    None
+}
+
+/// Move a damaged file into the `quarantine` directory next to it
+///
+/// The file keeps its name with the current time appended, so repeated
+/// quarantines of the same file never overwrite each other.
+///
+/// # Errors
+/// Returns an error if the quarantine directory can't be created or the file can't be moved.
+pub async fn quarantine(path: &Path) -> Result<PathBuf, Error> {
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(QUARANTINE_DIR);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let target = dir.join(format!(
+        "{}.{}",
+        file_name(path),
+        chrono::Utc::now().format(QUARANTINE_TIME_FORMAT)
+    ));
+    tokio::fs::rename(path, &target).await?;
+    Ok(target)
+}
+
+/// Find the quarantined copy of a missing file whose damage hasn't been repaired
+///
+/// Quarantine moves a damaged file away, so without this the next load would
+/// find it missing and quietly start from a backup or from nothing. The newest
+/// copy counts until the file is written again, which leaves a backup newer
+/// than the quarantine.
+pub async fn unrepaired_quarantine(path: &Path) -> Option<PathBuf> {
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(QUARANTINE_DIR);
+    let prefix = format!("{}.", file_name(path));
+    let mut entries = tokio::fs::read_dir(&dir).await.ok()?;
+
+    let mut newest: Option<(chrono::NaiveDateTime, PathBuf)> = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(at) = name
+            .strip_prefix(&prefix)
+            .and_then(|at| chrono::NaiveDateTime::parse_from_str(at, QUARANTINE_TIME_FORMAT).ok())
+        else {
+            continue;
+        };
+        if newest.as_ref().is_none_or(|(newest, _)| at > *newest) {
+            newest = Some((at, entry.path()));
+        }
+    }
+    let (quarantined_at, quarantined) = newest?;
+
+    let written_since = tokio::fs::metadata(backup_path(path, 1))
+        .await
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| {
+            chrono::DateTime::<chrono::Utc>::from(modified).naive_utc() > quarantined_at
+        });
+    (!written_since).then_some(quarantined)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(contents: &str) -> Result<u32, Error> {
+        contents.trim().parse::<u32>().map_err(Error::from)
+    }
+
+    #[tokio::test]
+    async fn test_rotation_and_fallback() {
+        let dir = std::env::temp_dir().join(format!("daemon-atomic-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("value.txt");
+
+        for value in 1..=4 {
+            write_atomic(&path, value.to_string().as_bytes(), 2)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "4");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 1)).unwrap(), "3");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 2)).unwrap(), "2");
+        assert!(!backup_path(&path, 3).exists());
+
+        // A damaged newest backup falls back to the one before it
+        std::fs::write(backup_path(&path, 1), "not a number").unwrap();
+        let (backup, value) = read_newest_backup(&path, 2, parse_number).await.unwrap();
+        assert_eq!(backup, backup_path(&path, 2));
+        assert_eq!(value, 2);
+        assert!(
+            read_newest_backup(&dir.join("missing.txt"), 2, parse_number)
+                .await
+                .is_none()
+        );
+
+        // Quarantine times are kept to the millisecond
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let quarantined = quarantine(&path).await.unwrap();
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(&quarantined).unwrap(), "4");
+        assert_eq!(unrepaired_quarantine(&path).await, Some(quarantined));
+
+        // Writing the file again repairs it
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        write_atomic(&path, b"5", 2).await.unwrap();
+        write_atomic(&path, b"6", 2).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(unrepaired_quarantine(&path).await, None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
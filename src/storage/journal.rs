@@ -0,0 +1,775 @@
+use super::atomic;
+use super::migrations::{self, Collection, SCHEMA_VERSION};
+use super::{ChangeSet, Corruption, Records, Snapshot, Storage};
+use crate::Error;
+use crate::data::{EnforcementState, GuildConfig, PendingEnforcement, UserWarningState, Warning};
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// Default number of journal entries after which the journal is compacted
+pub const DEFAULT_COMPACT_EVERY: usize = 1000;
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const EVENTS_PREFIX: &str = "events-";
+
+/// A single change to the moderation data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JournalEvent {
+    ConfigChanged { config: GuildConfig },
+    WarningCreated { warning: Warning },
+    WarningRemoved { id: String },
+    EnforcementScheduled { enforcement: PendingEnforcement },
+    EnforcementExecuted { enforcement: PendingEnforcement },
+    EnforcementReversed { enforcement: PendingEnforcement },
+    EnforcementCancelled { enforcement: PendingEnforcement },
+    EnforcementFailed { enforcement: PendingEnforcement },
+    EnforcementRemoved { id: String },
+    WarningStateChanged { state: UserWarningState },
+    WarningStateRemoved { key: String },
+}
+
+impl JournalEvent {
+    fn for_enforcement(enforcement: PendingEnforcement) -> Self {
+        match enforcement.state {
+            EnforcementState::Pending => Self::EnforcementScheduled { enforcement },
+            EnforcementState::Active | EnforcementState::Completed => {
+                Self::EnforcementExecuted { enforcement }
+            }
+            EnforcementState::Reversed => Self::EnforcementReversed { enforcement },
+            EnforcementState::Cancelled => Self::EnforcementCancelled { enforcement },
+            EnforcementState::Failed => Self::EnforcementFailed { enforcement },
+        }
+    }
+
+    /// Turn a batch of changes into events, in the order they should be replayed
+    fn from_changes(changes: ChangeSet) -> Vec<Self> {
+        let mut events = Vec::new();
+        events.extend(
+            changes
+                .guild_configs
+                .into_iter()
+                .map(|config| Self::ConfigChanged { config }),
+        );
+        events.extend(
+            changes
+                .warnings
+                .into_iter()
+                .map(|warning| Self::WarningCreated { warning }),
+        );
+        events.extend(
+            changes
+                .removed_warnings
+                .into_iter()
+                .map(|id| Self::WarningRemoved { id }),
+        );
+        events.extend(changes.enforcements.into_iter().map(Self::for_enforcement));
+        events.extend(
+            changes
+                .removed_enforcements
+                .into_iter()
+                .map(|id| Self::EnforcementRemoved { id }),
+        );
+        events.extend(
+            changes
+                .user_warning_states
+                .into_iter()
+                .map(|state| Self::WarningStateChanged { state }),
+        );
+        events.extend(
+            changes
+                .removed_user_warning_states
+                .into_iter()
+                .map(|key| Self::WarningStateRemoved { key }),
+        );
+        events
+    }
+
+    /// Apply the event to a set of records
+    fn replay(self, records: &mut Records) {
+        match self {
+            Self::ConfigChanged { config } => records.insert_guild_config(config),
+            Self::WarningCreated { warning } => records.insert_warning(warning),
+            Self::WarningRemoved { id } => {
+                records.warnings.remove(&id);
+            }
+            Self::EnforcementScheduled { enforcement }
+            | Self::EnforcementExecuted { enforcement }
+            | Self::EnforcementReversed { enforcement }
+            | Self::EnforcementCancelled { enforcement }
+            | Self::EnforcementFailed { enforcement } => {
+                records.insert_enforcement(enforcement);
+            }
+            Self::EnforcementRemoved { id } => {
+                records.enforcements.remove(&id);
+            }
+            Self::WarningStateChanged { state } => records.insert_user_warning_state(state),
+            Self::WarningStateRemoved { key } => {
+                records.user_warning_states.remove(&key);
+            }
+        }
+    }
+}
+
+/// One line of the journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    at: DateTime<Utc>,
+    schema_version: u32,
+    #[serde(flatten)]
+    event: JournalEvent,
+}
+
+/// Field holding the record of each collection inside an event
+const EVENT_RECORD_FIELDS: &[(&str, Collection)] = &[
+    ("config", Collection::GuildConfigs),
+    ("warning", Collection::Warnings),
+    ("enforcement", Collection::Enforcements),
+    ("state", Collection::UserWarningStates),
+];
+
+impl JournalEntry {
+    /// Parse a journal line, upgrading the record it carries to the current schema
+    fn parse(line: &str) -> Result<Self, Error> {
+        let mut value: Value = serde_json::from_str(line)?;
+        let version = value
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or("journal entry has no schema_version")?;
+
+        if version != SCHEMA_VERSION {
+            for &(field, collection) in EVENT_RECORD_FIELDS {
+                let Some(record) = value.get_mut(field) else {
+                    continue;
+                };
+                let migrated = migrations::migrate(collection, version, vec![record.take()])?;
+                *record = migrated.into_iter().next().unwrap_or_default();
+            }
+            value["schema_version"] = Value::from(SCHEMA_VERSION);
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Contents of a compaction snapshot
+#[derive(Serialize)]
+struct SnapshotFile<'a> {
+    schema_version: u32,
+    taken_at: DateTime<Utc>,
+    /// Time a point-in-time recovery restored, if this snapshot was made by one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovered_as_of: Option<DateTime<Utc>>,
+    guild_configs: Vec<&'a GuildConfig>,
+    warnings: Vec<&'a Warning>,
+    enforcements: Vec<&'a PendingEnforcement>,
+    user_warning_states: Vec<&'a UserWarningState>,
+}
+
+/// A compaction snapshot read back before its records are migrated
+#[derive(Deserialize)]
+struct RawSnapshotFile {
+    schema_version: u32,
+    taken_at: DateTime<Utc>,
+    #[serde(default)]
+    recovered_as_of: Option<DateTime<Utc>>,
+    guild_configs: Vec<Value>,
+    warnings: Vec<Value>,
+    enforcements: Vec<Value>,
+    user_warning_states: Vec<Value>,
+}
+
+impl RawSnapshotFile {
+    fn into_records(self) -> Result<Records, Error> {
+        let version = self.schema_version;
+        let mut records = Records::default();
+        for config in
+            migrations::migrate_into(Collection::GuildConfigs, version, self.guild_configs)?
+        {
+            records.insert_guild_config(config);
+        }
+        for warning in migrations::migrate_into(Collection::Warnings, version, self.warnings)? {
+            records.insert_warning(warning);
+        }
+        for enforcement in
+            migrations::migrate_into(Collection::Enforcements, version, self.enforcements)?
+        {
+            records.insert_enforcement(enforcement);
+        }
+        for state in migrations::migrate_into(
+            Collection::UserWarningStates,
+            version,
+            self.user_warning_states,
+        )? {
+            records.insert_user_warning_state(state);
+        }
+        Ok(records)
+    }
+}
+
+/// The readable entries of a generation's journal
+#[derive(Default)]
+struct EventsFile {
+    entries: Vec<JournalEntry>,
+    /// Length of the file without an incomplete last line, if it has one
+    valid_len: Option<u64>,
+    corruption: Option<Corruption>,
+}
+
+/// Current state of the journal
+#[derive(Default)]
+struct JournalState {
+    loaded: bool,
+    /// Length to cut the current generation's journal to before appending
+    valid_len: Option<u64>,
+    /// Damage found in the replayed journal, which blocks appending until repaired
+    corruption: Vec<Corruption>,
+    records: Records,
+    /// Generation that new entries are appended to
+    generation: u64,
+    /// Newest generation on disk, which can be later than the current one
+    /// after a point-in-time recovery
+    newest_generation: u64,
+    /// Entries appended to the current generation so far
+    entries: usize,
+}
+
+/// Storage backend that appends every change to a journal
+///
+/// Each change is written as a timestamped event (warning created, enforcement
+/// scheduled, executed, reversed or cancelled, config changed, ...), and
+/// loading replays the events on top of the last snapshot. Every
+/// `compact_every` entries a new snapshot is taken and a new generation of the
+/// journal is started. Old generations are kept, so the full history of every
+/// record stays on disk and the data can be rebuilt as it was at any point in
+/// time.
+pub struct JournalStorage {
+    dir: PathBuf,
+    compact_every: usize,
+    replay_until: Option<DateTime<Utc>>,
+    state: Mutex<JournalState>,
+}
+
+impl JournalStorage {
+    /// Create a journal backend rooted at the given directory
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>, compact_every: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            compact_every: compact_every.max(1),
+            replay_until: None,
+            state: Mutex::new(JournalState::default()),
+        }
+    }
+
+    /// Rebuild the data as it was at the given time instead of replaying everything
+    ///
+    /// The recovered state becomes a new snapshot when it is loaded, and changes
+    /// from then on are journaled on top of it. The history after that point stays
+    /// in the older generations. Once a recovery to `until` has been made, loading
+    /// with the same time replays everything as usual.
+    #[must_use]
+    pub fn replay_until(mut self, until: DateTime<Utc>) -> Self {
+        self.replay_until = Some(until);
+        self
+    }
+
+    fn snapshot_path(&self, generation: u64) -> PathBuf {
+        self.dir
+            .join(format!("{SNAPSHOT_PREFIX}{generation:08}.json"))
+    }
+
+    fn events_path(&self, generation: u64) -> PathBuf {
+        self.dir
+            .join(format!("{EVENTS_PREFIX}{generation:08}.jsonl"))
+    }
+
+    /// Every generation on disk, oldest first
+    async fn generations(&self) -> Result<Vec<u64>, Error> {
+        let mut generations = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(generations),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let generation = name
+                .strip_prefix(SNAPSHOT_PREFIX)
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .or_else(|| {
+                    name.strip_prefix(EVENTS_PREFIX)
+                        .and_then(|rest| rest.strip_suffix(".jsonl"))
+                })
+                .and_then(|number| number.parse::<u64>().ok());
+            if let Some(generation) = generation {
+                generations.push(generation);
+            }
+        }
+
+        generations.sort_unstable();
+        generations.dedup();
+        Ok(generations)
+    }
+
+    /// Read the snapshot a generation starts from, or nothing if it starts empty
+    async fn read_snapshot(&self, generation: u64) -> Result<Option<RawSnapshotFile>, Error> {
+        match tokio::fs::read_to_string(self.snapshot_path(generation)).await {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read every entry of a generation's journal
+    ///
+    /// Unreadable entries are skipped, and the file is moved into quarantine
+    /// and reported as damaged; until the journal is repaired, later loads read
+    /// the quarantined copy. An incomplete last line is what a crash in the
+    /// middle of an append leaves behind, so it is dropped without a report.
+    async fn read_entries(&self, generation: u64) -> Result<EventsFile, Error> {
+        let path = self.events_path(generation);
+        let (contents, quarantined_to) = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => (contents, None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let Some(quarantined) = atomic::unrepaired_quarantine(&path).await else {
+                    return Ok(EventsFile::default());
+                };
+                (
+                    tokio::fs::read_to_string(&quarantined).await?,
+                    Some(quarantined),
+                )
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut file = EventsFile::default();
+        let complete = match contents.rfind('\n') {
+            Some(end) => &contents[..=end],
+            None => "",
+        };
+        if complete.len() < contents.len() && quarantined_to.is_none() {
+            warn!("Ignoring incomplete last entry of {}", path.display());
+            file.valid_len = Some(complete.len() as u64);
+        }
+
+        let mut lost = 0;
+        let mut first_error = None;
+        for (i, line) in complete.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match JournalEntry::parse(line) {
+                Ok(entry) => file.entries.push(entry),
+                Err(e) => {
+                    error!("{} line {} is damaged: {e}", path.display(), i + 1);
+                    lost += 1;
+                    first_error.get_or_insert_with(|| format!("line {}: {e}", i + 1));
+                }
+            }
+        }
+
+        if let Some(error) = first_error {
+            let quarantined_to = match quarantined_to {
+                Some(quarantined) => quarantined,
+                None => atomic::quarantine(&path).await?,
+            };
+            file.corruption = Some(Corruption {
+                source: path.display().to_string(),
+                error,
+                salvaged: file.entries.len(),
+                lost: Some(lost),
+                quarantined_to: Some(quarantined_to.display().to_string()),
+            });
+        }
+        Ok(file)
+    }
+
+    /// Whether a generation was already started by recovering the data as of `until`
+    async fn recovered(&self, until: DateTime<Utc>) -> Result<bool, Error> {
+        for generation in self.generations().await? {
+            let snapshot = self.read_snapshot(generation).await?;
+            if snapshot.is_some_and(|snapshot| snapshot.recovered_as_of == Some(until)) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Rebuild the records from the newest generation that starts before `until`
+    async fn replay(
+        &self,
+        state: &mut JournalState,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let mut generations = self.generations().await?;
+        if generations.is_empty() {
+            generations.push(0);
+        }
+
+        // Pick the newest generation whose snapshot is old enough
+        let mut start = None;
+        for &generation in generations.iter().rev() {
+            let snapshot = self.read_snapshot(generation).await?;
+            let usable = match (&snapshot, until) {
+                (Some(snapshot), Some(until)) => snapshot.taken_at <= until,
+                _ => true,
+            };
+            if usable {
+                start = Some((generation, snapshot));
+                break;
+            }
+        }
+
+        let Some((generation, snapshot)) = start else {
+            return Err("No journal generation starts early enough to replay to that time".into());
+        };
+
+        state.records = match snapshot {
+            Some(snapshot) => snapshot.into_records()?,
+            None => Records::default(),
+        };
+        state.generation = generation;
+        state.newest_generation = generations.last().copied().unwrap_or(generation);
+        state.entries = 0;
+
+        let file = self.read_entries(generation).await?;
+        state.valid_len = file.valid_len;
+        state.corruption = file.corruption.into_iter().collect();
+        for entry in file.entries {
+            if until.is_some_and(|until| entry.at > until) {
+                break;
+            }
+            entry.event.replay(&mut state.records);
+            state.entries += 1;
+        }
+
+        info!(
+            "Replayed {} journal entries from generation {generation}",
+            state.entries
+        );
+        Ok(())
+    }
+
+    /// Snapshot the current records and start a new generation of the journal
+    ///
+    /// `recovered_as_of` marks a snapshot made by a point-in-time recovery.
+    async fn compact(
+        &self,
+        state: &mut JournalState,
+        recovered_as_of: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let generation = state.newest_generation + 1;
+        let records = &state.records;
+        let snapshot = SnapshotFile {
+            schema_version: SCHEMA_VERSION,
+            taken_at: Utc::now(),
+            recovered_as_of,
+            guild_configs: records.guild_configs.values().collect(),
+            warnings: records.warnings.values().collect(),
+            enforcements: records.enforcements.values().collect(),
+            user_warning_states: records.user_warning_states.values().collect(),
+        };
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let json = serde_json::to_string(&snapshot)?;
+        atomic::write_atomic(&self.snapshot_path(generation), json.as_bytes(), 0).await?;
+
+        info!(
+            "Compacted journal generation {} ({} entries) into a snapshot",
+            state.generation, state.entries
+        );
+        state.generation = generation;
+        state.newest_generation = generation;
+        state.entries = 0;
+        state.valid_len = None;
+        state.corruption.clear();
+        Ok(())
+    }
+
+    async fn load_state(&self, state: &mut JournalState) -> Result<(), Error> {
+        // A recovery only happens once, so a restart with the same time still set
+        // keeps everything journaled since
+        let until = match self.replay_until {
+            Some(until) if self.recovered(until).await? => {
+                warn!("Journal was already recovered as of {until}, replaying everything since");
+                None
+            }
+            until => until,
+        };
+        self.replay(state, until).await?;
+
+        // Later entries in the replayed generation must never be replayed on top of
+        // the recovered state, so continue in a fresh generation
+        if let Some(until) = until {
+            if !state.corruption.is_empty() {
+                return Err("Cannot recover a point in time from a damaged journal".into());
+            }
+            info!("Recovered journal state as of {until}");
+            self.compact(state, Some(until)).await?;
+        }
+
+        state.loaded = true;
+        Ok(())
+    }
+}
+
+/// Append lines to a file and flush them to disk
+async fn append(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(contents).await?;
+    file.sync_data().await?;
+    Ok(())
+}
+
+#[serenity::async_trait]
+impl Storage for JournalStorage {
+    fn name(&self) -> &'static str {
+        "journal"
+    }
+
+    fn retained_copies(&self) -> Option<&'static str> {
+        Some(
+            "the journal's event files and snapshots, since the current generation keeps the records until the next compaction and older generations are never deleted, and any damaged journals moved into the `quarantine` directory",
+        )
+    }
+
+    async fn load(&self) -> Result<Snapshot, Error> {
+        let mut state = self.state.lock().await;
+        self.load_state(&mut state).await?;
+        Ok(Snapshot {
+            corruption: state.corruption.clone(),
+            ..state.records.to_snapshot()
+        })
+    }
+
+    async fn repair(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().await;
+        if !state.loaded {
+            return Err("Cannot repair a journal that hasn't been replayed".into());
+        }
+        // The salvaged records become the snapshot of a new generation, so the
+        // damaged entries are never replayed again
+        self.compact(&mut state, None).await
+    }
+
+    async fn apply(&self, changes: ChangeSet) -> Result<(), Error> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().await;
+        // Never append to a journal we haven't replayed yet
+        if !state.loaded {
+            self.load_state(&mut state).await?;
+        }
+        if !state.corruption.is_empty() {
+            return Err("Refusing to append to a damaged journal before it is repaired".into());
+        }
+
+        let at = Utc::now();
+        let events = JournalEvent::from_changes(changes);
+        let mut lines = String::new();
+        for event in &events {
+            let entry = JournalEntry {
+                at,
+                schema_version: SCHEMA_VERSION,
+                event: event.clone(),
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.events_path(state.generation);
+        // Drop an incomplete entry left by a crash so new entries start on their own line
+        if let Some(valid_len) = state.valid_len.take() {
+            let file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .await?;
+            file.set_len(valid_len).await?;
+        }
+        append(&path, lines.as_bytes()).await?;
+
+        state.entries += events.len();
+        for event in events {
+            event.replay(&mut state.records);
+        }
+
+        if state.entries >= self.compact_every {
+            self.compact(&mut state, None).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::EnforcementAction;
+
+    fn enforcement(state: EnforcementState) -> PendingEnforcement {
+        PendingEnforcement::test(
+            "enf-id",
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+            state,
+        )
+    }
+
+    fn enforcement_changes(state: EnforcementState) -> ChangeSet {
+        ChangeSet {
+            enforcements: vec![enforcement(state)],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_journal_replay_compaction_and_point_in_time() {
+        let dir = std::env::temp_dir().join(format!("daemon-journal-{}", uuid::Uuid::new_v4()));
+        let storage = JournalStorage::new(&dir, 2);
+
+        storage
+            .apply(enforcement_changes(EnforcementState::Pending))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let scheduled = Utc::now();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        // The second entry triggers a compaction
+        storage
+            .apply(enforcement_changes(EnforcementState::Active))
+            .await
+            .unwrap();
+        storage
+            .apply(enforcement_changes(EnforcementState::Reversed))
+            .await
+            .unwrap();
+
+        let snapshot = JournalStorage::new(&dir, 2).load().await.unwrap();
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Reversed);
+
+        // Going back in time replays the first generation up to that point
+        let snapshot = JournalStorage::new(&dir, 2)
+            .replay_until(scheduled)
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Pending);
+
+        // and the recovered state is what a normal load sees from then on
+        let snapshot = JournalStorage::new(&dir, 2).load().await.unwrap();
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Pending);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_happens_once() {
+        let dir = std::env::temp_dir().join(format!("daemon-journal-{}", uuid::Uuid::new_v4()));
+        JournalStorage::new(&dir, 100)
+            .apply(enforcement_changes(EnforcementState::Pending))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let until = Utc::now();
+
+        let storage = JournalStorage::new(&dir, 100).replay_until(until);
+        storage.load().await.unwrap();
+        storage
+            .apply(enforcement_changes(EnforcementState::Active))
+            .await
+            .unwrap();
+
+        // Restarting with the same time still set keeps the change made since
+        let storage = JournalStorage::new(&dir, 100).replay_until(until);
+        let snapshot = storage.load().await.unwrap();
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Active);
+        assert_eq!(storage.generations().await.unwrap(), [0, 1]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_damaged_and_torn_entries() {
+        let dir = std::env::temp_dir().join(format!("daemon-journal-{}", uuid::Uuid::new_v4()));
+        let storage = JournalStorage::new(&dir, 100);
+        storage
+            .apply(enforcement_changes(EnforcementState::Pending))
+            .await
+            .unwrap();
+
+        // A crash in the middle of an append is dropped quietly
+        let path = storage.events_path(0);
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"at\":");
+        std::fs::write(&path, &contents).unwrap();
+
+        let storage = JournalStorage::new(&dir, 100);
+        let snapshot = storage.load().await.unwrap();
+        assert!(snapshot.corruption.is_empty());
+        storage
+            .apply(enforcement_changes(EnforcementState::Active))
+            .await
+            .unwrap();
+        let snapshot = JournalStorage::new(&dir, 100).load().await.unwrap();
+        assert!(snapshot.corruption.is_empty());
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Active);
+
+        // A damaged entry in the middle is reported and blocks appending until repaired
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, format!("garbage\n{contents}")).unwrap();
+        let storage = JournalStorage::new(&dir, 100);
+        let snapshot = storage.load().await.unwrap();
+        assert_eq!(snapshot.corruption.len(), 1);
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Active);
+        assert!(
+            storage
+                .apply(enforcement_changes(EnforcementState::Reversed))
+                .await
+                .is_err()
+        );
+
+        storage.repair().await.unwrap();
+        let snapshot = JournalStorage::new(&dir, 100).load().await.unwrap();
+        assert!(snapshot.corruption.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_old_journal_entry_is_migrated() {
+        let mut record = serde_json::to_value(enforcement(EnforcementState::Pending)).unwrap();
+        record.as_object_mut().unwrap().remove("state");
+        record["executed"] = Value::Bool(true);
+        let line = serde_json::json!({
+            "at": Utc::now(),
+            "schema_version": 1,
+            "event": "enforcement_executed",
+            "enforcement": record,
+        })
+        .to_string();
+
+        let entry = JournalEntry::parse(&line).unwrap();
+        let JournalEvent::EnforcementExecuted { enforcement } = entry.event else {
+            panic!("Expected an executed enforcement");
+        };
+        assert_eq!(enforcement.state, EnforcementState::Completed);
+    }
+}
@@ -0,0 +1,262 @@
+//! Versioned on-disk schema and the steps that upgrade old records
+//!
+//! Every persisted collection carries the schema version it was written with.
+//! On load, records written with an older version are passed through each
+//! migration step in order until they match the current structs, so new fields
+//! can ship without losing or corrupting existing records.
+//!
+//! Steps work on `serde_json::Value` records regardless of the backend; YAML
+//! values are converted first with [`yaml_to_json`].
+
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tracing::debug;
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Version of files written before the schema was versioned
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// The persisted collections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    GuildConfigs,
+    Warnings,
+    Enforcements,
+    UserWarningStates,
+}
+
+/// A single upgrade step from one schema version to the next
+pub struct Migration {
+    /// Version this step upgrades from, to `from + 1`
+    pub from: u32,
+    /// What the step changes
+    pub description: &'static str,
+    /// Rewrite one record of the given collection in place
+    pub apply: fn(Collection, &mut Map<String, Value>),
+}
+
+/// Every migration step, ordered by the version it upgrades from
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "derive enforcement state from the legacy executed flag",
+        apply: derive_enforcement_state,
+    },
+    Migration {
+        from: 2,
+        description: "restore active enforcements that older builds marked completed",
+        apply: restore_active_enforcements,
+    },
+];
+
+/// Records without a `state` predate the enforcement lifecycle, so the legacy
+/// `executed` flag is all we know about them.
+fn derive_enforcement_state(collection: Collection, record: &mut Map<String, Value>) {
+    if collection != Collection::Enforcements || record.contains_key("state") {
+        return;
+    }
+
+    let executed = record
+        .get("executed")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let state = if executed { "Completed" } else { "Pending" };
+    record.insert("state".to_string(), Value::String(state.to_string()));
+}
+
+/// Older builds forced every executed enforcement to `Completed` on load, which
+/// dropped timed mutes and deafens that were still waiting to be reversed.
+/// Only actions that need reversing get a `reverse_at`, so a completed record
+/// that has one but was never reversed was really still active.
+fn restore_active_enforcements(collection: Collection, record: &mut Map<String, Value>) {
+    if collection != Collection::Enforcements
+        || record.get("state").and_then(Value::as_str) != Some("Completed")
+    {
+        return;
+    }
+
+    let is_set = |field: &str| record.get(field).is_some_and(|value| !value.is_null());
+    if is_set("reverse_at") && !is_set("reversed_at") {
+        record.insert("state".to_string(), Value::String("Active".to_string()));
+    }
+}
+
+/// Upgrade records written with `version` to the current schema
+///
+/// # Errors
+/// Returns an error if the records were written by a newer schema or a record
+/// is not a map.
+pub fn migrate(
+    collection: Collection,
+    version: u32,
+    records: Vec<Value>,
+) -> Result<Vec<Value>, Error> {
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "{collection:?} were written with schema version {version}, but this build only understands up to {SCHEMA_VERSION}"
+        )
+        .into());
+    }
+
+    let steps: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.from >= version).collect();
+    if steps.is_empty() {
+        return Ok(records);
+    }
+    for step in &steps {
+        debug!(
+            "Migrating {collection:?} to schema version {}: {}",
+            step.from + 1,
+            step.description
+        );
+    }
+
+    records
+        .into_iter()
+        .map(|record| {
+            let Value::Object(mut map) = record else {
+                return Err(format!("{collection:?} record is not a map: {record}").into());
+            };
+            for step in &steps {
+                (step.apply)(collection, &mut map);
+            }
+            Ok(Value::Object(map))
+        })
+        .collect()
+}
+
+/// Upgrade records to the current schema and deserialize them
+///
+/// # Errors
+/// Returns an error if migration fails or a record doesn't match the current structs.
+pub fn migrate_into<T: DeserializeOwned>(
+    collection: Collection,
+    version: u32,
+    records: Vec<Value>,
+) -> Result<Vec<T>, Error> {
+    migrate(collection, version, records)?
+        .into_iter()
+        .map(|record| serde_json::from_value(record).map_err(Error::from))
+        .collect()
+}
+
+/// Convert a YAML value to JSON, turning YAML enum tags into single-key maps
+#[must_use]
+pub fn yaml_to_json(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else {
+                n.as_f64().map_or(Value::Null, Value::from)
+            }
+        }
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(seq) => {
+            Value::Array(seq.into_iter().map(yaml_to_json).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        serde_yaml::Value::String(s) => s,
+                        other => serde_yaml::to_string(&other)
+                            .unwrap_or_default()
+                            .trim()
+                            .to_string(),
+                    };
+                    (key, yaml_to_json(value))
+                })
+                .collect(),
+        ),
+        serde_yaml::Value::Tagged(tagged) => {
+            let mut map = Map::new();
+            map.insert(
+                tagged.tag.to_string().trim_start_matches('!').to_string(),
+                yaml_to_json(tagged.value),
+            );
+            Value::Object(map)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{EnforcementAction, EnforcementState, PendingEnforcement};
+
+    #[test]
+    fn test_migrations_are_contiguous() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.from, LEGACY_SCHEMA_VERSION + i as u32);
+        }
+        assert_eq!(
+            LEGACY_SCHEMA_VERSION + MIGRATIONS.len() as u32,
+            SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    fn test_legacy_enforcement_gets_state() {
+        let yaml = "
+- id: enf-id
+  warning_id: warn-id
+  user_id: 12345
+  guild_id: 11111
+  action: !VoiceMute
+    duration: 300
+  execute_at: 2023-01-02T00:00:00Z
+  created_at: 2023-01-01T00:00:00Z
+  executed: true
+";
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml).unwrap();
+        let serde_json::Value::Array(records) = yaml_to_json(value) else {
+            panic!("Expected a list of records");
+        };
+
+        let enforcements: Vec<PendingEnforcement> =
+            migrate_into(Collection::Enforcements, LEGACY_SCHEMA_VERSION, records).unwrap();
+        assert_eq!(enforcements[0].state, EnforcementState::Completed);
+        assert!(matches!(
+            enforcements[0].action,
+            EnforcementAction::VoiceMute {
+                duration: Some(300)
+            }
+        ));
+    }
+
+    #[test]
+    fn test_flattened_active_enforcement_is_restored() {
+        let mut record = Map::new();
+        record.insert("state".to_string(), Value::from("Completed"));
+        record.insert(
+            "reverse_at".to_string(),
+            Value::from("2023-01-02T00:05:00Z"),
+        );
+        record.insert("reversed_at".to_string(), Value::Null);
+        restore_active_enforcements(Collection::Enforcements, &mut record);
+        assert_eq!(record["state"], "Active");
+
+        // Reversed timed actions and one-time actions stay as they are
+        record.insert("state".to_string(), Value::from("Reversed"));
+        restore_active_enforcements(Collection::Enforcements, &mut record);
+        assert_eq!(record["state"], "Reversed");
+
+        record.insert("state".to_string(), Value::from("Completed"));
+        record.insert("reverse_at".to_string(), Value::Null);
+        restore_active_enforcements(Collection::Enforcements, &mut record);
+        assert_eq!(record["state"], "Completed");
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        assert!(migrate(Collection::Warnings, SCHEMA_VERSION + 1, Vec::new()).is_err());
+    }
+}
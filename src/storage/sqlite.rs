@@ -0,0 +1,476 @@
+use super::migrations::{self, Collection, LEGACY_SCHEMA_VERSION, SCHEMA_VERSION};
+use super::{ChangeSet, Corruption, Snapshot, Storage, user_state_key};
+use crate::Error;
+use crate::data::{GuildConfig, PendingEnforcement, UserWarningState, Warning};
+use poise::serenity_prelude as serenity;
+use rusqlite::{Connection, params};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS guild_configs (
+        guild_id INTEGER PRIMARY KEY,
+        body TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS warnings (
+        id TEXT PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS enforcements (
+        id TEXT PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS user_warning_states (
+        key TEXT PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS quarantine (
+        source TEXT NOT NULL,
+        key TEXT NOT NULL,
+        body TEXT NOT NULL,
+        error TEXT NOT NULL,
+        quarantined_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS warnings_by_user ON warnings (guild_id, user_id);
+    CREATE INDEX IF NOT EXISTS enforcements_by_user ON enforcements (guild_id, user_id);
+";
+
+/// Each table with its key column and the collection its rows belong to
+const TABLES: &[(&str, &str, Collection)] = &[
+    ("guild_configs", "guild_id", Collection::GuildConfigs),
+    ("warnings", "id", Collection::Warnings),
+    ("enforcements", "id", Collection::Enforcements),
+    ("user_warning_states", "key", Collection::UserWarningStates),
+];
+
+/// Storage backend keeping every record as a row in an embedded `SQLite` database
+///
+/// Records are stored as JSON in a `body` column next to the keys used for lookups,
+/// so each change is a single-row upsert or delete. The schema version of the
+/// bodies is kept in `PRAGMA user_version`. Rows that can't be read are copied
+/// into the `quarantine` table.
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at the given path
+    ///
+    /// # Errors
+    /// Returns an error if the database cannot be opened or the schema cannot be created.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        conn.execute_batch(SCHEMA)?;
+        migrate_bodies(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a closure against the connection on the blocking thread pool
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| Error::from("SQLite connection lock poisoned"))?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+#[serenity::async_trait]
+impl Storage for SqliteStorage {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn retained_copies(&self) -> Option<&'static str> {
+        Some(
+            "damaged rows kept in the database's `quarantine` table, and freed database pages until the database is vacuumed",
+        )
+    }
+
+    async fn load(&self) -> Result<Snapshot, Error> {
+        self.with_conn(|conn| {
+            let mut corruption = Vec::new();
+            Ok(Snapshot {
+                guild_configs: load_table(conn, "guild_configs", &mut corruption)?,
+                warnings: load_table(conn, "warnings", &mut corruption)?,
+                enforcements: load_table(conn, "enforcements", &mut corruption)?,
+                user_warning_states: load_table(conn, "user_warning_states", &mut corruption)?,
+                corruption,
+            })
+        })
+        .await
+    }
+
+    async fn repair(&self) -> Result<(), Error> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let bad_rows = [
+                read_table::<GuildConfig>(&tx, "guild_configs")?.bad_rows,
+                read_table::<Warning>(&tx, "warnings")?.bad_rows,
+                read_table::<PendingEnforcement>(&tx, "enforcements")?.bad_rows,
+                read_table::<UserWarningState>(&tx, "user_warning_states")?.bad_rows,
+            ];
+            for row in bad_rows.iter().flatten() {
+                tx.execute(
+                    &format!(
+                        "DELETE FROM {} WHERE CAST({} AS TEXT) = ?1",
+                        row.table,
+                        table_key(row.table)
+                    ),
+                    params![row.key],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    async fn apply(&self, changes: ChangeSet) -> Result<(), Error> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+
+            for config in &changes.guild_configs {
+                tx.execute(
+                    "INSERT OR REPLACE INTO guild_configs (guild_id, body) VALUES (?1, ?2)",
+                    params![config.guild_id as i64, serde_json::to_string(config)?],
+                )?;
+            }
+            for warning in &changes.warnings {
+                tx.execute(
+                    "INSERT OR REPLACE INTO warnings (id, guild_id, user_id, body) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        warning.id,
+                        warning.guild_id as i64,
+                        warning.user_id as i64,
+                        serde_json::to_string(warning)?
+                    ],
+                )?;
+            }
+            for id in &changes.removed_warnings {
+                tx.execute("DELETE FROM warnings WHERE id = ?1", params![id])?;
+            }
+            for enforcement in &changes.enforcements {
+                tx.execute(
+                    "INSERT OR REPLACE INTO enforcements (id, guild_id, user_id, body) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        enforcement.id,
+                        enforcement.guild_id as i64,
+                        enforcement.user_id as i64,
+                        serde_json::to_string(enforcement)?
+                    ],
+                )?;
+            }
+            for id in &changes.removed_enforcements {
+                tx.execute("DELETE FROM enforcements WHERE id = ?1", params![id])?;
+            }
+            for state in &changes.user_warning_states {
+                tx.execute(
+                    "INSERT OR REPLACE INTO user_warning_states (key, guild_id, user_id, body) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        user_state_key(state.user_id, state.guild_id),
+                        state.guild_id as i64,
+                        state.user_id as i64,
+                        serde_json::to_string(state)?
+                    ],
+                )?;
+            }
+            for key in &changes.removed_user_warning_states {
+                tx.execute("DELETE FROM user_warning_states WHERE key = ?1", params![key])?;
+            }
+
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Upgrade every stored body to the current schema version
+fn migrate_bodies(conn: &mut Connection) -> Result<(), Error> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // Databases created before the schema was versioned report 0
+    let version = version.max(LEGACY_SCHEMA_VERSION);
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Database was written with schema version {version}, but this build only understands up to {SCHEMA_VERSION}"
+        )
+        .into());
+    }
+
+    let tx = conn.transaction()?;
+    for &(table, key, collection) in TABLES {
+        let rows = {
+            let mut stmt = tx.prepare(&format!("SELECT {key}, body FROM {table}"))?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, rusqlite::types::Value>(0)?,
+                    row.get::<_, String>(1)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+        if rows.is_empty() {
+            continue;
+        }
+
+        for (key_value, body) in rows {
+            // Unreadable rows are left alone here and quarantined when loaded
+            let Ok(body) = serde_json::from_str::<serde_json::Value>(&body) else {
+                continue;
+            };
+            let Ok(migrated) = migrations::migrate(collection, version, vec![body]) else {
+                continue;
+            };
+            for body in migrated {
+                tx.execute(
+                    &format!("UPDATE {table} SET body = ?1 WHERE {key} = ?2"),
+                    params![body.to_string(), key_value],
+                )?;
+            }
+        }
+    }
+    tx.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))?;
+    tx.commit()?;
+
+    tracing::info!("Migrated database from schema version {version} to {SCHEMA_VERSION}");
+    Ok(())
+}
+
+/// Name of the key column of a table
+fn table_key(table: &str) -> &'static str {
+    TABLES
+        .iter()
+        .find(|(name, _, _)| *name == table)
+        .map_or("id", |(_, key, _)| key)
+}
+
+/// A row whose body couldn't be read
+struct BadRow {
+    table: &'static str,
+    key: String,
+    body: String,
+    error: String,
+}
+
+/// The readable records of a table, and the rows that weren't
+struct Table<T> {
+    table: &'static str,
+    records: Vec<T>,
+    bad_rows: Vec<BadRow>,
+}
+
+/// Deserialize every `body` in a table, setting aside the ones that can't be read
+fn read_table<T: DeserializeOwned>(
+    conn: &Connection,
+    table: &'static str,
+) -> Result<Table<T>, Error> {
+    let key = table_key(table);
+    let mut stmt = conn.prepare(&format!("SELECT CAST({key} AS TEXT), body FROM {table}"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut records = Vec::with_capacity(rows.len());
+    let mut bad_rows = Vec::new();
+    for (key, body) in rows {
+        match serde_json::from_str(&body) {
+            Ok(record) => records.push(record),
+            Err(e) => bad_rows.push(BadRow {
+                table,
+                key,
+                body,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(Table {
+        table,
+        records,
+        bad_rows,
+    })
+}
+
+/// Read a table's records, quarantining and reporting the rows that can't be read
+fn load_table<T: DeserializeOwned>(
+    conn: &Connection,
+    table: &'static str,
+    corruption: &mut Vec<Corruption>,
+) -> Result<Vec<T>, Error> {
+    let table = read_table(conn, table)?;
+    corruption.extend(quarantine_rows(conn, &table)?);
+    Ok(table.records)
+}
+
+/// Copy a table's unreadable rows into the quarantine table and describe the damage
+fn quarantine_rows<T>(conn: &Connection, table: &Table<T>) -> Result<Option<Corruption>, Error> {
+    let Some(first) = table.bad_rows.first() else {
+        return Ok(None);
+    };
+
+    let now = chrono::Utc::now().to_rfc3339();
+    for row in &table.bad_rows {
+        error!(
+            "Row {} of the {} table is damaged: {}",
+            row.key, row.table, row.error
+        );
+        conn.execute(
+            "INSERT INTO quarantine (source, key, body, error, quarantined_at)
+             SELECT ?1, ?2, ?3, ?4, ?5
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM quarantine WHERE source = ?1 AND key = ?2 AND body = ?3
+             )",
+            params![row.table, row.key, row.body, row.error, now],
+        )?;
+    }
+
+    Ok(Some(Corruption {
+        source: format!("{} table", table.table),
+        error: format!("row {}: {}", first.key, first.error),
+        salvaged: table.records.len(),
+        lost: Some(table.bad_rows.len()),
+        quarantined_to: Some("quarantine table".to_string()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{EnforcementAction, EnforcementState};
+
+    fn enforcement(id: &str, state: EnforcementState) -> PendingEnforcement {
+        PendingEnforcement::test(
+            id,
+            EnforcementAction::VoiceMute {
+                duration: Some(300),
+            },
+            state,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_upsert_and_remove() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        let storage = SqliteStorage::with_connection(conn).expect("Failed to create schema");
+
+        let changes = ChangeSet {
+            enforcements: vec![
+                enforcement("a", EnforcementState::Pending),
+                enforcement("b", EnforcementState::Pending),
+            ],
+            ..Default::default()
+        };
+        storage.apply(changes).await.expect("Failed to apply");
+
+        let changes = ChangeSet {
+            enforcements: vec![enforcement("a", EnforcementState::Active)],
+            removed_enforcements: vec!["b".to_string()],
+            ..Default::default()
+        };
+        storage.apply(changes).await.expect("Failed to apply");
+
+        let snapshot = storage.load().await.expect("Failed to load");
+        assert_eq!(snapshot.enforcements.len(), 1);
+        assert_eq!(snapshot.enforcements[0].id, "a");
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Active);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_migrates_unversioned_database() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        conn.execute_batch(SCHEMA).expect("Failed to create schema");
+
+        // A body from before enforcement state was persisted
+        let mut body = serde_json::to_value(enforcement("a", EnforcementState::Pending)).unwrap();
+        body.as_object_mut().unwrap().remove("state");
+        body["executed"] = serde_json::Value::Bool(true);
+        conn.execute(
+            "INSERT INTO enforcements (id, guild_id, user_id, body) VALUES ('a', 11111, 12345, ?1)",
+            params![body.to_string()],
+        )
+        .unwrap();
+
+        let storage = SqliteStorage::with_connection(conn).expect("Failed to migrate");
+        let snapshot = storage.load().await.expect("Failed to load");
+        assert_eq!(snapshot.enforcements[0].state, EnforcementState::Completed);
+
+        let version: u32 = storage
+            .with_conn(|conn| Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_quarantines_damaged_rows() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        let storage = SqliteStorage::with_connection(conn).expect("Failed to create schema");
+        let changes = ChangeSet {
+            enforcements: vec![enforcement("a", EnforcementState::Pending)],
+            ..Default::default()
+        };
+        storage.apply(changes).await.expect("Failed to apply");
+        storage
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO enforcements (id, guild_id, user_id, body) VALUES ('b', 1, 2, '{not json')",
+                    [],
+                )?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        let snapshot = storage.load().await.expect("Failed to load");
+        assert_eq!(snapshot.enforcements.len(), 1);
+        assert_eq!(snapshot.corruption.len(), 1);
+        assert_eq!(snapshot.corruption[0].lost, Some(1));
+
+        storage.repair().await.expect("Failed to repair");
+        let snapshot = storage.load().await.expect("Failed to load");
+        assert!(snapshot.corruption.is_empty());
+
+        let quarantined: u32 = storage
+            .with_conn(|conn| {
+                Ok(conn.query_row("SELECT COUNT(*) FROM quarantine", [], |row| row.get(0))?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(quarantined, 1);
+    }
+}
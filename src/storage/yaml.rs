@@ -0,0 +1,459 @@
+use super::atomic::{self, DEFAULT_BACKUP_GENERATIONS};
+use super::migrations::{self, Collection, LEGACY_SCHEMA_VERSION, SCHEMA_VERSION};
+use super::{ChangeSet, Corruption, Records, Snapshot, Storage, user_state_key};
+use crate::Error;
+use crate::data::{GuildConfig, PendingEnforcement, UserWarningState, Warning};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+const CONFIG_FILE: &str = "bot_config.yaml";
+const WARNINGS_FILE: &str = "warnings.yaml";
+const ENFORCEMENTS_FILE: &str = "enforcements.yaml";
+const WARNING_STATES_FILE: &str = "warning_states.yaml";
+
+/// Layout of a collection file: the schema version it was written with and its records
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    schema_version: u32,
+    records: Vec<&'a T>,
+}
+
+/// An envelope read back before its records are migrated
+#[derive(Deserialize)]
+struct RawEnvelope {
+    schema_version: u32,
+    records: Vec<serde_yaml::Value>,
+}
+
+/// In-memory copy of what is on disk, so a change to one record only
+/// rewrites the file of the collection it belongs to
+#[derive(Default)]
+struct Mirror {
+    loaded: bool,
+    records: Records,
+}
+
+/// Storage backend keeping one YAML file per collection
+///
+/// Files are replaced atomically and the previous versions of each file are
+/// kept as numbered backups, which `load` falls back to if a file is missing or
+/// can't be salvaged.
+pub struct YamlStorage {
+    dir: PathBuf,
+    backup_generations: usize,
+    mirror: Mutex<Mirror>,
+}
+
+impl YamlStorage {
+    /// Create a YAML backend rooted at the given directory
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_backup_generations(dir, DEFAULT_BACKUP_GENERATIONS)
+    }
+
+    /// Create a YAML backend that keeps the given number of backups per file
+    #[must_use]
+    pub fn with_backup_generations(dir: impl Into<PathBuf>, backup_generations: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            backup_generations,
+            mirror: Mutex::new(Mirror::default()),
+        }
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    /// Read every file from disk into the mirror, returning the damage found
+    async fn read_all(&self, mirror: &mut Mirror) -> Result<Vec<Corruption>, Error> {
+        let mut corruption = Vec::new();
+        let records = &mut mirror.records;
+
+        let (configs, damage) = self
+            .read_records::<GuildConfig>(CONFIG_FILE, Collection::GuildConfigs)
+            .await?;
+        records.guild_configs = configs
+            .into_iter()
+            .map(|config| (config.guild_id, config))
+            .collect();
+        corruption.extend(damage);
+
+        let (warnings, damage) = self
+            .read_records::<Warning>(WARNINGS_FILE, Collection::Warnings)
+            .await?;
+        records.warnings = warnings
+            .into_iter()
+            .map(|warning| (warning.id.clone(), warning))
+            .collect();
+        corruption.extend(damage);
+
+        let (enforcements, damage) = self
+            .read_records::<PendingEnforcement>(ENFORCEMENTS_FILE, Collection::Enforcements)
+            .await?;
+        records.enforcements = enforcements
+            .into_iter()
+            .map(|enforcement| (enforcement.id.clone(), enforcement))
+            .collect();
+        corruption.extend(damage);
+
+        let (states, damage) = self
+            .read_records::<UserWarningState>(WARNING_STATES_FILE, Collection::UserWarningStates)
+            .await?;
+        records.user_warning_states = states
+            .into_iter()
+            .map(|state| (user_state_key(state.user_id, state.guild_id), state))
+            .collect();
+        corruption.extend(damage);
+
+        mirror.loaded = true;
+        Ok(corruption)
+    }
+
+    /// Read a list of records from a YAML file
+    ///
+    /// A missing file falls back to its newest backup, since a crash between
+    /// rotating the backups and renaming the new file into place leaves only the
+    /// backups behind. A damaged file is moved into quarantine and every record
+    /// that still parses is kept; if the file can't be parsed at all, the newest
+    /// backup that can is used instead. Until the file is written again, later
+    /// loads read the quarantined copy and report the same damage.
+    async fn read_records<T: DeserializeOwned>(
+        &self,
+        file: &str,
+        collection: Collection,
+    ) -> Result<(Vec<T>, Option<Corruption>), Error> {
+        let path = self.path(file);
+        let parse = |contents: &str| parse_collection::<T>(collection, contents);
+
+        let (contents, quarantined_to) = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => (contents, None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(quarantined) = atomic::unrepaired_quarantine(&path).await {
+                    warn!(
+                        "{} is still in quarantine at {}",
+                        path.display(),
+                        quarantined.display()
+                    );
+                    let contents = tokio::fs::read_to_string(&quarantined).await?;
+                    (contents, Some(quarantined))
+                } else {
+                    let backup =
+                        atomic::read_newest_backup(&path, self.backup_generations, parse).await;
+                    let Some((backup, records)) = backup else {
+                        return Ok((Vec::new(), None));
+                    };
+                    info!(
+                        "Recovered {} from backup {}",
+                        path.display(),
+                        backup.display()
+                    );
+                    return Ok((records, None));
+                }
+            }
+            Err(e) => return Err(format!("Failed to read {}: {e}", path.display()).into()),
+        };
+
+        let error = match parse(&contents) {
+            Ok(records) => return Ok((records, None)),
+            Err(e) => e,
+        };
+        error!("{} is damaged: {error}", path.display());
+
+        let quarantined_to = match quarantined_to {
+            Some(quarantined) => quarantined,
+            None => atomic::quarantine(&path).await?,
+        };
+        let (records, lost) = match salvage_collection::<T>(collection, &contents) {
+            Some((records, lost)) => (records, Some(lost)),
+            None => {
+                let backup =
+                    atomic::read_newest_backup(&path, self.backup_generations, parse).await;
+                match backup {
+                    Some((backup, records)) => {
+                        warn!(
+                            "Salvaged {} from backup {}",
+                            path.display(),
+                            backup.display()
+                        );
+                        (records, None)
+                    }
+                    None => (Vec::new(), None),
+                }
+            }
+        };
+
+        let corruption = Corruption {
+            source: path.display().to_string(),
+            error: error.to_string(),
+            salvaged: records.len(),
+            lost,
+            quarantined_to: Some(quarantined_to.display().to_string()),
+        };
+        Ok((records, Some(corruption)))
+    }
+
+    /// Serialize a list of records to a YAML file
+    async fn write_records<'a, T>(
+        &self,
+        file: &str,
+        records: impl Iterator<Item = &'a T>,
+    ) -> Result<(), Error>
+    where
+        T: Serialize + 'a,
+    {
+        let envelope = Envelope {
+            schema_version: SCHEMA_VERSION,
+            records: records.collect(),
+        };
+        let yaml = serde_yaml::to_string(&envelope)?;
+        atomic::write_atomic(&self.path(file), yaml.as_bytes(), self.backup_generations).await
+    }
+}
+
+/// Split a collection file into its schema version and raw records
+fn read_envelope(contents: &str) -> Result<(u32, Vec<serde_yaml::Value>), Error> {
+    match serde_yaml::from_str::<serde_yaml::Value>(contents)? {
+        // Files written before the schema was versioned are a bare list
+        serde_yaml::Value::Sequence(records) => Ok((LEGACY_SCHEMA_VERSION, records)),
+        value @ serde_yaml::Value::Mapping(_) => {
+            let envelope: RawEnvelope = serde_yaml::from_value(value)?;
+            Ok((envelope.schema_version, envelope.records))
+        }
+        _ => Err("expected a list of records or a versioned envelope".into()),
+    }
+}
+
+/// Parse a collection file, upgrading records written with an older schema
+fn parse_collection<T: DeserializeOwned>(
+    collection: Collection,
+    contents: &str,
+) -> Result<Vec<T>, Error> {
+    let (version, records) = read_envelope(contents)?;
+    if version < SCHEMA_VERSION {
+        info!("Migrating {collection:?} from schema version {version} to {SCHEMA_VERSION}");
+    }
+
+    let records = records.into_iter().map(migrations::yaml_to_json).collect();
+    migrations::migrate_into(collection, version, records)
+}
+
+/// Parse every record of a damaged collection file that can still be read on its own
+///
+/// Returns the records along with how many were lost, or `None` if the file
+/// isn't readable as a list of records at all.
+fn salvage_collection<T: DeserializeOwned>(
+    collection: Collection,
+    contents: &str,
+) -> Option<(Vec<T>, usize)> {
+    let (version, raw_records) = read_envelope(contents).ok()?;
+
+    let mut records = Vec::new();
+    let mut lost = 0;
+    for (i, record) in raw_records.into_iter().enumerate() {
+        let record = migrations::yaml_to_json(record);
+        match migrations::migrate_into(collection, version, vec![record]) {
+            Ok(parsed) => records.extend(parsed),
+            Err(e) => {
+                warn!("Dropping unreadable {collection:?} record #{}: {e}", i + 1);
+                lost += 1;
+            }
+        }
+    }
+    Some((records, lost))
+}
+
+#[serenity::async_trait]
+impl Storage for YamlStorage {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn retained_copies(&self) -> Option<&'static str> {
+        Some(
+            "the YAML backups (`warnings.yaml.1`, `warnings.yaml.2`, ...) until they rotate out, and any damaged files moved into the `quarantine` directory",
+        )
+    }
+
+    async fn load(&self) -> Result<Snapshot, Error> {
+        let mut mirror = self.mirror.lock().await;
+        let corruption = self.read_all(&mut mirror).await?;
+
+        Ok(Snapshot {
+            corruption,
+            ..mirror.records.to_snapshot()
+        })
+    }
+
+    async fn repair(&self) -> Result<(), Error> {
+        let mirror = self.mirror.lock().await;
+        if !mirror.loaded {
+            return Err("Cannot repair data that hasn't been loaded".into());
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let records = &mirror.records;
+        self.write_records(CONFIG_FILE, records.guild_configs.values())
+            .await?;
+        self.write_records(WARNINGS_FILE, records.warnings.values())
+            .await?;
+        self.write_records(ENFORCEMENTS_FILE, records.enforcements.values())
+            .await?;
+        self.write_records(WARNING_STATES_FILE, records.user_warning_states.values())
+            .await
+    }
+
+    async fn apply(&self, changes: ChangeSet) -> Result<(), Error> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut mirror = self.mirror.lock().await;
+        // Never overwrite files we haven't read yet
+        if !mirror.loaded {
+            let corruption = self.read_all(&mut mirror).await?;
+            if !corruption.is_empty() {
+                return Err("Refusing to write over damaged data that was never loaded".into());
+            }
+        }
+
+        let configs_changed = !changes.guild_configs.is_empty();
+        let warnings_changed = !changes.warnings.is_empty() || !changes.removed_warnings.is_empty();
+        let enforcements_changed =
+            !changes.enforcements.is_empty() || !changes.removed_enforcements.is_empty();
+        let states_changed = !changes.user_warning_states.is_empty()
+            || !changes.removed_user_warning_states.is_empty();
+
+        mirror.records.apply(changes);
+
+        // Create the data directory if it doesn't exist
+        if !self.dir.exists() {
+            tokio::fs::create_dir_all(&self.dir).await?;
+        }
+
+        if configs_changed {
+            self.write_records(CONFIG_FILE, mirror.records.guild_configs.values())
+                .await?;
+        }
+        if warnings_changed {
+            self.write_records(WARNINGS_FILE, mirror.records.warnings.values())
+                .await?;
+        }
+        if enforcements_changed {
+            self.write_records(ENFORCEMENTS_FILE, mirror.records.enforcements.values())
+                .await?;
+        }
+        if states_changed {
+            self.write_records(
+                WARNING_STATES_FILE,
+                mirror.records.user_warning_states.values(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_yaml_apply_only_rewrites_changed_collection() {
+        let dir = std::env::temp_dir().join(format!("daemon-yaml-{}", uuid::Uuid::new_v4()));
+        let storage = YamlStorage::new(&dir);
+
+        let changes = ChangeSet {
+            guild_configs: vec![GuildConfig::new(42)],
+            ..Default::default()
+        };
+        storage.apply(changes).await.expect("Failed to apply");
+
+        assert!(dir.join(CONFIG_FILE).exists());
+        assert!(!dir.join(WARNINGS_FILE).exists());
+
+        // A fresh backend sees the record that was written
+        let snapshot = YamlStorage::new(&dir).load().await.expect("Failed to load");
+        assert_eq!(snapshot.guild_configs.len(), 1);
+        assert_eq!(snapshot.guild_configs[0].guild_id, 42);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_legacy_and_versioned_files() {
+        let mut config = GuildConfig::new(42);
+        config.enforcement_log_channel_id = Some(7);
+
+        // Files written before versioning are a bare list
+        let legacy = serde_yaml::to_string(&vec![&config]).unwrap();
+        let configs: Vec<GuildConfig> =
+            parse_collection(Collection::GuildConfigs, &legacy).unwrap();
+        assert_eq!(configs[0].enforcement_log_channel_id, Some(7));
+
+        let envelope = Envelope {
+            schema_version: SCHEMA_VERSION,
+            records: vec![&config],
+        };
+        let current = serde_yaml::to_string(&envelope).unwrap();
+        let configs: Vec<GuildConfig> =
+            parse_collection(Collection::GuildConfigs, &current).unwrap();
+        assert_eq!(configs[0].guild_id, 42);
+
+        let future = current.replace(
+            &format!("schema_version: {SCHEMA_VERSION}"),
+            &format!("schema_version: {}", SCHEMA_VERSION + 1),
+        );
+        assert!(parse_collection::<GuildConfig>(Collection::GuildConfigs, &future).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_damaged_file_is_quarantined_and_salvaged() {
+        let dir = std::env::temp_dir().join(format!("daemon-yaml-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The second record is missing required fields
+        let damaged = format!(
+            "schema_version: {SCHEMA_VERSION}\nrecords:\n{}- guild_id: 43\n",
+            serde_yaml::to_string(&vec![GuildConfig::new(42)]).unwrap()
+        );
+        std::fs::write(dir.join(CONFIG_FILE), &damaged).unwrap();
+
+        let storage = YamlStorage::new(&dir);
+        let snapshot = storage.load().await.expect("Failed to load");
+        assert_eq!(snapshot.guild_configs.len(), 1);
+        assert_eq!(snapshot.corruption.len(), 1);
+        assert_eq!(snapshot.corruption[0].lost, Some(1));
+
+        // The damaged file is moved into quarantine as it was
+        assert!(!dir.join(CONFIG_FILE).exists());
+        let quarantined = snapshot.corruption[0].quarantined_to.clone().unwrap();
+        assert_eq!(std::fs::read_to_string(&quarantined).unwrap(), damaged);
+
+        // and reported again by later loads until it is repaired, without more copies
+        let snapshot = YamlStorage::new(&dir).load().await.expect("Failed to load");
+        assert_eq!(snapshot.guild_configs.len(), 1);
+        assert_eq!(
+            snapshot.corruption[0].quarantined_to.as_deref(),
+            Some(quarantined.as_str())
+        );
+        assert_eq!(
+            std::fs::read_dir(dir.join(atomic::QUARANTINE_DIR))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        // Repairing writes back what was salvaged
+        storage.repair().await.expect("Failed to repair");
+        let snapshot = YamlStorage::new(&dir).load().await.expect("Failed to load");
+        assert_eq!(snapshot.guild_configs.len(), 1);
+        assert!(snapshot.corruption.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
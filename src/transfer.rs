@@ -0,0 +1,615 @@
+//! Exporting and importing a guild's moderation records
+//!
+//! An export holds everything stored for one guild: its config, warnings,
+//! pending, active and completed enforcements, and user warning states. JSON
+//! exports are a single document. CSV exports have one row per record, with a
+//! few readable columns for spreadsheets and the full record as JSON in the
+//! `data` column, so both formats import back without losing anything.
+
+use crate::Error;
+use crate::data::{
+    Data, EnforcementState, GuildConfig, PendingEnforcement, UserWarningState, Warning,
+};
+use crate::storage::migrations::{self, Collection, SCHEMA_VERSION};
+use crate::storage::user_state_key;
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Version of the export layout, independent of the record schema
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// File formats an export can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFormat {
+    Json,
+    Csv,
+}
+
+impl TransferFormat {
+    /// Parse a format name (`json` or `csv`)
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from a file name's extension
+    #[must_use]
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        name.rsplit_once('.')
+            .and_then(|(_, extension)| Self::parse(extension))
+    }
+
+    /// File extension for the format
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// Everything stored for one guild
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildExport {
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub guild_id: u64,
+    pub exported_at: DateTime<Utc>,
+    pub guild_config: Option<GuildConfig>,
+    pub warnings: Vec<Warning>,
+    pub pending_enforcements: Vec<PendingEnforcement>,
+    pub active_enforcements: Vec<PendingEnforcement>,
+    pub completed_enforcements: Vec<PendingEnforcement>,
+    pub user_warning_states: Vec<UserWarningState>,
+}
+
+/// One row of a CSV export
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    record_type: String,
+    id: String,
+    guild_id: u64,
+    user_id: Option<u64>,
+    timestamp: Option<DateTime<Utc>>,
+    state: Option<String>,
+    summary: String,
+    schema_version: u32,
+    /// The full record as JSON
+    data: String,
+}
+
+const GUILD_CONFIG_RECORD: &str = "guild_config";
+const WARNING_RECORD: &str = "warning";
+const ENFORCEMENT_RECORD: &str = "enforcement";
+const USER_WARNING_STATE_RECORD: &str = "user_warning_state";
+
+/// What an import did
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Records that didn't exist yet
+    pub added: usize,
+    /// Records that already existed with the same contents
+    pub unchanged: usize,
+    /// Existing records replaced because `overwrite` was set
+    pub replaced: usize,
+    /// Existing records that differ from the imported ones
+    pub conflicts: Vec<String>,
+}
+
+impl GuildExport {
+    /// Collect every record stored for a guild
+    #[must_use]
+    pub fn from_data(data: &Data, guild_id: u64) -> Self {
+        let enforcements = |map: &dashmap::DashMap<String, PendingEnforcement>| {
+            let mut enforcements: Vec<PendingEnforcement> = map
+                .iter()
+                .filter(|entry| entry.guild_id == guild_id)
+                .map(|entry| entry.value().clone())
+                .collect();
+            enforcements.sort_by_key(|enforcement| enforcement.created_at);
+            enforcements
+        };
+
+        let mut warnings: Vec<Warning> = data
+            .warnings
+            .iter()
+            .filter(|entry| entry.guild_id == guild_id)
+            .map(|entry| entry.value().clone())
+            .collect();
+        warnings.sort_by_key(|warning| warning.timestamp);
+
+        let mut user_warning_states: Vec<UserWarningState> = data
+            .user_warning_states
+            .iter()
+            .filter(|entry| entry.guild_id == guild_id)
+            .map(|entry| entry.value().clone())
+            .collect();
+        user_warning_states.sort_by_key(|state| state.user_id);
+
+        Self {
+            format_version: EXPORT_FORMAT_VERSION,
+            schema_version: SCHEMA_VERSION,
+            guild_id,
+            exported_at: Utc::now(),
+            guild_config: data
+                .guild_configs
+                .get(&serenity::GuildId::new(guild_id))
+                .map(|entry| entry.value().clone()),
+            warnings,
+            pending_enforcements: enforcements(&data.pending_enforcements),
+            active_enforcements: enforcements(&data.active_enforcements),
+            completed_enforcements: enforcements(&data.completed_enforcements),
+            user_warning_states,
+        }
+    }
+
+    /// Total number of records in the export
+    #[must_use]
+    pub fn record_count(&self) -> usize {
+        usize::from(self.guild_config.is_some())
+            + self.warnings.len()
+            + self.enforcements().count()
+            + self.user_warning_states.len()
+    }
+
+    /// Every enforcement, whatever its state
+    pub fn enforcements(&self) -> impl Iterator<Item = &PendingEnforcement> {
+        self.pending_enforcements
+            .iter()
+            .chain(&self.active_enforcements)
+            .chain(&self.completed_enforcements)
+    }
+
+    /// Write the export in the given format
+    ///
+    /// # Errors
+    /// Returns an error if a record can't be serialized.
+    pub fn to_bytes(&self, format: TransferFormat) -> Result<Vec<u8>, Error> {
+        match format {
+            TransferFormat::Json => Ok(serde_json::to_vec_pretty(self)?),
+            TransferFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// Read an export in the given format, upgrading records from older schemas
+    ///
+    /// # Errors
+    /// Returns an error if the file isn't a readable export.
+    pub fn from_bytes(bytes: &[u8], format: TransferFormat) -> Result<Self, Error> {
+        match format {
+            TransferFormat::Json => Self::from_json(bytes),
+            TransferFormat::Csv => Self::from_csv(bytes),
+        }
+    }
+
+    fn from_json(bytes: &[u8]) -> Result<Self, Error> {
+        let mut value: Value = serde_json::from_slice(bytes)?;
+        let version = value
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or("Export has no schema_version")?;
+
+        if version != SCHEMA_VERSION {
+            let fields = [
+                ("warnings", Collection::Warnings),
+                ("pending_enforcements", Collection::Enforcements),
+                ("active_enforcements", Collection::Enforcements),
+                ("completed_enforcements", Collection::Enforcements),
+                ("user_warning_states", Collection::UserWarningStates),
+            ];
+            for (field, collection) in fields {
+                let Some(records) = value.get_mut(field) else {
+                    continue;
+                };
+                let Value::Array(list) = records.take() else {
+                    return Err(format!("{field} is not a list").into());
+                };
+                *records = Value::Array(migrations::migrate(collection, version, list)?);
+            }
+            if let Some(config) = value.get_mut("guild_config").filter(|c| !c.is_null()) {
+                let migrated =
+                    migrations::migrate(Collection::GuildConfigs, version, vec![config.take()])?;
+                *config = migrated.into_iter().next().unwrap_or_default();
+            }
+            value["schema_version"] = Value::from(SCHEMA_VERSION);
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn to_csv(&self) -> Result<Vec<u8>, Error> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        if let Some(config) = &self.guild_config {
+            writer.serialize(CsvRow {
+                record_type: GUILD_CONFIG_RECORD.to_string(),
+                id: config.guild_id.to_string(),
+                guild_id: config.guild_id,
+                user_id: None,
+                timestamp: None,
+                state: None,
+                summary: format!(
+                    "chaos {:.2}, altar {}",
+                    config.chaos_factor,
+                    config
+                        .enforcement_log_channel_id
+                        .map_or("none".to_string(), |id| id.to_string())
+                ),
+                schema_version: SCHEMA_VERSION,
+                data: serde_json::to_string(config)?,
+            })?;
+        }
+        for warning in &self.warnings {
+            writer.serialize(CsvRow {
+                record_type: WARNING_RECORD.to_string(),
+                id: warning.id.clone(),
+                guild_id: warning.guild_id,
+                user_id: Some(warning.user_id),
+                timestamp: Some(warning.timestamp),
+                state: None,
+                summary: warning.reason.clone(),
+                schema_version: SCHEMA_VERSION,
+                data: serde_json::to_string(warning)?,
+            })?;
+        }
+        for enforcement in self.enforcements() {
+            writer.serialize(CsvRow {
+                record_type: ENFORCEMENT_RECORD.to_string(),
+                id: enforcement.id.clone(),
+                guild_id: enforcement.guild_id,
+                user_id: Some(enforcement.user_id),
+                timestamp: Some(enforcement.created_at),
+                state: Some(format!("{:?}", enforcement.state)),
+                summary: enforcement.action.to_string(),
+                schema_version: SCHEMA_VERSION,
+                data: serde_json::to_string(enforcement)?,
+            })?;
+        }
+        for state in &self.user_warning_states {
+            writer.serialize(CsvRow {
+                record_type: USER_WARNING_STATE_RECORD.to_string(),
+                id: user_state_key(state.user_id, state.guild_id),
+                guild_id: state.guild_id,
+                user_id: Some(state.user_id),
+                timestamp: Some(state.last_updated),
+                state: None,
+                summary: format!("{} warnings", state.warning_timestamps.len()),
+                schema_version: SCHEMA_VERSION,
+                data: serde_json::to_string(state)?,
+            })?;
+        }
+
+        Ok(writer.into_inner().map_err(|e| e.to_string())?)
+    }
+
+    fn from_csv(bytes: &[u8]) -> Result<Self, Error> {
+        fn parse<T: serde::de::DeserializeOwned>(
+            row: &CsvRow,
+            collection: Collection,
+        ) -> Result<T, Error> {
+            let record: Value = serde_json::from_str(&row.data)?;
+            migrations::migrate_into(collection, row.schema_version, vec![record])?
+                .pop()
+                .ok_or_else(|| "empty record".into())
+        }
+
+        let mut export = Self {
+            format_version: EXPORT_FORMAT_VERSION,
+            schema_version: SCHEMA_VERSION,
+            guild_id: 0,
+            exported_at: Utc::now(),
+            guild_config: None,
+            warnings: Vec::new(),
+            pending_enforcements: Vec::new(),
+            active_enforcements: Vec::new(),
+            completed_enforcements: Vec::new(),
+            user_warning_states: Vec::new(),
+        };
+
+        let mut reader = csv::Reader::from_reader(bytes);
+        for (i, row) in reader.deserialize::<CsvRow>().enumerate() {
+            // Line 1 is the header
+            let line = i + 2;
+            let row = row.map_err(|e| format!("line {line}: {e}"))?;
+            if export.guild_id == 0 {
+                export.guild_id = row.guild_id;
+            }
+
+            let result = match row.record_type.as_str() {
+                GUILD_CONFIG_RECORD => parse(&row, Collection::GuildConfigs)
+                    .map(|config| export.guild_config = Some(config)),
+                WARNING_RECORD => {
+                    parse(&row, Collection::Warnings).map(|warning| export.warnings.push(warning))
+                }
+                ENFORCEMENT_RECORD => parse::<PendingEnforcement>(&row, Collection::Enforcements)
+                    .map(|enforcement| match enforcement.state {
+                        EnforcementState::Pending => export.pending_enforcements.push(enforcement),
+                        EnforcementState::Active => export.active_enforcements.push(enforcement),
+                        _ => export.completed_enforcements.push(enforcement),
+                    }),
+                USER_WARNING_STATE_RECORD => parse(&row, Collection::UserWarningStates)
+                    .map(|state| export.user_warning_states.push(state)),
+                other => Err(format!("unknown record type {other}").into()),
+            };
+            result.map_err(|e| format!("line {line}: {e}"))?;
+        }
+
+        Ok(export)
+    }
+
+    /// Check that the export can be imported into the given guild
+    ///
+    /// Returns a description of every problem found.
+    #[must_use]
+    pub fn validate(&self, guild_id: u64) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.format_version > EXPORT_FORMAT_VERSION {
+            problems.push(format!(
+                "Export format {} is newer than this bot understands ({EXPORT_FORMAT_VERSION})",
+                self.format_version
+            ));
+        }
+        if self.guild_id != guild_id {
+            problems.push(format!(
+                "Export is for guild {}, not this guild ({guild_id})",
+                self.guild_id
+            ));
+        }
+
+        let mut check_guild = |kind: &str, id: &str, record_guild_id: u64| {
+            if record_guild_id != guild_id {
+                problems.push(format!("{kind} {id} belongs to guild {record_guild_id}"));
+            }
+        };
+        if let Some(config) = &self.guild_config {
+            check_guild(
+                "Guild config",
+                &config.guild_id.to_string(),
+                config.guild_id,
+            );
+        }
+        for warning in &self.warnings {
+            check_guild("Warning", &warning.id, warning.guild_id);
+        }
+        for enforcement in self.enforcements() {
+            check_guild("Enforcement", &enforcement.id, enforcement.guild_id);
+        }
+        for state in &self.user_warning_states {
+            check_guild(
+                "Warning state",
+                &user_state_key(state.user_id, state.guild_id),
+                state.guild_id,
+            );
+        }
+
+        let lists = [
+            ("pending_enforcements", &self.pending_enforcements),
+            ("active_enforcements", &self.active_enforcements),
+        ];
+        for (list, enforcements) in lists {
+            let expected = if list == "pending_enforcements" {
+                EnforcementState::Pending
+            } else {
+                EnforcementState::Active
+            };
+            for enforcement in enforcements.iter().filter(|e| e.state != expected) {
+                problems.push(format!(
+                    "Enforcement {} is {:?} but listed in {list}",
+                    enforcement.id, enforcement.state
+                ));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for id in self
+            .warnings
+            .iter()
+            .map(|warning| &warning.id)
+            .chain(self.enforcements().map(|enforcement| &enforcement.id))
+        {
+            if !seen.insert(id) {
+                problems.push(format!("ID {id} appears more than once"));
+            }
+        }
+        let mut seen = HashSet::new();
+        for state in &self.user_warning_states {
+            if !seen.insert(state.user_id) {
+                problems.push(format!(
+                    "User {} has more than one warning state",
+                    state.user_id
+                ));
+            }
+        }
+
+        problems
+    }
+
+    /// Merge the records into `data`
+    ///
+    /// New records are added. Existing records with different contents are
+    /// reported as conflicts and kept as they are, unless `overwrite` is set.
+    pub fn merge_into(self, data: &Data, overwrite: bool) -> ImportReport {
+        let mut report = ImportReport::default();
+
+        if let Some(config) = self.guild_config {
+            let existing = data
+                .guild_configs
+                .get(&serenity::GuildId::new(config.guild_id))
+                .map(|entry| entry.value().clone());
+            let description = format!("Guild config {}", config.guild_id);
+            if report.check(existing.as_ref(), &config, description, overwrite) {
+                data.set_guild_config(config);
+            }
+        }
+
+        for warning in self.warnings {
+            let existing = data
+                .warnings
+                .get(&warning.id)
+                .map(|entry| entry.value().clone());
+            let description = format!("Warning {}", warning.id);
+            if report.check(existing.as_ref(), &warning, description, overwrite) {
+                data.insert_warning(warning);
+            }
+        }
+
+        let enforcements = self
+            .pending_enforcements
+            .into_iter()
+            .chain(self.active_enforcements)
+            .chain(self.completed_enforcements);
+        for enforcement in enforcements {
+            let existing = data
+                .pending_enforcements
+                .get(&enforcement.id)
+                .or_else(|| data.active_enforcements.get(&enforcement.id))
+                .or_else(|| data.completed_enforcements.get(&enforcement.id))
+                .map(|entry| entry.value().clone());
+            let description = format!("Enforcement {}", enforcement.id);
+            if report.check(existing.as_ref(), &enforcement, description, overwrite) {
+                data.store_enforcement(enforcement);
+            }
+        }
+
+        for state in self.user_warning_states {
+            let key = user_state_key(state.user_id, state.guild_id);
+            let existing = data
+                .user_warning_states
+                .get(&key)
+                .map(|entry| entry.value().clone());
+            let description = format!("Warning state of user {}", state.user_id);
+            if report.check(existing.as_ref(), &state, description, overwrite) {
+                data.set_user_warning_state(state);
+            }
+        }
+
+        report
+    }
+}
+
+impl ImportReport {
+    /// Compare an imported record with the existing one, returning whether to store it
+    fn check<T: Serialize>(
+        &mut self,
+        existing: Option<&T>,
+        imported: &T,
+        description: String,
+        overwrite: bool,
+    ) -> bool {
+        let Some(existing) = existing else {
+            self.added += 1;
+            return true;
+        };
+
+        if serde_json::to_value(existing).ok() == serde_json::to_value(imported).ok() {
+            self.unchanged += 1;
+            false
+        } else if overwrite {
+            self.replaced += 1;
+            true
+        } else {
+            self.conflicts.push(description);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::EnforcementAction;
+
+    fn sample_data(guild_id: u64) -> Data {
+        let data = Data::new();
+        let mut config = GuildConfig::new(guild_id);
+        config.enforcement_log_channel_id = Some(7);
+        data.set_guild_config(config);
+        data.insert_warning(Warning {
+            id: "warn-id".to_string(),
+            user_id: 12345,
+            issuer_id: 67890,
+            guild_id,
+            reason: "Being loud, again".to_string(),
+            timestamp: Utc::now(),
+            notification_method: crate::data::NotificationMethod::DirectMessage,
+            enforcement: None,
+            severity: 3.0,
+            judgment: None,
+            category: None,
+        });
+        data.store_enforcement(PendingEnforcement {
+            warning_id: "warn-id".to_string(),
+            guild_id,
+            reverse_at: Some(Utc::now()),
+            ..PendingEnforcement::test(
+                "enf-id",
+                EnforcementAction::VoiceMute {
+                    duration: Some(300),
+                },
+                EnforcementState::Active,
+            )
+        });
+        let _ = data.add_to_user_warning_state(
+            12345,
+            guild_id,
+            "Being loud".to_string(),
+            67890,
+            3.0,
+            "voice",
+        );
+        data
+    }
+
+    #[test]
+    fn test_round_trip_in_both_formats() {
+        let export = GuildExport::from_data(&sample_data(11111), 11111);
+        assert_eq!(export.record_count(), 4);
+
+        for format in [TransferFormat::Json, TransferFormat::Csv] {
+            let bytes = export.to_bytes(format).unwrap();
+            let imported = GuildExport::from_bytes(&bytes, format).unwrap();
+            assert!(imported.validate(11111).is_empty());
+            assert_eq!(imported.active_enforcements.len(), 1);
+            assert_eq!(imported.warnings[0].reason, "Being loud, again");
+
+            let data = Data::new();
+            let report = imported.merge_into(&data, false);
+            assert_eq!(report.added, 4);
+            assert!(data.active_enforcements.contains_key("enf-id"));
+        }
+    }
+
+    #[test]
+    fn test_conflicts_and_validation() {
+        let data = sample_data(11111);
+        let mut export = GuildExport::from_data(&data, 11111);
+        export.warnings[0].reason = "Something else".to_string();
+
+        assert!(!export.validate(22222).is_empty());
+
+        let report = export.clone().merge_into(&data, false);
+        assert_eq!(report.unchanged, 3);
+        assert_eq!(report.conflicts, vec!["Warning warn-id".to_string()]);
+        assert_eq!(
+            data.warnings.get("warn-id").unwrap().reason,
+            "Being loud, again"
+        );
+
+        let report = export.merge_into(&data, true);
+        assert_eq!(report.replaced, 1);
+        assert_eq!(
+            data.warnings.get("warn-id").unwrap().reason,
+            "Something else"
+        );
+    }
+}